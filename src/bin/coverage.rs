@@ -0,0 +1,36 @@
+extern crate cem;
+
+use cem::trace::Coverage;
+use std::io::{self, BufReader};
+use std::fs::File;
+
+/// Prints a coverage map for each file given on the command line, or the number of bytes
+/// consumed by each field if `--totals` is passed.
+fn main() {
+	let mut totals = false;
+
+	for arg in ::std::env::args().skip(1) {
+		if arg == "--totals" {
+			totals = true;
+			continue;
+		}
+
+		let file = BufReader::new(File::open(&arg).unwrap());
+		let coverage = Coverage::trace(file).unwrap();
+		let unconsumed: u64 = coverage.unconsumed.iter().map(|range| range.end - range.start).sum();
+
+		println!("{}: {} bytes, {} unconsumed", arg, coverage.len, unconsumed);
+
+		if totals {
+			for (path, bytes) in coverage.totals() {
+				println!("  {:10} {}", bytes, path);
+			}
+
+			if let Some(ref error) = coverage.error {
+				println!("  tracing stopped: {}", error);
+			}
+		} else {
+			coverage.write_map(&mut io::stdout()).unwrap();
+		}
+	}
+}
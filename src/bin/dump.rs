@@ -0,0 +1,26 @@
+extern crate cem;
+
+use std::io::{self, Read};
+use std::fs::File;
+use std::process;
+
+/// Prints an annotated hex dump of the file given on the command line.
+fn main() {
+	let path = match ::std::env::args().nth(1) {
+		Some(path) => path,
+		None => {
+			eprintln!("usage: dump <file.cem>");
+			process::exit(2);
+		}
+	};
+
+	let mut data = Vec::new();
+	File::open(&path).and_then(|mut file| file.read_to_end(&mut data)).unwrap();
+
+	let stdout = io::stdout();
+	let coverage = cem::dump::dump(&data, &mut stdout.lock()).unwrap();
+
+	if coverage.error.is_some() {
+		process::exit(1);
+	}
+}
@@ -0,0 +1,46 @@
+extern crate cem;
+
+use cem::{Scene, V2};
+use cem::fingerprint::{Fingerprint, Options};
+use std::collections::BTreeMap;
+use std::io::BufReader;
+use std::fs::File;
+
+/// Fingerprints every V2 model given on the command line and prints the groups of files with identical content.
+/// Pass `--ignore-names` to ignore node, material and tag point names, and `--quantize=<step>` to round floats.
+fn main() {
+	let mut options = Options::default();
+	let mut paths = Vec::new();
+
+	// Options apply to every file wherever they appear, so they are all parsed before any file is hashed.
+	for arg in ::std::env::args().skip(1) {
+		if arg == "--ignore-names" {
+			options.ignore_names = true;
+		} else if let Some(step) = arg.strip_prefix("--quantize=") {
+			options.quantize = Some(step.parse().expect("quantization step must be a number"));
+		} else {
+			paths.push(arg);
+		}
+	}
+
+	let mut groups: BTreeMap<Fingerprint, Vec<String>> = BTreeMap::new();
+
+	for arg in paths {
+		let mut file = BufReader::new(File::open(&arg).unwrap());
+
+		match Scene::<V2>::read(&mut file) {
+			Ok(scene) => groups.entry(Fingerprint::of(&scene, options)).or_default().push(arg),
+			Err(e) => eprintln!("{}: {}", arg, e)
+		}
+	}
+
+	for (fingerprint, paths) in groups {
+		if paths.len() > 1 {
+			println!("{}", fingerprint);
+
+			for path in paths {
+				println!("  {}", path);
+			}
+		}
+	}
+}
@@ -1,6 +1,6 @@
 extern crate cem;
 
-use cem::{ModelHeader, Model, v1, V2, V5, Encode};
+use cem::{ModelHeader, Model, Scene, v1, V2, V5, Encode};
 use std::io::BufReader;
 
 // const PATH: &str = "/home/coderbot/Programming/Java/EmpireEarthReverse/extract/data/models";
@@ -18,7 +18,13 @@ fn main() {
 
 		if header == V2::HEADER {
 
-			let (model, _) = V2::read(&mut file).unwrap();
+			let mut scene = Scene::<V2>::read_without_header(&mut file).unwrap();
+			scene.read_trailing(&mut file).unwrap();
+			let model = &scene.model;
+
+			if scene.has_trailing() {
+				println!("  {:32} {} bytes of trailing data after the scene", name, scene.trailing.len());
+			}
 
 			for frame in &model.frames {
 				use cem::collider::ColliderBuilder;
@@ -0,0 +1,37 @@
+extern crate cem;
+
+use cem::recover::Recovered;
+use std::io::{BufReader, BufWriter};
+use std::fs::File;
+use std::process;
+
+/// Recovers what it can from a damaged V2 model, reports the problems found and writes the result to a new file.
+fn main() {
+	let args: Vec<String> = ::std::env::args().skip(1).collect();
+
+	if args.len() != 2 {
+		eprintln!("usage: salvage <damaged.cem> <recovered.cem>");
+		process::exit(2);
+	}
+
+	let recovered = Recovered::read(&mut BufReader::new(File::open(&args[0]).unwrap()));
+
+	for problem in &recovered.problems {
+		println!("{:08x} {}: {}", problem.offset, problem.context, problem.message);
+	}
+
+	println!("recovered {} of {} frames", recovered.model.frames.len(), recovered.expected_frames);
+
+	let scene = match recovered.into_scene() {
+		Ok(scene) => scene,
+		Err(e) => {
+			eprintln!("not enough of the model survived to write it back out: {}", e);
+			process::exit(1);
+		}
+	};
+
+	if let Err(e) = File::create(&args[1]).and_then(|file| scene.write(&mut BufWriter::new(file))) {
+		eprintln!("{}: {}", args[1], e);
+		process::exit(1);
+	}
+}
@@ -0,0 +1,95 @@
+extern crate cem;
+
+use cem::{Scene, V2};
+use cem::image::png;
+use cem::render::raster::RasterOptions;
+use cem::render::sheet::{self, Sequence};
+use cem::textures::Resolver;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::fs::File;
+
+/// Writes a contact sheet of every frame of each V2 model given on the command line to `<name>.sheet.png`.
+/// Pass `--turntable=<angles>` to turn the first frame around instead, `--size=<pixels>` to change the size of
+/// each cell, `--columns=<count>` to fix the number of columns, and `--animate` to also write the sequence as an
+/// animated PNG to `<name>.anim.png`, at the rate given by `--fps=<rate>`. Textures are taken from the data
+/// directories given by `--root=<directory>` before the models.
+fn main() {
+	let mut options = RasterOptions::default();
+	let mut resolver = Resolver::new();
+	let mut sequence = Sequence::Frames;
+	let mut columns = 0;
+	let mut animate = false;
+	let mut fps = 10.0;
+
+	for arg in ::std::env::args().skip(1) {
+		if let Some(root) = arg.strip_prefix("--root=") {
+			if let Err(e) = resolver.add_root(root) {
+				eprintln!("{}: {}", root, e);
+			}
+
+			continue;
+		}
+
+		if let Some(angles) = arg.strip_prefix("--turntable=") {
+			sequence = Sequence::Turntable { frame: 0, angles: angles.parse().expect("angles must be a whole number") };
+			continue;
+		}
+
+		if let Some(size) = arg.strip_prefix("--size=") {
+			options.width = size.parse().expect("size must be a whole number of pixels");
+			options.height = options.width;
+			continue;
+		}
+
+		if let Some(count) = arg.strip_prefix("--columns=") {
+			columns = count.parse().expect("columns must be a whole number");
+			continue;
+		}
+
+		if let Some(rate) = arg.strip_prefix("--fps=") {
+			fps = rate.parse().expect("fps must be a number");
+			continue;
+		}
+
+		if arg == "--animate" {
+			animate = true;
+			continue;
+		}
+
+		let scene = match Scene::<V2>::read(&mut BufReader::new(File::open(&arg).unwrap())) {
+			Ok(scene) => scene,
+			Err(e) => {
+				eprintln!("{}: {}", arg, e);
+				continue;
+			}
+		};
+
+		let images = match sheet::render(&scene, sequence, &options, |name| resolver.image(name).ok()) {
+			Ok(images) => images,
+			Err(e) => {
+				eprintln!("{}: {}", arg, e);
+				continue;
+			}
+		};
+
+		let path = Path::new(&arg);
+		let contact_sheet = sheet::contact_sheet(&images, columns, true, [255, 255, 255, 255]).unwrap();
+		let output = path.with_extension("sheet.png");
+		let mut w = BufWriter::new(File::create(&output).unwrap());
+
+		png::write(&mut w, &contact_sheet).unwrap();
+		w.flush().unwrap();
+		println!("{}", output.display());
+
+		if animate {
+			let frames: Vec<_> = images.into_iter().map(|(image, _)| image).collect();
+			let output = path.with_extension("anim.png");
+			let mut w = BufWriter::new(File::create(&output).unwrap());
+
+			png::write_animated(&mut w, &frames, fps).unwrap();
+			w.flush().unwrap();
+			println!("{}", output.display());
+		}
+	}
+}
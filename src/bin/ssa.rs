@@ -0,0 +1,47 @@
+extern crate cem;
+
+use cem::ssa::Archive;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::fs::{self, File};
+
+/// Lists the entries of each SSA archive given on the command line. Pass `--extract=<directory>` before the
+/// archives to also write their contents below that directory, keeping the paths stored in the archive.
+fn main() {
+	let mut extract = None;
+
+	for arg in ::std::env::args().skip(1) {
+		if let Some(directory) = arg.strip_prefix("--extract=") {
+			extract = Some(PathBuf::from(directory));
+			continue;
+		}
+
+		let mut archive = match Archive::read(BufReader::new(File::open(&arg).unwrap())) {
+			Ok(archive) => archive,
+			Err(e) => {
+				eprintln!("{}: {}", arg, e);
+				continue;
+			}
+		};
+
+		for entry in archive.entries().to_vec() {
+			println!("{:10} {}", entry.size, entry.name);
+
+			if let Some(ref directory) = extract {
+				let path = entry.name.split(['\\', '/']).filter(|part| !part.is_empty() && *part != "..").fold(directory.clone(), |path, part| path.join(part));
+
+				let result = archive.read_entry(&entry.name).and_then(|data| {
+					if let Some(parent) = path.parent() {
+						fs::create_dir_all(parent)?;
+					}
+
+					fs::write(&path, data)
+				});
+
+				if let Err(e) = result {
+					eprintln!("{}: {}: {}", arg, entry.name, e);
+				}
+			}
+		}
+	}
+}
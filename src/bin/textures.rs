@@ -0,0 +1,43 @@
+extern crate cem;
+
+use cem::{Scene, V2};
+use cem::textures::Resolver;
+use std::io::BufReader;
+use std::fs::File;
+use std::process;
+
+/// Reports the textures that cannot be found for every V2 model given on the command line.
+/// Pass `--root=<directory>` before the models, once for each data directory to search, in order of priority.
+/// Exits with status 1 if any texture is missing.
+fn main() {
+	let mut resolver = Resolver::new();
+	let mut missing_total = 0;
+
+	for arg in ::std::env::args().skip(1) {
+		if let Some(root) = arg.strip_prefix("--root=") {
+			if let Err(e) = resolver.add_root(root) {
+				eprintln!("{}: {}", root, e);
+				process::exit(2);
+			}
+
+			continue;
+		}
+
+		let scene = match Scene::<V2>::read(&mut BufReader::new(File::open(&arg).unwrap())) {
+			Ok(scene) => scene,
+			Err(e) => {
+				eprintln!("{}: {}", arg, e);
+				continue;
+			}
+		};
+
+		for missing in resolver.missing(&scene) {
+			println!("{}: node '{}', material '{}': missing texture '{}'", arg, missing.node, missing.material, missing.texture);
+			missing_total += 1;
+		}
+	}
+
+	if missing_total > 0 {
+		process::exit(1);
+	}
+}
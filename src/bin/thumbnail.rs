@@ -0,0 +1,62 @@
+extern crate cem;
+
+use cem::{Scene, V2};
+use cem::image::png;
+use cem::render::raster::{self, RasterOptions};
+use cem::textures::Resolver;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::fs::File;
+
+/// Renders a PNG thumbnail next to every V2 model given on the command line, replacing the extension with `.png`.
+/// Pass `--size=<pixels>` to change the size of the thumbnails, `--frame=<index>` to draw another frame, and
+/// `--root=<directory>` before the models, once for each data directory to take textures from.
+fn main() {
+	let mut options = RasterOptions::default();
+	let mut resolver = Resolver::new();
+
+	for arg in ::std::env::args().skip(1) {
+		if let Some(root) = arg.strip_prefix("--root=") {
+			if let Err(e) = resolver.add_root(root) {
+				eprintln!("{}: {}", root, e);
+			}
+
+			continue;
+		}
+
+		if let Some(size) = arg.strip_prefix("--size=") {
+			options.width = size.parse().expect("size must be a whole number of pixels");
+			options.height = options.width;
+			continue;
+		}
+
+		if let Some(frame) = arg.strip_prefix("--frame=") {
+			options.frame = frame.parse().expect("frame must be a whole number");
+			continue;
+		}
+
+		let scene = match Scene::<V2>::read(&mut BufReader::new(File::open(&arg).unwrap())) {
+			Ok(scene) => scene,
+			Err(e) => {
+				eprintln!("{}: {}", arg, e);
+				continue;
+			}
+		};
+
+		let image = match raster::render(&scene, &options, |name| resolver.image(name).ok()) {
+			Ok(image) => image,
+			Err(e) => {
+				eprintln!("{}: {}", arg, e);
+				continue;
+			}
+		};
+
+		let output = Path::new(&arg).with_extension("png");
+		let mut w = BufWriter::new(File::create(&output).unwrap());
+
+		png::write(&mut w, &image).unwrap();
+		w.flush().unwrap();
+
+		println!("{}", output.display());
+	}
+}
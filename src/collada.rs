@@ -0,0 +1,453 @@
+//! Every node becomes a COLLADA node with its frame transform as a `matrix`. Each frame of a model is
+//! written as its own geometry, and models with several frames use a morph controller with one target
+//! per frame, whose weights are animated so that each frame is shown in turn. Tag points become child
+//! nodes named with `gltf::TAG_POINT_PREFIX`, with an animated `translate`. Tag points missing from any
+//! frame are left out.
+//!
+//! COLLADA has no concept of LOD levels, so only the chosen level is written. Texture coordinates are
+//! flipped vertically, since COLLADA puts the origin at the bottom left.
+
+use std::io::{self, Write};
+use std::collections::HashMap;
+use cgmath::Matrix4;
+use scene::Scene;
+use v2::{V2, Frame};
+use gltf::{TAG_POINT_PREFIX, uri_encode};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ExportOptions {
+	/// LOD level to write. Models with fewer levels use their last one.
+	pub lod: usize,
+	/// Frames per second used for the animations.
+	pub frame_rate: f32
+}
+
+impl Default for ExportOptions {
+	fn default() -> Self {
+		ExportOptions {
+			lod: 0,
+			frame_rate: 30.0
+		}
+	}
+}
+
+/// Writes a scene as a COLLADA 1.4.1 document.
+pub fn export<W>(w: &mut W, scene: &Scene<V2>, options: &ExportOptions) -> io::Result<()> where W: Write {
+	let mut writer = Writer {
+		options,
+		nodes: 0,
+		images: HashMap::new(),
+		library_images: Vec::new(),
+		library_effects: Vec::new(),
+		library_materials: Vec::new(),
+		library_geometries: Vec::new(),
+		library_controllers: Vec::new(),
+		library_animations: Vec::new()
+	};
+
+	let mut visual_scene = Vec::new();
+	writer.node(&mut visual_scene, scene, 3)?;
+
+	writeln!(w, "<?xml version=\"1.0\" encoding=\"utf-8\"?>")?;
+	writeln!(w, "<COLLADA xmlns=\"http://www.collada.org/2005/11/COLLADASchema\" version=\"1.4.1\">")?;
+	writeln!(w, "\t<asset>")?;
+	writeln!(w, "\t\t<contributor><authoring_tool>cem</authoring_tool></contributor>")?;
+	// Fixed timestamps keep the output reproducible.
+	writeln!(w, "\t\t<created>1970-01-01T00:00:00Z</created>")?;
+	writeln!(w, "\t\t<modified>1970-01-01T00:00:00Z</modified>")?;
+	writeln!(w, "\t</asset>")?;
+
+	library(w, "library_images", &writer.library_images)?;
+	library(w, "library_effects", &writer.library_effects)?;
+	library(w, "library_materials", &writer.library_materials)?;
+	library(w, "library_geometries", &writer.library_geometries)?;
+	library(w, "library_controllers", &writer.library_controllers)?;
+	library(w, "library_animations", &writer.library_animations)?;
+
+	writeln!(w, "\t<library_visual_scenes>")?;
+	writeln!(w, "\t\t<visual_scene id=\"scene\" name=\"{}\">", escape(&scene.name))?;
+	w.write_all(&visual_scene)?;
+	writeln!(w, "\t\t</visual_scene>")?;
+	writeln!(w, "\t</library_visual_scenes>")?;
+
+	writeln!(w, "\t<scene>")?;
+	writeln!(w, "\t\t<instance_visual_scene url=\"#scene\"/>")?;
+	writeln!(w, "\t</scene>")?;
+	writeln!(w, "</COLLADA>")
+}
+
+fn library<W>(w: &mut W, name: &str, contents: &[u8]) -> io::Result<()> where W: Write {
+	if contents.is_empty() {
+		return Ok(());
+	}
+
+	writeln!(w, "\t<{}>", name)?;
+	w.write_all(contents)?;
+	writeln!(w, "\t</{}>", name)
+}
+
+pub(crate) fn escape(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+
+	for c in text.chars() {
+		match c {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'"' => escaped.push_str("&quot;"),
+			'\'' => escaped.push_str("&apos;"),
+			// XML 1.0 cannot hold most control characters, even escaped.
+			c if (c as u32) < 0x20 && c != '\t' && c != '\n' && c != '\r' => escaped.push('?'),
+			c => escaped.push(c)
+		}
+	}
+
+	escaped
+}
+
+fn join<I, T>(values: I) -> String where I: IntoIterator<Item = T>, T: ToString {
+	values.into_iter().map(|value| value.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// Joins floats, spelling infinities the way xs:float does. Rust already writes NaN as `NaN`.
+fn floats<I>(values: I) -> String where I: IntoIterator<Item = f32> {
+	join(values.into_iter().map(|value| if value == f32::INFINITY {
+		"INF".to_string()
+	} else if value == f32::NEG_INFINITY {
+		"-INF".to_string()
+	} else {
+		value.to_string()
+	}))
+}
+
+/// The 16 elements of a matrix in row-major order, as COLLADA expects.
+fn rows(m: &Matrix4<f32>) -> Vec<f32> {
+	(0..4).flat_map(|row| vec![m.x[row], m.y[row], m.z[row], m.w[row]]).collect()
+}
+
+/// Writes an animation of a single target, with one key per frame.
+fn animation<W>(w: &mut W, id: &str, target: &str, frame_rate: f32, values: &[f32], parameters: &[&str]) -> io::Result<()> where W: Write {
+	let stride = parameters.len();
+	let frames = values.len() / stride;
+	let kind = if stride == 16 { "float4x4" } else { "float" };
+
+	writeln!(w, "\t\t<animation id=\"{}\">", id)?;
+
+	writeln!(w, "\t\t\t<source id=\"{}-input\">", id)?;
+	writeln!(w, "\t\t\t\t<float_array id=\"{}-input-array\" count=\"{}\">{}</float_array>", id, frames, floats((0..frames).map(|frame| frame as f32 / frame_rate)))?;
+	writeln!(w, "\t\t\t\t<technique_common><accessor source=\"#{}-input-array\" count=\"{}\" stride=\"1\"><param name=\"TIME\" type=\"float\"/></accessor></technique_common>", id, frames)?;
+	writeln!(w, "\t\t\t</source>")?;
+
+	writeln!(w, "\t\t\t<source id=\"{}-output\">", id)?;
+	writeln!(w, "\t\t\t\t<float_array id=\"{}-output-array\" count=\"{}\">{}</float_array>", id, values.len(), floats(values.iter().cloned()))?;
+	write!(w, "\t\t\t\t<technique_common><accessor source=\"#{}-output-array\" count=\"{}\" stride=\"{}\">", id, frames, stride)?;
+
+	if stride == 16 {
+		write!(w, "<param name=\"TRANSFORM\" type=\"{}\"/>", kind)?;
+	} else {
+		for parameter in parameters {
+			write!(w, "<param name=\"{}\" type=\"{}\"/>", parameter, kind)?;
+		}
+	}
+
+	writeln!(w, "</accessor></technique_common>")?;
+	writeln!(w, "\t\t\t</source>")?;
+
+	writeln!(w, "\t\t\t<source id=\"{}-interpolation\">", id)?;
+	writeln!(w, "\t\t\t\t<Name_array id=\"{}-interpolation-array\" count=\"{}\">{}</Name_array>", id, frames, join((0..frames).map(|_| "LINEAR")))?;
+	writeln!(w, "\t\t\t\t<technique_common><accessor source=\"#{}-interpolation-array\" count=\"{}\" stride=\"1\"><param name=\"INTERPOLATION\" type=\"name\"/></accessor></technique_common>", id, frames)?;
+	writeln!(w, "\t\t\t</source>")?;
+
+	writeln!(w, "\t\t\t<sampler id=\"{}-sampler\">", id)?;
+	writeln!(w, "\t\t\t\t<input semantic=\"INPUT\" source=\"#{}-input\"/>", id)?;
+	writeln!(w, "\t\t\t\t<input semantic=\"OUTPUT\" source=\"#{}-output\"/>", id)?;
+	writeln!(w, "\t\t\t\t<input semantic=\"INTERPOLATION\" source=\"#{}-interpolation\"/>", id)?;
+	writeln!(w, "\t\t\t</sampler>")?;
+	writeln!(w, "\t\t\t<channel source=\"#{}-sampler\" target=\"{}\"/>", id, target)?;
+	writeln!(w, "\t\t</animation>")
+}
+
+struct Writer<'o> {
+	options: &'o ExportOptions,
+	nodes: usize,
+	/// Image id for each distinct texture name.
+	images: HashMap<String, String>,
+	library_images: Vec<u8>,
+	library_effects: Vec<u8>,
+	library_materials: Vec<u8>,
+	library_geometries: Vec<u8>,
+	library_controllers: Vec<u8>,
+	library_animations: Vec<u8>
+}
+
+impl<'o> Writer<'o> {
+	fn node<W>(&mut self, w: &mut W, scene: &Scene<V2>, depth: usize) -> io::Result<()> where W: Write {
+		let id = format!("node{}", self.nodes);
+		self.nodes += 1;
+
+		let indent = "\t".repeat(depth);
+		let model = &scene.model;
+
+		writeln!(w, "{}<node id=\"{}\" name=\"{}\" type=\"NODE\">", indent, id, escape(&scene.name))?;
+
+		if let Some(frame) = model.frames.first() {
+			writeln!(w, "{}\t<matrix sid=\"transform\">{}</matrix>", indent, floats(rows(&frame.transform)))?;
+
+			// Compared bit for bit, so that a NaN in an unchanging transform is not mistaken for animation.
+			let bits = |m: &Matrix4<f32>| rows(m).iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+
+			if model.frames.iter().any(|other| bits(&other.transform) != bits(&frame.transform)) {
+				let values: Vec<f32> = model.frames.iter()
+					.flat_map(|frame| rows(&frame.transform))
+					.collect();
+
+				animation(&mut self.library_animations, &format!("{}-transform", id), &format!("{}/transform", id), self.options.frame_rate, &values, &["TRANSFORM"; 16])?;
+			}
+		}
+
+		let materials = self.materials(&id, model)?;
+
+		if let Some(instance) = self.geometry(&id, model, &materials)? {
+			writeln!(w, "{}\t<{} url=\"#{}\">", indent, instance.0, instance.1)?;
+			writeln!(w, "{}\t\t<bind_material><technique_common>", indent)?;
+
+			for (symbol, material) in &materials {
+				writeln!(w, "{}\t\t\t<instance_material symbol=\"{}\" target=\"#{}\"><bind_vertex_input semantic=\"UVSET0\" input_semantic=\"TEXCOORD\" input_set=\"0\"/></instance_material>", indent, symbol, material)?;
+			}
+
+			writeln!(w, "{}\t\t</technique_common></bind_material>", indent)?;
+			writeln!(w, "{}\t</{}>", indent, instance.0)?;
+		}
+
+		for (i, name) in model.tag_points.iter().enumerate() {
+			let positions: Option<Vec<[f32; 3]>> = model.frames.iter().map(|frame| {
+				frame.tag_points.get(i).map(|position| [position.x, position.y, position.z])
+			}).collect();
+
+			let positions = match positions {
+				Some(positions) => positions.concat(),
+				None => continue
+			};
+
+			let tag_id = format!("{}-tag{}", id, i);
+
+			writeln!(w, "{}\t<node id=\"{}\" name=\"{}{}\" type=\"NODE\">", indent, tag_id, TAG_POINT_PREFIX, escape(name))?;
+			writeln!(w, "{}\t\t<translate sid=\"location\">{}</translate>", indent, floats(positions.iter().cloned().take(3)))?;
+			writeln!(w, "{}\t</node>", indent)?;
+
+			if model.frames.len() > 1 {
+				animation(&mut self.library_animations, &format!("{}-location", tag_id), &format!("{}/location", tag_id), self.options.frame_rate, &positions, &["X", "Y", "Z"])?;
+			}
+		}
+
+		for child in &scene.children {
+			self.node(w, child, depth + 1)?;
+		}
+
+		writeln!(w, "{}</node>", indent)
+	}
+
+	/// Writes the effects and materials of a model, returning the symbol and id of each material.
+	fn materials(&mut self, id: &str, model: &V2) -> io::Result<Vec<(String, String)>> {
+		let mut bindings = Vec::with_capacity(model.materials.len());
+
+		for (i, material) in model.materials.iter().enumerate() {
+			let material_id = format!("{}-material{}", id, i);
+			let effect_id = format!("{}-effect{}", id, i);
+			let w = &mut self.library_effects;
+
+			writeln!(w, "\t\t<effect id=\"{}\">", effect_id)?;
+			writeln!(w, "\t\t\t<profile_COMMON>")?;
+
+			let diffuse = if material.texture_name.is_empty() {
+				"<color>1 1 1 1</color>".to_string()
+			} else {
+				let image = match self.images.get(&material.texture_name) {
+					Some(image) => image.clone(),
+					None => {
+						let image = format!("image{}", self.images.len());
+
+						writeln!(self.library_images, "\t\t<image id=\"{}\" name=\"{}\"><init_from>{}</init_from></image>", image, escape(&material.texture_name), escape(&uri_encode(&material.texture_name)))?;
+						self.images.insert(material.texture_name.clone(), image.clone());
+
+						image
+					}
+				};
+
+				writeln!(w, "\t\t\t\t<newparam sid=\"surface\"><surface type=\"2D\"><init_from>{}</init_from></surface></newparam>", image)?;
+				writeln!(w, "\t\t\t\t<newparam sid=\"sampler\"><sampler2D><source>surface</source></sampler2D></newparam>")?;
+
+				"<texture texture=\"sampler\" texcoord=\"UVSET0\"/>".to_string()
+			};
+
+			writeln!(w, "\t\t\t\t<technique sid=\"common\"><lambert><diffuse>{}</diffuse></lambert></technique>", diffuse)?;
+			writeln!(w, "\t\t\t</profile_COMMON>")?;
+			writeln!(w, "\t\t</effect>")?;
+
+			// Special meanings such as player color are kept in an extra, which other tools preserve but ignore.
+			let extra = match material.special() {
+				Some(special) => format!("<extra><technique profile=\"cem\"><special>{}</special></technique></extra>", special.name()),
+				None => String::new()
+			};
+
+			writeln!(self.library_materials, "\t\t<material id=\"{}\" name=\"{}\"><instance_effect url=\"#{}\"/>{}</material>", material_id, escape(&material.name), effect_id, extra)?;
+
+			bindings.push((format!("material{}", i), material_id));
+		}
+
+		Ok(bindings)
+	}
+
+	/// Writes the geometry of every frame, and a morph controller if there are several.
+	/// Returns the instance element and id to place in the node.
+	fn geometry(&mut self, id: &str, model: &V2, materials: &[(String, String)]) -> io::Result<Option<(&'static str, String)>> {
+		if model.frames.first().map(|frame| frame.vertices.is_empty()).unwrap_or(true) {
+			return Ok(None);
+		}
+
+		let lod = self.options.lod.min(model.lod_levels.len().saturating_sub(1));
+		let geometries: Vec<String> = (0..model.frames.len()).map(|frame| format!("{}-frame{}", id, frame)).collect();
+
+		for (frame, geometry) in model.frames.iter().zip(&geometries) {
+			self.frame_geometry(geometry, model, frame, lod, materials)?;
+		}
+
+		if geometries.len() == 1 {
+			return Ok(Some(("instance_geometry", geometries[0].clone())));
+		}
+
+		let controller = format!("{}-morph", id);
+		let targets = &geometries[1..];
+		let w = &mut self.library_controllers;
+
+		// With absolute targets, normalized morphing shows exactly one frame whenever a single weight is 1.
+		writeln!(w, "\t\t<controller id=\"{}\">", controller)?;
+		writeln!(w, "\t\t\t<morph source=\"#{}\" method=\"NORMALIZED\">", geometries[0])?;
+		writeln!(w, "\t\t\t\t<source id=\"{}-targets\">", controller)?;
+		writeln!(w, "\t\t\t\t\t<IDREF_array id=\"{}-targets-array\" count=\"{}\">{}</IDREF_array>", controller, targets.len(), targets.join(" "))?;
+		writeln!(w, "\t\t\t\t\t<technique_common><accessor source=\"#{}-targets-array\" count=\"{}\" stride=\"1\"><param name=\"MORPH_TARGET\" type=\"IDREF\"/></accessor></technique_common>", controller, targets.len())?;
+		writeln!(w, "\t\t\t\t</source>")?;
+		writeln!(w, "\t\t\t\t<source id=\"{}-weights\">", controller)?;
+		writeln!(w, "\t\t\t\t\t<float_array id=\"{}-weights-array\" count=\"{}\">{}</float_array>", controller, targets.len(), join(targets.iter().map(|_| 0)))?;
+		writeln!(w, "\t\t\t\t\t<technique_common><accessor source=\"#{}-weights-array\" count=\"{}\" stride=\"1\"><param name=\"MORPH_WEIGHT\" type=\"float\"/></accessor></technique_common>", controller, targets.len())?;
+		writeln!(w, "\t\t\t\t</source>")?;
+		writeln!(w, "\t\t\t\t<targets>")?;
+		writeln!(w, "\t\t\t\t\t<input semantic=\"MORPH_TARGET\" source=\"#{}-targets\"/>", controller)?;
+		writeln!(w, "\t\t\t\t\t<input semantic=\"MORPH_WEIGHT\" source=\"#{}-weights\"/>", controller)?;
+		writeln!(w, "\t\t\t\t</targets>")?;
+		writeln!(w, "\t\t\t</morph>")?;
+		writeln!(w, "\t\t</controller>")?;
+
+		for target in 0..targets.len() {
+			let values: Vec<f32> = (0..geometries.len()).map(|frame| if frame == target + 1 { 1.0 } else { 0.0 }).collect();
+
+			animation(&mut self.library_animations, &format!("{}-weight{}", controller, target), &format!("{}-weights({})", controller, target), self.options.frame_rate, &values, &["WEIGHT"])?;
+		}
+
+		Ok(Some(("instance_controller", controller)))
+	}
+
+	fn frame_geometry(&mut self, id: &str, model: &V2, frame: &Frame, lod: usize, materials: &[(String, String)]) -> io::Result<()> {
+		let w = &mut self.library_geometries;
+		let count = frame.vertices.len();
+
+		let sources: [(&str, String, &[&str]); 3] = [
+			("positions", floats(frame.vertices.iter().flat_map(|v| vec![v.position.x, v.position.y, v.position.z])), &["X", "Y", "Z"]),
+			("normals", floats(frame.vertices.iter().flat_map(|v| vec![v.normal.x, v.normal.y, v.normal.z])), &["X", "Y", "Z"]),
+			("texcoords", floats(frame.vertices.iter().flat_map(|v| vec![v.texture.x, 1.0 - v.texture.y])), &["S", "T"])
+		];
+
+		writeln!(w, "\t\t<geometry id=\"{}\">", id)?;
+		writeln!(w, "\t\t\t<mesh>")?;
+
+		for &(name, ref values, parameters) in &sources {
+			writeln!(w, "\t\t\t\t<source id=\"{}-{}\">", id, name)?;
+			writeln!(w, "\t\t\t\t\t<float_array id=\"{}-{}-array\" count=\"{}\">{}</float_array>", id, name, count * parameters.len(), values)?;
+			write!(w, "\t\t\t\t\t<technique_common><accessor source=\"#{}-{}-array\" count=\"{}\" stride=\"{}\">", id, name, count, parameters.len())?;
+
+			for parameter in parameters {
+				write!(w, "<param name=\"{}\" type=\"float\"/>", parameter)?;
+			}
+
+			writeln!(w, "</accessor></technique_common>")?;
+			writeln!(w, "\t\t\t\t</source>")?;
+		}
+
+		writeln!(w, "\t\t\t\t<vertices id=\"{}-vertices\"><input semantic=\"POSITION\" source=\"#{}-positions\"/></vertices>", id, id)?;
+
+		for (material, (symbol, _)) in model.materials.iter().zip(materials) {
+			let triangles = model.selection(material, lod);
+
+			if triangles.is_empty() {
+				continue;
+			}
+
+			writeln!(w, "\t\t\t\t<triangles material=\"{}\" count=\"{}\">", symbol, triangles.len())?;
+			writeln!(w, "\t\t\t\t\t<input semantic=\"VERTEX\" source=\"#{}-vertices\" offset=\"0\"/>", id)?;
+			writeln!(w, "\t\t\t\t\t<input semantic=\"NORMAL\" source=\"#{}-normals\" offset=\"0\"/>", id)?;
+			writeln!(w, "\t\t\t\t\t<input semantic=\"TEXCOORD\" source=\"#{}-texcoords\" offset=\"0\" set=\"0\"/>", id)?;
+			writeln!(w, "\t\t\t\t\t<p>{}</p>", join(triangles.iter().flat_map(|t| vec![t.0, t.1, t.2])))?;
+			writeln!(w, "\t\t\t\t</triangles>")?;
+		}
+
+		writeln!(w, "\t\t\t</mesh>")?;
+		writeln!(w, "\t\t</geometry>")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Matrix4, Vector3};
+	use samples::v2_scene;
+	use scene::Scene;
+	use v2::{self, V2};
+	use super::{export, ExportOptions};
+
+	fn document(scene: &Scene<V2>) -> String {
+		let mut xml = Vec::new();
+		export(&mut xml, scene, &ExportOptions::default()).unwrap();
+
+		String::from_utf8(xml).unwrap()
+	}
+
+	#[test]
+	fn special_materials_are_kept_in_an_extra() {
+		let mut scene = v2_scene();
+		assert!(!document(&scene).contains("<extra>"));
+
+		scene.model.materials[0].name = "Team_Colour".to_string();
+		assert!(document(&scene).contains("<extra><technique profile=\"cem\"><special>player color</special></technique></extra>"));
+	}
+
+	#[test]
+	fn changing_transform_is_animated_as_a_matrix() {
+		let mut scene = v2_scene();
+		assert!(!document(&scene).contains("node0-transform"));
+
+		let second = {
+			let first = &scene.model.frames[0];
+			v2::Frame::from_vertices(first.vertices.clone(), first.tag_points.clone(), scene.model.center)
+		};
+
+		scene.model.frames.push(second);
+		scene.model.frames[0].transform = Matrix4::from_translation(Vector3::new(2.0, 0.0, 0.0));
+
+		let xml = document(&scene);
+		assert!(xml.contains("<matrix sid=\"transform\">1 0 0 2 0 1 0 0 0 0 1 0 0 0 0 1</matrix>"));
+		assert!(xml.contains("<float_array id=\"node0-transform-output-array\" count=\"32\">1 0 0 2 0 1 0 0 0 0 1 0 0 0 0 1 1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1</float_array>"));
+		assert!(xml.contains("<param name=\"TRANSFORM\" type=\"float4x4\"/>"));
+		assert!(xml.contains("<channel source=\"#node0-transform-sampler\" target=\"node0/transform\"/>"));
+	}
+
+	#[test]
+	fn non_finite_floats_are_written_as_xs_float() {
+		let mut scene = v2_scene();
+		scene.model.frames[0].vertices[0].position.x = f32::INFINITY;
+		scene.model.frames[0].vertices[1].position.x = f32::NEG_INFINITY;
+
+		let xml = document(&scene);
+		assert!(xml.contains(">INF 0 -1 -INF 0.1 -1 "), "{}", xml);
+		assert!(xml.contains(" NaN 0 0 1</matrix>"));
+		assert!(!xml.contains("inf"));
+	}
+}
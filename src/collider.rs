@@ -29,6 +29,7 @@ impl CenterBuilder {
 }
 
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Collider {
 	pub aabb: Aabb,
 	pub radius: f32
@@ -68,6 +69,7 @@ impl ColliderBuilder {
 
 /// An axis-aligned bounding box containing a lower corner and upper corner.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Aabb {
 	pub lower: Point3<f32>,
 	pub upper: Point3<f32>
@@ -0,0 +1,107 @@
+//! Each line shows the offset of a field, its raw bytes in hexadecimal, its path and its decoded value.
+//! Long fields such as strings and matrices continue their bytes on the following lines.
+
+use std::io::{self, Write};
+use trace::{Coverage, Span};
+
+/// Number of bytes shown on each line of the dump.
+const LINE: usize = 16;
+/// Maximum number of bytes shown after the point where parsing failed.
+const CONTEXT: usize = 256;
+
+/// Writes an annotated hex dump of a CEM file, following the structure of the model readers.
+/// If the file fails to parse, the dump stops at the offending field and shows the bytes from that point on.
+/// Returns the coverage information gathered while tracing the file.
+pub fn dump<W>(data: &[u8], w: &mut W) -> io::Result<Coverage> where W: Write {
+	let coverage = Coverage::trace(data)?;
+	let mut unconsumed = coverage.unconsumed.iter().peekable();
+
+	let end = coverage.failure.as_ref().map(|failure| failure.start).unwrap_or(data.len() as u64);
+
+	for span in &coverage.spans {
+		while let Some(range) = unconsumed.peek() {
+			if range.start > span.start || range.start >= end {
+				break;
+			}
+
+			write_bytes(w, data, range.start, range.end, "<unconsumed>")?;
+			unconsumed.next();
+		}
+
+		write_span(w, data, span)?;
+	}
+
+	match coverage.failure {
+		Some(ref failure) => {
+			writeln!(w)?;
+			writeln!(w, "error at {:08x} in {}: {}", failure.start, failure.path, failure.value)?;
+
+			let context = (failure.start as usize + CONTEXT).min(data.len()) as u64;
+			write_bytes(w, data, failure.start, context, "<offending bytes>")?;
+
+			if context < data.len() as u64 {
+				writeln!(w, "... {} more bytes", data.len() as u64 - context)?;
+			}
+		},
+		None => {
+			for range in unconsumed {
+				write_bytes(w, data, range.start, range.end, "<unconsumed>")?;
+			}
+
+			if let Some(ref error) = coverage.error {
+				writeln!(w)?;
+				writeln!(w, "error: {}", error)?;
+			}
+		}
+	}
+
+	Ok(coverage)
+}
+
+fn write_span<W>(w: &mut W, data: &[u8], span: &Span) -> io::Result<()> where W: Write {
+	write_bytes(w, data, span.start, span.end, &format!("{} = {}", span.path, span.value))
+}
+
+fn write_bytes<W>(w: &mut W, data: &[u8], start: u64, end: u64, annotation: &str) -> io::Result<()> where W: Write {
+	let bytes = &data[start as usize..end as usize];
+
+	for (i, chunk) in bytes.chunks(LINE).enumerate() {
+		write!(w, "{:08x}  ", start as usize + i * LINE)?;
+
+		for byte in chunk {
+			write!(w, "{:02x} ", byte)?;
+		}
+
+		if i == 0 {
+			for _ in chunk.len()..LINE {
+				write!(w, "   ")?;
+			}
+
+			write!(w, " {}", annotation)?;
+		}
+
+		writeln!(w)?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use samples::v2_scene;
+
+	#[test]
+	fn bad_magic_shows_the_error_and_the_header() {
+		let mut data = Vec::new();
+		v2_scene().write(&mut data).unwrap();
+		data[..4].copy_from_slice(b"XXXX");
+
+		let mut output = Vec::new();
+		let coverage = super::dump(&data, &mut output).unwrap();
+		let output = String::from_utf8(output).unwrap();
+
+		assert!(coverage.error.is_some());
+		assert!(output.contains("error at 00000000 in header: Unknown model header"), "{}", output);
+		assert!(output.contains("<offending bytes>"));
+	}
+}
@@ -2,6 +2,7 @@ use std::io::{self, Read, Write};
 use byteorder::{LittleEndian, WriteBytesExt, ReadBytesExt};
 use std::borrow::Cow;
 use cgmath::{Point2, Point3, Vector3, Matrix4};
+use MAX_PREALLOCATION;
 
 pub trait Encode: Sized {
 	fn read<R>(r: &mut R) -> io::Result<Self> where R: Read;
@@ -13,15 +14,15 @@ impl Encode for String {
 		Cow::read(data).map(Cow::into_owned)
 	}
 
-	fn write<W: Write>(&self, w: &mut W) -> io::Result<()> where W: Write {
-		write_str(w, &self)
+	fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+		write_str(w, self)
 	}
 }
 
 impl<'a> Encode for Cow<'a, str> {
 	fn read<T: Read>(data: &mut T) -> io::Result<Self> {
 		let len = data.read_u32::<LittleEndian>()? as usize;
-		let mut string = String::with_capacity(len);
+		let mut string = String::with_capacity(len.min(MAX_PREALLOCATION));
 		let mut end = false;
 
 		for _ in 0..len {
@@ -39,17 +40,17 @@ impl<'a> Encode for Cow<'a, str> {
 		Ok(Cow::Owned(string))
 	}
 
-	fn write<W: Write>(&self, w: &mut W) -> io::Result<()> where W: Write {
-		write_str(w, &self)
+	fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+		write_str(w, self)
 	}
 }
 
 /// Writes a string encoded with ISO-8859-1, replacing unknown characters with a question mark ('?').
-fn write_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> where W: Write {
-	let s = s.trim_right_matches('\0');
+fn write_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+	let s = s.trim_end_matches('\0');
 	let len = s.chars().count() + 1;
 
-	if len > u32::max_value() as usize {
+	if len > u32::MAX as usize {
 		return Err(io::Error::new(io::ErrorKind::InvalidData, "Cannot write a string more than 4GB long"));
 	}
 
@@ -58,7 +59,7 @@ fn write_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> where W: Write {
 	for char in s.chars() {
 		// Simply replace all unknown chars with a ?, as an encoding error.
 
-		w.write_u8(if char < '\u{256}' { char as u8 } else { '?' as u8 })?;
+		w.write_u8(if char < '\u{100}' { char as u8 } else { b'?' })?;
 	}
 
 	w.write_u8(0)
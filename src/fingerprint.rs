@@ -0,0 +1,245 @@
+//! The fingerprint covers geometry, topology, materials and tag points. Data that can be derived from
+//! the geometry, such as the center and colliders, is left out, as is any trailing data.
+//! The hash is 128-bit FNV-1a over a canonical encoding, so fingerprints are stable across runs and platforms.
+
+use std::fmt;
+use cgmath::{Point2, Point3, Vector3, Matrix4};
+use scene::{Scene, Model};
+use v2::V2;
+
+const FNV_OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// Controls which differences between models are ignored.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Options {
+	/// Ignore the names of scene nodes, materials and tag points. Texture names are always included.
+	pub ignore_names: bool,
+	/// Round every float to a multiple of this step before hashing, so that models differing only by
+	/// float noise produce the same fingerprint. Values that straddle a rounding boundary can still differ.
+	pub quantize: Option<f32>
+}
+
+/// A canonical content hash of a model or scene.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Fingerprint(pub u128);
+
+impl Fingerprint {
+	pub fn of<T>(content: &T, options: Options) -> Self where T: Content {
+		let mut hasher = ContentHasher { state: FNV_OFFSET, options };
+		content.hash_content(&mut hasher);
+
+		Fingerprint(hasher.state)
+	}
+}
+
+impl fmt::Display for Fingerprint {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:032x}", self.0)
+	}
+}
+
+/// Something that can be fingerprinted.
+pub trait Content {
+	fn hash_content(&self, hasher: &mut ContentHasher);
+}
+
+/// Accumulates the canonical encoding of some content.
+pub struct ContentHasher {
+	state: u128,
+	options: Options
+}
+
+impl ContentHasher {
+	pub fn write(&mut self, bytes: &[u8]) {
+		for &byte in bytes {
+			self.state ^= byte as u128;
+			self.state = self.state.wrapping_mul(FNV_PRIME);
+		}
+	}
+
+	pub fn write_u32(&mut self, value: u32) {
+		self.write(&value.to_le_bytes());
+	}
+
+	/// Writes a float, treating all NaNs as equal and negative zero as zero, then applying the quantization step if set.
+	pub fn write_f32(&mut self, value: f32) {
+		if value.is_nan() {
+			self.write(&[0xFF; 8]);
+			return;
+		}
+
+		match self.options.quantize {
+			Some(step) if step > 0.0 && value.is_finite() => {
+				let steps = (value / step).round() as i64;
+
+				self.write(&steps.to_le_bytes());
+			},
+			_ => {
+				let value = if value == 0.0 { 0.0f32 } else { value };
+
+				self.write(&(value.to_bits() as i64).to_le_bytes());
+			}
+		}
+	}
+
+	pub fn write_str(&mut self, value: &str) {
+		self.write_u32(value.len() as u32);
+		self.write(value.as_bytes());
+	}
+
+	/// Writes a name, unless names are ignored.
+	pub fn write_name(&mut self, value: &str) {
+		if !self.options.ignore_names {
+			self.write_str(value);
+		}
+	}
+
+	pub fn write_point2(&mut self, value: Point2<f32>) {
+		self.write_f32(value.x);
+		self.write_f32(value.y);
+	}
+
+	pub fn write_point3(&mut self, value: Point3<f32>) {
+		self.write_f32(value.x);
+		self.write_f32(value.y);
+		self.write_f32(value.z);
+	}
+
+	pub fn write_vector3(&mut self, value: Vector3<f32>) {
+		self.write_f32(value.x);
+		self.write_f32(value.y);
+		self.write_f32(value.z);
+	}
+
+	pub fn write_matrix4(&mut self, value: &Matrix4<f32>) {
+		let array: &[[f32; 4]; 4] = value.as_ref();
+
+		for column in array {
+			for &component in column {
+				self.write_f32(component);
+			}
+		}
+	}
+}
+
+impl Content for V2 {
+	fn hash_content(&self, h: &mut ContentHasher) {
+		h.write_u32(self.lod_levels.len() as u32);
+		for triangles in &self.lod_levels {
+			h.write_u32(triangles.len() as u32);
+
+			for triangle in triangles {
+				h.write_u32(triangle.0);
+				h.write_u32(triangle.1);
+				h.write_u32(triangle.2);
+			}
+		}
+
+		h.write_u32(self.materials.len() as u32);
+		for material in &self.materials {
+			h.write_name(&material.name);
+			h.write_u32(material.texture);
+
+			for selection in &material.triangles {
+				h.write_u32(selection.offset);
+				h.write_u32(selection.len);
+			}
+
+			h.write_u32(material.vertex_offset);
+			h.write_u32(material.vertex_count);
+			h.write_str(&material.texture_name);
+		}
+
+		h.write_u32(self.tag_points.len() as u32);
+		for tag_point in &self.tag_points {
+			h.write_name(tag_point);
+		}
+
+		h.write_u32(self.frames.len() as u32);
+		for frame in &self.frames {
+			h.write_u32(frame.vertices.len() as u32);
+
+			for vertex in &frame.vertices {
+				h.write_point3(vertex.position);
+				h.write_vector3(vertex.normal);
+				h.write_point2(vertex.texture);
+			}
+
+			for &tag_point in &frame.tag_points {
+				h.write_point3(tag_point);
+			}
+
+			h.write_matrix4(&frame.transform);
+		}
+	}
+}
+
+impl<M> Content for Scene<M> where M: Model + Content {
+	fn hash_content(&self, h: &mut ContentHasher) {
+		h.write_name(&self.name);
+		self.model.hash_content(h);
+
+		h.write_u32(self.children.len() as u32);
+		for child in &self.children {
+			child.hash_content(h);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use samples::v2_scene;
+	use super::{Fingerprint, Options};
+
+	#[test]
+	fn equal_scenes_have_equal_fingerprints() {
+		let (a, mut b) = (v2_scene(), v2_scene());
+		assert_eq!(Fingerprint::of(&a, Options::default()), Fingerprint::of(&b, Options::default()));
+
+		// Derived and trailing data is left out, and the sample transform holds a NaN with its own payload.
+		b.model.center.x += 1.0;
+		b.trailing.clear();
+		b.model.frames[0].transform.x.w = f32::NAN;
+		b.model.frames[0].transform.y.z = 0.0;
+		assert_eq!(Fingerprint::of(&a, Options::default()), Fingerprint::of(&b, Options::default()));
+
+		b.model.frames[0].vertices[0].position.y += 1.0;
+		assert_ne!(Fingerprint::of(&a, Options::default()), Fingerprint::of(&b, Options::default()));
+	}
+
+	#[test]
+	fn renamed_scenes_match_only_when_names_are_ignored() {
+		let ignore_names = Options { ignore_names: true, ..Options::default() };
+		let a = v2_scene();
+
+		let mut b = v2_scene();
+		b.name = "renamed".to_string();
+		b.model.materials[0].name = "renamed".to_string();
+		b.model.tag_points[0] = "renamed".to_string();
+		b.children[0].name = "renamed".to_string();
+
+		assert_ne!(Fingerprint::of(&a, Options::default()), Fingerprint::of(&b, Options::default()));
+		assert_eq!(Fingerprint::of(&a, ignore_names), Fingerprint::of(&b, ignore_names));
+
+		// Texture names are content, not names.
+		b.model.materials[0].texture_name = "other".to_string();
+		assert_ne!(Fingerprint::of(&a, ignore_names), Fingerprint::of(&b, ignore_names));
+	}
+
+	#[test]
+	fn float_noise_matches_only_when_quantized() {
+		let quantize = Options { quantize: Some(0.01), ..Options::default() };
+		let a = v2_scene();
+
+		let mut b = v2_scene();
+		b.model.frames[0].vertices[1].position.x += 0.0001;
+		b.model.frames[0].tag_points[0].z -= 0.0001;
+
+		assert_ne!(Fingerprint::of(&a, Options::default()), Fingerprint::of(&b, Options::default()));
+		assert_eq!(Fingerprint::of(&a, quantize), Fingerprint::of(&b, quantize));
+
+		b.model.frames[0].vertices[1].position.x += 0.1;
+		assert_ne!(Fingerprint::of(&a, quantize), Fingerprint::of(&b, quantize));
+	}
+}
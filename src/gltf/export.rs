@@ -0,0 +1,715 @@
+use std::io::{self, Write};
+use std::collections::HashMap;
+use byteorder::{WriteBytesExt, LittleEndian};
+use cgmath::{Matrix4, SquareMatrix};
+use json::Value;
+use scene::Scene;
+use transform::{Decomposition, Parts};
+use v2::{self, V2};
+use super::{TAG_POINT_PREFIX, FLOAT, UNSIGNED_INT, GLB_MAGIC, GLB_JSON, GLB_BIN, base64_encode, uri_encode};
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ExportOptions {
+	/// Frames per second used for the animation track.
+	pub frame_rate: f32
+}
+
+impl Default for ExportOptions {
+	fn default() -> Self {
+		ExportOptions {
+			frame_rate: 30.0
+		}
+	}
+}
+
+/// A glTF document together with its binary buffer, ready to be written as either a `.gltf` or a `.glb` file.
+#[derive(Debug)]
+pub struct Export {
+	json: Value,
+	buffer: Vec<u8>,
+	warnings: Vec<String>
+}
+
+impl Export {
+	/// Converts a scene. Every node becomes a glTF node, every material selection becomes a primitive,
+	/// frames after the first become morph targets driven by an animation, tag points become empty
+	/// nodes named with `TAG_POINT_PREFIX`, and lower LOD levels are attached with `MSFT_lod`. Materials with
+	/// a special meaning, such as player color, give its name in `extras.special`. Tag points missing from
+	/// any frame are left out. Transforms with shear or projection cannot be split into a translation,
+	/// rotation and scale, so they are written as the static matrix of the first frame and a warning is given.
+	///
+	/// Fails with `InvalidInput` if the frame rate is not positive, or if the frames of a model do not all
+	/// have the same number of vertices.
+	pub fn new(scene: &Scene<V2>, options: &ExportOptions) -> io::Result<Self> {
+		if !(options.frame_rate.is_finite() && options.frame_rate > 0.0) {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Frame rate must be positive, not {}", options.frame_rate)));
+		}
+
+		let mut builder = Builder {
+			options,
+			buffer: Vec::new(),
+			buffer_views: Vec::new(),
+			accessors: Vec::new(),
+			meshes: Vec::new(),
+			nodes: Vec::new(),
+			materials: Vec::new(),
+			textures: Vec::new(),
+			images: HashMap::new(),
+			samplers: Vec::new(),
+			channels: Vec::new(),
+			timelines: HashMap::new(),
+			uses_lod: false,
+			warnings: Vec::new()
+		};
+
+		let root = builder.node(scene)?;
+
+		Ok(builder.finish(root))
+	}
+
+	/// Parts of the scene that could not be represented exactly, as readable messages.
+	pub fn warnings(&self) -> &[String] {
+		&self.warnings
+	}
+
+	/// The binary buffer referenced by the document.
+	pub fn buffer(&self) -> &[u8] {
+		&self.buffer
+	}
+
+	/// Writes the document as JSON. If `buffer_uri` is given, the buffer is referenced by that URI and must
+	/// be written there separately; otherwise it is embedded as a base64 data URI.
+	pub fn write_gltf<W>(&self, w: &mut W, buffer_uri: Option<&str>) -> io::Result<()> where W: Write {
+		let uri = match buffer_uri {
+			Some(uri) => uri.to_string(),
+			None => format!("data:application/octet-stream;base64,{}", base64_encode(&self.buffer))
+		};
+
+		write!(w, "{}", self.json_with_buffer(Some(uri)))
+	}
+
+	/// Writes a binary glTF file, with the buffer stored in the BIN chunk.
+	pub fn write_glb<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
+		let mut json = self.json_with_buffer(None).to_string().into_bytes();
+		while !json.len().is_multiple_of(4) {
+			json.push(b' ');
+		}
+
+		let mut bin = self.buffer.clone();
+		while !bin.len().is_multiple_of(4) {
+			bin.push(0);
+		}
+
+		let bin_chunk = if bin.is_empty() { 0 } else { 8 + bin.len() };
+
+		w.write_u32::<LittleEndian>(GLB_MAGIC)?;
+		w.write_u32::<LittleEndian>(2)?;
+		w.write_u32::<LittleEndian>((12 + 8 + json.len() + bin_chunk) as u32)?;
+
+		w.write_u32::<LittleEndian>(json.len() as u32)?;
+		w.write_u32::<LittleEndian>(GLB_JSON)?;
+		w.write_all(&json)?;
+
+		if !bin.is_empty() {
+			w.write_u32::<LittleEndian>(bin.len() as u32)?;
+			w.write_u32::<LittleEndian>(GLB_BIN)?;
+			w.write_all(&bin)?;
+		}
+
+		Ok(())
+	}
+
+	fn json_with_buffer(&self, uri: Option<String>) -> Value {
+		let mut json = self.json.clone();
+
+		if !self.buffer.is_empty() {
+			let mut buffer = Value::object().with("byteLength", self.buffer.len());
+
+			if let Some(uri) = uri {
+				buffer.insert("uri", uri);
+			}
+
+			json.insert("buffers", vec![buffer]);
+		}
+
+		json
+	}
+}
+
+/// Accessors shared by all LOD levels of a model.
+struct Attributes {
+	position: usize,
+	normal: usize,
+	texcoord: usize,
+	targets: Vec<(usize, usize)>
+}
+
+/// The transform of a node, which its LOD levels share.
+struct Transform {
+	/// Properties set for the first frame.
+	properties: Vec<(&'static str, Vec<f32>)>,
+	/// Output accessors of the animated properties, empty if the transform does not change.
+	animated: Vec<(&'static str, usize)>,
+	frames: usize
+}
+
+struct Builder<'o> {
+	options: &'o ExportOptions,
+	buffer: Vec<u8>,
+	buffer_views: Vec<Value>,
+	accessors: Vec<Value>,
+	meshes: Vec<Value>,
+	nodes: Vec<Value>,
+	materials: Vec<Value>,
+	textures: Vec<Value>,
+	/// Texture index for each distinct texture name.
+	images: HashMap<String, usize>,
+	samplers: Vec<Value>,
+	channels: Vec<Value>,
+	/// Keyframe time accessors, by number of frames.
+	timelines: HashMap<usize, usize>,
+	uses_lod: bool,
+	warnings: Vec<String>
+}
+
+impl<'o> Builder<'o> {
+	fn node(&mut self, scene: &Scene<V2>) -> io::Result<usize> {
+		let index = self.nodes.len();
+		self.nodes.push(Value::Null);
+
+		let model = &scene.model;
+		let mut node = Value::object().with("name", scene.name.as_str());
+		let mut children = Vec::new();
+
+		let transform = self.transform(&scene.name, model);
+		self.apply(&mut node, index, &transform);
+
+		let materials: Vec<usize> = model.materials.iter().map(|material| self.material(material)).collect();
+
+		if let Some(attributes) = self.attributes(&scene.name, model)? {
+			let meshes: Vec<Option<usize>> = (0..model.lod_levels.len())
+				.map(|lod| self.mesh(model, lod, &attributes, &materials, &format!("{} LOD {}", scene.name, lod)))
+				.collect();
+
+			if let Some(&Some(mesh)) = meshes.first() {
+				node.insert("mesh", mesh);
+				self.animate_weights(index, model.frames.len());
+			}
+
+			let mut lods = Vec::new();
+
+			for (lod, mesh) in meshes.into_iter().enumerate().skip(1) {
+				if let Some(mesh) = mesh {
+					let lod_index = self.nodes.len();
+
+					let mut lod_node = Value::object()
+						.with("name", format!("{} LOD {}", scene.name, lod))
+						.with("mesh", mesh);
+
+					self.apply(&mut lod_node, lod_index, &transform);
+					self.nodes.push(lod_node);
+
+					self.animate_weights(lod_index, model.frames.len());
+					lods.push(lod_index);
+				}
+			}
+
+			if !lods.is_empty() {
+				self.uses_lod = true;
+				node.insert("extensions", Value::object().with("MSFT_lod", Value::object().with("ids", lods)));
+			}
+		}
+
+		for (i, name) in model.tag_points.iter().enumerate() {
+			let positions: Option<Vec<[f32; 3]>> = model.frames.iter().map(|frame| {
+				frame.tag_points.get(i).map(|position| [position.x, position.y, position.z])
+			}).collect();
+
+			let positions = match positions {
+				Some(positions) => positions,
+				None => continue
+			};
+
+			let tag_index = self.nodes.len();
+
+			let mut tag = Value::object().with("name", format!("{}{}", TAG_POINT_PREFIX, name));
+
+			if let Some(first) = positions.first() {
+				tag.insert("translation", first.to_vec());
+			}
+
+			self.nodes.push(tag);
+
+			if positions.len() > 1 {
+				let output = self.floats(&positions.concat(), "VEC3", None, false);
+				self.channel(tag_index, "translation", positions.len(), output);
+			}
+
+			children.push(tag_index);
+		}
+
+		for child in &scene.children {
+			children.push(self.node(child)?);
+		}
+
+		if !children.is_empty() {
+			node.insert("children", children);
+		}
+
+		self.nodes[index] = node;
+		Ok(index)
+	}
+
+	/// Splits the frame transforms into the translation, rotation and scale of the first frame, and
+	/// animates them if they change between frames.
+	fn transform(&mut self, name: &str, model: &V2) -> Transform {
+		let mut transform = Transform {
+			properties: Vec::new(),
+			animated: Vec::new(),
+			frames: model.frames.len()
+		};
+
+		if model.frames.iter().all(|frame| frame.transform == Matrix4::identity()) {
+			return transform;
+		}
+
+		let decompositions: Vec<Decomposition> = model.frames.iter().map(|frame| Decomposition::new(frame.transform)).collect();
+
+		if decompositions.iter().any(|decomposition| decomposition.has_shear() || !decomposition.is_affine()) {
+			let matrix: &[f32; 16] = model.frames[0].transform.as_ref();
+			transform.properties.push(("matrix", matrix.to_vec()));
+
+			self.warnings.push(if model.frames.iter().all(|frame| frame.transform == model.frames[0].transform) {
+				format!("{} has a sheared or projective transform, written as a matrix", name)
+			} else {
+				format!("{} has a sheared or projective transform, written as the matrix of the first frame without animation", name)
+			});
+
+			return transform;
+		}
+
+		let parts: Vec<Parts> = decompositions.iter().map(|decomposition| decomposition.parts).collect();
+		let first = parts[0];
+
+		transform.properties.push(("translation", vec![first.translation.x, first.translation.y, first.translation.z]));
+		transform.properties.push(("rotation", vec![first.rotation.v.x, first.rotation.v.y, first.rotation.v.z, first.rotation.s]));
+		transform.properties.push(("scale", vec![first.scale.x, first.scale.y, first.scale.z]));
+
+		if parts.iter().all(|part| *part == first) {
+			return transform;
+		}
+
+		let translations: Vec<f32> = parts.iter().flat_map(|p| vec![p.translation.x, p.translation.y, p.translation.z]).collect();
+		let rotations: Vec<f32> = parts.iter().flat_map(|p| vec![p.rotation.v.x, p.rotation.v.y, p.rotation.v.z, p.rotation.s]).collect();
+		let scales: Vec<f32> = parts.iter().flat_map(|p| vec![p.scale.x, p.scale.y, p.scale.z]).collect();
+
+		transform.animated.push(("translation", self.floats(&translations, "VEC3", None, false)));
+		transform.animated.push(("rotation", self.floats(&rotations, "VEC4", None, false)));
+		transform.animated.push(("scale", self.floats(&scales, "VEC3", None, false)));
+
+		transform
+	}
+
+	/// Gives a node the transform, and animates it.
+	fn apply(&mut self, node: &mut Value, index: usize, transform: &Transform) {
+		for (property, values) in &transform.properties {
+			node.insert(property, values.clone());
+		}
+
+		for &(property, output) in &transform.animated {
+			self.channel(index, property, transform.frames, output);
+		}
+	}
+
+	/// Writes the vertex attributes of the first frame, and the differences of every other frame as morph targets.
+	fn attributes(&mut self, name: &str, model: &V2) -> io::Result<Option<Attributes>> {
+		let base = match model.frames.first() {
+			Some(frame) if !frame.vertices.is_empty() => frame,
+			_ => return Ok(None)
+		};
+
+		for (i, frame) in model.frames.iter().enumerate() {
+			if frame.vertices.len() != base.vertices.len() {
+				return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+					"Frame {} of {} has {} vertices, but the first frame has {}", i, name, frame.vertices.len(), base.vertices.len()
+				)));
+			}
+		}
+
+		let positions: Vec<f32> = base.vertices.iter().flat_map(|v| vec![v.position.x, v.position.y, v.position.z]).collect();
+		let normals: Vec<f32> = base.vertices.iter().flat_map(|v| vec![v.normal.x, v.normal.y, v.normal.z]).collect();
+		let texcoords: Vec<f32> = base.vertices.iter().flat_map(|v| vec![v.texture.x, v.texture.y]).collect();
+
+		let mut attributes = Attributes {
+			position: self.floats(&positions, "VEC3", Some(ARRAY_BUFFER), true),
+			normal: self.floats(&normals, "VEC3", Some(ARRAY_BUFFER), false),
+			texcoord: self.floats(&texcoords, "VEC2", Some(ARRAY_BUFFER), false),
+			targets: Vec::new()
+		};
+
+		for frame in &model.frames[1..] {
+			let positions: Vec<f32> = frame.vertices.iter().zip(&base.vertices)
+				.flat_map(|(v, b)| vec![v.position.x - b.position.x, v.position.y - b.position.y, v.position.z - b.position.z])
+				.collect();
+
+			let normals: Vec<f32> = frame.vertices.iter().zip(&base.vertices)
+				.flat_map(|(v, b)| vec![v.normal.x - b.normal.x, v.normal.y - b.normal.y, v.normal.z - b.normal.z])
+				.collect();
+
+			attributes.targets.push((
+				self.floats(&positions, "VEC3", Some(ARRAY_BUFFER), true),
+				self.floats(&normals, "VEC3", Some(ARRAY_BUFFER), false)
+			));
+		}
+
+		Ok(Some(attributes))
+	}
+
+	/// Creates a mesh with one primitive for each material that selects triangles of this LOD level.
+	fn mesh(&mut self, model: &V2, lod: usize, attributes: &Attributes, materials: &[usize], name: &str) -> Option<usize> {
+		let mut primitives = Vec::new();
+
+		for (material, &material_index) in model.materials.iter().zip(materials) {
+			let triangles = model.selection(material, lod);
+
+			if triangles.is_empty() {
+				continue;
+			}
+
+			let indices: Vec<u32> = triangles.iter().flat_map(|t| vec![t.0, t.1, t.2]).collect();
+
+			let mut primitive = Value::object()
+				.with("attributes", Value::object()
+					.with("POSITION", attributes.position)
+					.with("NORMAL", attributes.normal)
+					.with("TEXCOORD_0", attributes.texcoord))
+				.with("indices", self.indices(&indices))
+				.with("material", material_index);
+
+			if !attributes.targets.is_empty() {
+				primitive.insert("targets", attributes.targets.iter().map(|&(position, normal)| {
+					Value::object().with("POSITION", position).with("NORMAL", normal)
+				}).collect::<Vec<_>>());
+			}
+
+			primitives.push(primitive);
+		}
+
+		if primitives.is_empty() {
+			return None;
+		}
+
+		let mut mesh = Value::object().with("name", name).with("primitives", primitives);
+
+		if !attributes.targets.is_empty() {
+			mesh.insert("weights", vec![0.0f32; attributes.targets.len()]);
+			mesh.insert("extras", Value::object().with("targetNames", (1..=attributes.targets.len())
+				.map(|frame| format!("frame {}", frame))
+				.collect::<Vec<_>>()));
+		}
+
+		self.meshes.push(mesh);
+		Some(self.meshes.len() - 1)
+	}
+
+	fn material(&mut self, material: &v2::Material) -> usize {
+		let mut pbr = Value::object()
+			.with("metallicFactor", 0.0f32)
+			.with("roughnessFactor", 1.0f32);
+
+		if !material.texture_name.is_empty() {
+			let texture = match self.images.get(&material.texture_name) {
+				Some(&texture) => texture,
+				None => {
+					let texture = self.textures.len();
+
+					self.textures.push(Value::object().with("source", texture));
+					self.images.insert(material.texture_name.clone(), texture);
+
+					texture
+				}
+			};
+
+			pbr.insert("baseColorTexture", Value::object().with("index", texture));
+		}
+
+		let mut json = Value::object()
+			.with("name", material.name.as_str())
+			.with("pbrMetallicRoughness", pbr);
+
+		if let Some(special) = material.special() {
+			json.insert("extras", Value::object().with("special", special.name()));
+		}
+
+		self.materials.push(json);
+
+		self.materials.len() - 1
+	}
+
+	/// Animates the morph target weights of a node so that each frame is shown in turn.
+	fn animate_weights(&mut self, node: usize, frames: usize) {
+		if frames < 2 {
+			return;
+		}
+
+		let targets = frames - 1;
+		let mut weights = vec![0.0f32; frames * targets];
+
+		for frame in 1..frames {
+			weights[frame * targets + frame - 1] = 1.0;
+		}
+
+		let output = self.floats(&weights, "SCALAR", None, false);
+		self.channel(node, "weights", frames, output);
+	}
+
+	fn channel(&mut self, node: usize, path: &str, frames: usize, output: usize) {
+		let input = match self.timelines.get(&frames) {
+			Some(&input) => input,
+			None => {
+				let times: Vec<f32> = (0..frames).map(|frame| frame as f32 / self.options.frame_rate).collect();
+				let input = self.floats(&times, "SCALAR", None, true);
+
+				self.timelines.insert(frames, input);
+				input
+			}
+		};
+
+		self.channels.push(Value::object()
+			.with("sampler", self.samplers.len())
+			.with("target", Value::object().with("node", node).with("path", path)));
+
+		self.samplers.push(Value::object()
+			.with("input", input)
+			.with("output", output)
+			.with("interpolation", "LINEAR"));
+	}
+
+	fn floats(&mut self, data: &[f32], kind: &str, target: Option<u32>, bounds: bool) -> usize {
+		let components = match kind {
+			"SCALAR" => 1,
+			"VEC2" => 2,
+			"VEC3" => 3,
+			_ => 4
+		};
+
+		let view = self.view(target, |buffer| for &value in data {
+			buffer.write_f32::<LittleEndian>(value).unwrap();
+		});
+
+		let mut accessor = Value::object()
+			.with("bufferView", view)
+			.with("componentType", FLOAT)
+			.with("count", data.len() / components)
+			.with("type", kind);
+
+		if bounds {
+			let mut min = vec![f32::INFINITY; components];
+			let mut max = vec![f32::NEG_INFINITY; components];
+
+			for element in data.chunks(components) {
+				for (i, &value) in element.iter().enumerate() {
+					min[i] = min[i].min(value);
+					max[i] = max[i].max(value);
+				}
+			}
+
+			accessor.insert("min", min);
+			accessor.insert("max", max);
+		}
+
+		self.accessors.push(accessor);
+		self.accessors.len() - 1
+	}
+
+	fn indices(&mut self, data: &[u32]) -> usize {
+		let view = self.view(Some(ELEMENT_ARRAY_BUFFER), |buffer| for &value in data {
+			buffer.write_u32::<LittleEndian>(value).unwrap();
+		});
+
+		self.accessors.push(Value::object()
+			.with("bufferView", view)
+			.with("componentType", UNSIGNED_INT)
+			.with("count", data.len())
+			.with("type", "SCALAR"));
+
+		self.accessors.len() - 1
+	}
+
+	fn view<F>(&mut self, target: Option<u32>, write: F) -> usize where F: FnOnce(&mut Vec<u8>) {
+		let offset = self.buffer.len();
+		write(&mut self.buffer);
+
+		let mut view = Value::object()
+			.with("buffer", 0u32)
+			.with("byteOffset", offset)
+			.with("byteLength", self.buffer.len() - offset);
+
+		if let Some(target) = target {
+			view.insert("target", target);
+		}
+
+		self.buffer_views.push(view);
+		self.buffer_views.len() - 1
+	}
+
+	fn finish(self, root: usize) -> Export {
+		let mut images: Vec<(&String, &usize)> = self.images.iter().collect();
+		images.sort_by_key(|&(_, &index)| index);
+
+		let mut json = Value::object()
+			.with("asset", Value::object().with("version", "2.0").with("generator", "cem"))
+			.with("scene", 0u32)
+			.with("scenes", vec![Value::object().with("nodes", vec![root])])
+			.with("nodes", self.nodes);
+
+		if !self.materials.is_empty() {
+			json.insert("materials", self.materials);
+		}
+
+		if !self.meshes.is_empty() {
+			json.insert("meshes", self.meshes);
+		}
+
+		if !self.accessors.is_empty() {
+			json.insert("accessors", self.accessors);
+			json.insert("bufferViews", self.buffer_views);
+		}
+
+		if !self.textures.is_empty() {
+			json.insert("textures", self.textures);
+			json.insert("images", images.into_iter()
+				.map(|(name, _)| Value::object().with("uri", uri_encode(name)))
+				.collect::<Vec<_>>());
+		}
+
+		if !self.channels.is_empty() {
+			json.insert("animations", vec![Value::object()
+				.with("name", "frames")
+				.with("channels", self.channels)
+				.with("samplers", self.samplers)]);
+		}
+
+		if self.uses_lod {
+			json.insert("extensionsUsed", vec!["MSFT_lod"]);
+		}
+
+		Export {
+			json,
+			buffer: self.buffer,
+			warnings: self.warnings
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use cgmath::{Matrix4, Vector3};
+	use json::Value;
+	use samples::v2_scene;
+	use scene::Scene;
+	use v2::{self, V2};
+	use gltf::TAG_POINT_PREFIX;
+	use super::{Export, ExportOptions};
+
+	fn document(scene: &Scene<V2>) -> Value {
+		let mut json = Vec::new();
+		Export::new(scene, &ExportOptions::default()).unwrap().write_gltf(&mut json, None).unwrap();
+
+		Value::parse(&String::from_utf8(json).unwrap()).unwrap()
+	}
+
+	fn node<'v>(document: &'v Value, name: &str) -> (usize, &'v Value) {
+		document.get("nodes").unwrap().elements().iter().enumerate()
+			.find(|&(_, node)| node.get("name").and_then(Value::as_str) == Some(name))
+			.unwrap()
+	}
+
+	fn animated_paths(document: &Value, node: usize) -> Vec<String> {
+		let mut paths: Vec<String> = document.get("animations").unwrap().elements()[0].get("channels").unwrap().elements().iter()
+			.map(|channel| channel.get("target").unwrap())
+			.filter(|target| target.get("node").and_then(Value::as_usize) == Some(node))
+			.map(|target| target.get("path").and_then(Value::as_str).unwrap().to_string())
+			.collect();
+
+		paths.sort();
+		paths
+	}
+
+	/// The sample scene with a second frame, moved along X.
+	fn moving_scene() -> Scene<V2> {
+		let mut scene = v2_scene();
+		scene.model.frames[0].transform = Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0));
+
+		let mut second = {
+			let first = &scene.model.frames[0];
+			v2::Frame::from_vertices(first.vertices.clone(), first.tag_points.clone(), scene.model.center)
+		};
+
+		second.transform = Matrix4::from_translation(Vector3::new(4.0, 2.0, 3.0));
+		scene.model.frames.push(second);
+
+		scene
+	}
+
+	#[test]
+	fn tag_point_missing_from_one_frame_is_left_out() {
+		let mut scene = moving_scene();
+		assert!(document(&scene).to_string().contains(TAG_POINT_PREFIX));
+
+		scene.model.frames[1].tag_points.clear();
+		assert!(!document(&scene).to_string().contains(TAG_POINT_PREFIX));
+	}
+
+	#[test]
+	fn lod_nodes_share_the_transform_and_animation() {
+		let document = document(&moving_scene());
+
+		let (root, root_node) = node(&document, "Scene Root");
+		let (lod, lod_node) = node(&document, "Scene Root LOD 1");
+
+		for property in &["translation", "rotation", "scale"] {
+			assert_eq!(lod_node.get(property), root_node.get(property), "{}", property);
+		}
+
+		assert_eq!(lod_node.get("translation").unwrap().to_string(), "[1,2,3]");
+		assert_eq!(animated_paths(&document, root), vec!["rotation", "scale", "translation", "weights"]);
+		assert_eq!(animated_paths(&document, lod), animated_paths(&document, root));
+	}
+
+	#[test]
+	fn projective_transform_is_written_as_a_matrix() {
+		let scene = v2_scene();
+		let export = Export::new(&scene, &ExportOptions::default()).unwrap();
+
+		let mut json = Vec::new();
+		export.write_gltf(&mut json, None).unwrap();
+		let document = Value::parse(&String::from_utf8(json).unwrap()).unwrap();
+
+		let (_, root) = node(&document, "Scene Root");
+		assert!(root.get("translation").is_none());
+		assert_eq!(root.get("matrix").unwrap().elements().len(), 16);
+		assert_eq!(export.warnings().len(), 1);
+	}
+
+	#[test]
+	fn frames_with_different_vertex_counts_are_rejected() {
+		let mut scene = moving_scene();
+		scene.model.frames[1].vertices.pop();
+
+		let error = Export::new(&scene, &ExportOptions::default()).unwrap_err();
+		assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	fn frame_rate_must_be_positive() {
+		for &frame_rate in &[0.0, -30.0, f32::NAN, f32::INFINITY] {
+			let error = Export::new(&v2_scene(), &ExportOptions { frame_rate }).unwrap_err();
+			assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{}", frame_rate);
+		}
+	}
+}
@@ -0,0 +1,1101 @@
+//! Each node with a mesh becomes a scene node, and nodes without a mesh are skipped over, with their
+//! children attached to the closest node above them that has a mesh. The first node with a mesh becomes
+//! the root, and any others at the top level become its children.
+//!
+//! Each material used by a mesh becomes one material, with its own range of vertices and one triangle
+//! selection per LOD level. Lower LOD levels are read from the `MSFT_lod` extension.
+//!
+//! Frames are sampled at every keyframe of the animation (the one named `frames`, or otherwise the first)
+//! that moves the node or its tag points, or changes its morph target weights. Without an animation, the
+//! rest pose becomes the first frame and each morph target becomes another. The world transform of the
+//! node becomes the frame transform, and tag points are stored relative to it.
+
+use std::io;
+use std::fs;
+use std::str;
+use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use byteorder::{ByteOrder, LittleEndian};
+use cgmath::{Point2, Point3, Vector3, Matrix4, Quaternion, SquareMatrix, InnerSpace, EuclideanSpace, Transform};
+use collider::CenterBuilder;
+use json::Value;
+use scene::Scene;
+use transform::Parts;
+use v2::{V2, Material, TriangleSelection, Frame, Vertex, Triangle};
+use super::{TAG_POINT_PREFIX, BYTE, UNSIGNED_BYTE, SHORT, UNSIGNED_SHORT, UNSIGNED_INT, FLOAT, GLB_MAGIC, GLB_JSON, GLB_BIN, base64_decode, uri_decode};
+
+const TRIANGLES: usize = 4;
+
+/// Required extensions that the import understands.
+const SUPPORTED_EXTENSIONS: &[&str] = &["MSFT_lod", "KHR_mesh_quantization"];
+
+/// Reads a `.gltf` or `.glb` file. External buffers are passed to `load` by their decoded relative URI.
+pub fn import<F>(data: &[u8], load: F) -> io::Result<Scene<V2>> where F: FnMut(&str) -> io::Result<Vec<u8>> {
+	Document::parse(data, load)?.scene()
+}
+
+/// Reads a `.gltf` or `.glb` file from disk, loading external buffers relative to it.
+pub fn import_file<P>(path: P) -> io::Result<Scene<V2>> where P: AsRef<Path> {
+	let path = path.as_ref();
+	let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+	import(&fs::read(path)?, |uri| fs::read(directory.join(uri)))
+}
+
+fn invalid<T>(message: String) -> io::Result<T> {
+	Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+fn list<'v>(value: &'v Value, key: &str) -> &'v [Value] {
+	value.get(key).map(Value::elements).unwrap_or(&[])
+}
+
+fn index(value: &Value, key: &str) -> Option<usize> {
+	value.get(key).and_then(Value::as_usize)
+}
+
+fn floats(value: &Value, key: &str) -> Option<Vec<f32>> {
+	value.get(key).map(|value| value.elements().iter().map(|e| e.as_f64().unwrap_or(0.0) as f32).collect())
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Property {
+	Translation,
+	Rotation,
+	Scale,
+	Weights
+}
+
+/// An animation channel, reduced to one value per keyframe.
+struct Channel {
+	node: usize,
+	property: Property,
+	times: Vec<f32>,
+	values: Vec<f32>,
+	step: bool
+}
+
+impl Channel {
+	fn sample(&self, time: f32) -> Vec<f32> {
+		let width = self.values.len() / self.times.len();
+		let key = |i: usize| &self.values[i * width..(i + 1) * width];
+
+		let i = match self.times.iter().position(|&t| t > time) {
+			Some(0) => return key(0).to_vec(),
+			None => return key(self.times.len() - 1).to_vec(),
+			Some(i) => i
+		};
+
+		let (a, b) = (key(i - 1), key(i));
+		let amount = (time - self.times[i - 1]) / (self.times[i] - self.times[i - 1]);
+
+		if self.step {
+			a.to_vec()
+		} else if self.property == Property::Rotation {
+			let a = Quaternion::new(a[3], a[0], a[1], a[2]);
+			let mut b = Quaternion::new(b[3], b[0], b[1], b[2]);
+
+			if a.dot(b) < 0.0 {
+				b = -b;
+			}
+
+			let q = a.slerp(b, amount);
+			vec![q.v.x, q.v.y, q.v.z, q.s]
+		} else {
+			a.iter().zip(b).map(|(a, b)| a + (b - a) * amount).collect()
+		}
+	}
+}
+
+/// A node with a mesh, along with the tag points and nodes with meshes below it.
+struct Part {
+	node: usize,
+	tags: Vec<usize>,
+	children: Vec<Part>
+}
+
+/// The accessors of a primitive that vertices are read from.
+#[derive(PartialEq)]
+struct SourceKey {
+	position: usize,
+	normal: Option<usize>,
+	texcoord: Option<usize>,
+	targets: Vec<(Option<usize>, Option<usize>)>
+}
+
+/// Position and normal differences of a morph target.
+type Target = (Option<Vec<f32>>, Option<Vec<f32>>);
+
+struct Source {
+	key: SourceKey,
+	positions: Vec<f32>,
+	normals: Option<Vec<f32>>,
+	texcoords: Option<Vec<f32>>,
+	targets: Vec<Target>
+}
+
+/// The vertices and triangles of one material.
+struct Group {
+	material: Option<usize>,
+	/// Source and index of each vertex.
+	vertices: Vec<(usize, u32)>,
+	lookup: HashMap<(usize, u32), u32>,
+	/// Triangles of each LOD level, indexing `vertices`.
+	triangles: Vec<Vec<Triangle>>
+}
+
+struct Document {
+	json: Value,
+	buffers: Vec<Vec<u8>>,
+	parents: Vec<Option<usize>>,
+	channels: Vec<Channel>
+}
+
+impl Document {
+	fn parse<F>(data: &[u8], mut load: F) -> io::Result<Self> where F: FnMut(&str) -> io::Result<Vec<u8>> {
+		let (text, mut bin) = if data.len() >= 12 && LittleEndian::read_u32(data) == GLB_MAGIC {
+			glb(data)?
+		} else {
+			(data, None)
+		};
+
+		let text = match str::from_utf8(text) {
+			Ok(text) => text.trim_start_matches('\u{feff}'),
+			Err(e) => return invalid(format!("The glTF JSON is not valid UTF-8: {}", e))
+		};
+
+		let json = match Value::parse(text) {
+			Ok(json) => json,
+			Err(e) => return invalid(format!("Invalid glTF JSON: {}", e))
+		};
+
+		let version = json.get("asset").and_then(|asset| asset.get("version")).and_then(Value::as_str).unwrap_or("");
+
+		if !version.starts_with("2.") {
+			return invalid(format!("Unsupported glTF version '{}', expected 2.x", version));
+		}
+
+		for extension in list(&json, "extensionsRequired") {
+			let extension = extension.as_str().unwrap_or("");
+
+			if !SUPPORTED_EXTENSIONS.contains(&extension) {
+				return invalid(format!("The file requires the unsupported extension {}", extension));
+			}
+		}
+
+		let mut buffers = Vec::new();
+
+		for (i, buffer) in list(&json, "buffers").iter().enumerate() {
+			let data = match buffer.get("uri").and_then(Value::as_str) {
+				Some(uri) if uri.starts_with("data:") => {
+					let comma = uri.find(',').unwrap_or(uri.len());
+
+					if !uri[..comma].ends_with(";base64") {
+						return invalid(format!("Buffer {} uses a data URI that is not base64 encoded", i));
+					}
+
+					match base64_decode(&uri[(comma + 1).min(uri.len())..]) {
+						Some(data) => data,
+						None => return invalid(format!("Buffer {} has an invalid base64 data URI", i))
+					}
+				},
+				Some(uri) => {
+					let name = uri_decode(uri);
+
+					load(&name).map_err(|e| io::Error::new(e.kind(), format!("Failed to load buffer {} from '{}': {}", i, name, e)))?
+				},
+				None if i == 0 => match bin.take() {
+					Some(bin) => bin,
+					None => return invalid("Buffer 0 has no URI and there is no GLB binary chunk".to_string())
+				},
+				None => return invalid(format!("Buffer {} has no URI", i))
+			};
+
+			let length = index(buffer, "byteLength").unwrap_or(0);
+
+			if data.len() < length {
+				return invalid(format!("Buffer {} is {} bytes long, but should be {}", i, data.len(), length));
+			}
+
+			buffers.push(data);
+		}
+
+		let nodes = list(&json, "nodes");
+		let mut parents = vec![None; nodes.len()];
+
+		for (parent, node) in nodes.iter().enumerate() {
+			for child in list(node, "children") {
+				match child.as_usize().and_then(|child| parents.get_mut(child)) {
+					Some(&mut Some(other)) => return invalid(format!("Node {} is a child of both node {} and node {}", child, other, parent)),
+					Some(slot) => *slot = Some(parent),
+					None => return invalid(format!("Node {} has an invalid child", parent))
+				}
+			}
+		}
+
+		// Walking up from every node must reach a root, or transforms would be computed forever.
+		let mut reaches_root = vec![false; nodes.len()];
+		let mut on_path = vec![false; nodes.len()];
+
+		for node in 0..nodes.len() {
+			let mut path = Vec::new();
+			let mut current = Some(node);
+
+			while let Some(at) = current {
+				if reaches_root[at] {
+					break;
+				}
+
+				if on_path[at] {
+					return invalid(format!("Node {} is its own ancestor", at));
+				}
+
+				on_path[at] = true;
+				path.push(at);
+				current = parents[at];
+			}
+
+			for at in path {
+				reaches_root[at] = true;
+				on_path[at] = false;
+			}
+		}
+
+		let mut document = Document {
+			json,
+			buffers,
+			parents,
+			channels: Vec::new()
+		};
+
+		document.channels = document.channels()?;
+
+		Ok(document)
+	}
+
+	fn element(&self, kind: &str, i: usize) -> io::Result<&Value> {
+		match list(&self.json, kind).get(i) {
+			Some(element) => Ok(element),
+			None => invalid(format!("Reference to {} {}, which does not exist", kind, i))
+		}
+	}
+
+	/// Reads the channels of the animation used for frames.
+	fn channels(&self) -> io::Result<Vec<Channel>> {
+		let animations = list(&self.json, "animations");
+
+		let animation = match animations.iter().find(|a| a.get("name").and_then(Value::as_str) == Some("frames")).or_else(|| animations.first()) {
+			Some(animation) => animation,
+			None => return Ok(Vec::new())
+		};
+
+		let samplers = list(animation, "samplers");
+		let mut channels = Vec::new();
+
+		for channel in list(animation, "channels") {
+			let target = match channel.get("target") {
+				Some(target) => target,
+				None => continue
+			};
+
+			let property = match target.get("path").and_then(Value::as_str) {
+				Some("translation") => Property::Translation,
+				Some("rotation") => Property::Rotation,
+				Some("scale") => Property::Scale,
+				Some("weights") => Property::Weights,
+				_ => continue
+			};
+
+			let node = match index(target, "node") {
+				Some(node) => node,
+				None => continue
+			};
+
+			let sampler = match index(channel, "sampler").and_then(|sampler| samplers.get(sampler)) {
+				Some(sampler) => sampler,
+				None => return invalid("An animation channel refers to a sampler that does not exist".to_string())
+			};
+
+			let times = self.accessor(index(sampler, "input").unwrap_or(usize::MAX), &["SCALAR"])?.0;
+			let (mut values, _) = self.accessor(index(sampler, "output").unwrap_or(usize::MAX), &["SCALAR", "VEC3", "VEC4"])?;
+
+			if times.is_empty() {
+				continue;
+			}
+
+			let interpolation = sampler.get("interpolation").and_then(Value::as_str).unwrap_or("LINEAR");
+
+			if interpolation == "CUBICSPLINE" {
+				// Frames are only sampled at keyframes, where the spline passes through the value itself.
+				// Each keyframe holds an in-tangent, the value and an out-tangent.
+				let width = values.len() / times.len() / 3;
+
+				if width == 0 || values.len() != times.len() * 3 * width {
+					return invalid(format!("A cubic spline sampler has {} keyframes but {} output values", times.len(), values.len()));
+				}
+
+				values = values.chunks(width * 3).flat_map(|key| key[width..width * 2].to_vec()).collect();
+			}
+
+			if values.len() % times.len() != 0 || values.is_empty() {
+				return invalid(format!("An animation sampler has {} keyframes but {} output values", times.len(), values.len()));
+			}
+
+			channels.push(Channel {
+				node,
+				property,
+				times: times.into_iter().map(|t| t as f32).collect(),
+				values: values.into_iter().map(|v| v as f32).collect(),
+				step: interpolation == "STEP"
+			});
+		}
+
+		Ok(channels)
+	}
+
+	/// Reads an accessor of one of the given types, converting normalized integers. Returns the values and the number of components.
+	fn accessor(&self, i: usize, types: &[&str]) -> io::Result<(Vec<f64>, usize)> {
+		let accessor = self.element("accessors", i)?;
+		let kind = accessor.get("type").and_then(Value::as_str).unwrap_or("");
+
+		if !types.contains(&kind) {
+			return invalid(format!("Accessor {} has type {}, expected {}", i, kind, types.join(" or ")));
+		}
+
+		let components = match kind {
+			"SCALAR" => 1,
+			"VEC2" => 2,
+			"VEC3" => 3,
+			_ => 4
+		};
+
+		let count = index(accessor, "count").unwrap_or(0);
+		let component_type = index(accessor, "componentType").unwrap_or(0) as u32;
+		let normalized = accessor.get("normalized") == Some(&Value::Bool(true));
+
+		let mut values = match index(accessor, "bufferView") {
+			Some(view) => self.read(view, index(accessor, "byteOffset").unwrap_or(0), count, components, component_type, normalized, true)?,
+			None => {
+				// Without a buffer view the count cannot be checked against any data, so bound it by all of the
+				// binary data instead. Anything larger cannot have come from an exporter.
+				let available: usize = self.buffers.iter().map(Vec::len).sum();
+
+				if count > available {
+					return invalid(format!("Accessor {} has {} elements, but there are only {} bytes of binary data", i, count, available));
+				}
+
+				vec![0.0; count * components]
+			}
+		};
+
+		if let Some(sparse) = accessor.get("sparse") {
+			let sparse_count = index(sparse, "count").unwrap_or(0);
+			let (indices, replacements) = match (sparse.get("indices"), sparse.get("values")) {
+				(Some(indices), Some(replacements)) => (indices, replacements),
+				_ => return invalid(format!("Accessor {} has incomplete sparse data", i))
+			};
+
+			let indices = self.read(
+				index(indices, "bufferView").unwrap_or(usize::MAX),
+				index(indices, "byteOffset").unwrap_or(0),
+				sparse_count, 1,
+				index(indices, "componentType").unwrap_or(0) as u32,
+				false, false
+			)?;
+
+			let replacements = self.read(
+				index(replacements, "bufferView").unwrap_or(usize::MAX),
+				index(replacements, "byteOffset").unwrap_or(0),
+				sparse_count, components, component_type, normalized, false
+			)?;
+
+			for (&target, replacement) in indices.iter().zip(replacements.chunks(components)) {
+				let target = target as usize;
+
+				if target >= count {
+					return invalid(format!("Sparse accessor {} replaces element {} of {}", i, target, count));
+				}
+
+				values[target * components..(target + 1) * components].copy_from_slice(replacement);
+			}
+		}
+
+		Ok((values, components))
+	}
+
+	/// Reads elements from a buffer view. Sparse data is always tightly packed, so the stride is only used if `strided` is set.
+	#[allow(clippy::too_many_arguments)]
+	fn read(&self, view: usize, offset: usize, count: usize, components: usize, component_type: u32, normalized: bool, strided: bool) -> io::Result<Vec<f64>> {
+		let size = match component_type {
+			BYTE | UNSIGNED_BYTE => 1,
+			SHORT | UNSIGNED_SHORT => 2,
+			UNSIGNED_INT | FLOAT => 4,
+			_ => return invalid(format!("Unknown accessor component type {}", component_type))
+		};
+
+		let view_json = self.element("bufferViews", view)?;
+		let buffer = self.buffers.get(index(view_json, "buffer").unwrap_or(usize::MAX));
+		let start = index(view_json, "byteOffset").unwrap_or(0);
+		let length = index(view_json, "byteLength").unwrap_or(0);
+
+		let bytes = match buffer.and_then(|buffer| buffer.get(start..start.checked_add(length)?)) {
+			Some(bytes) => bytes,
+			None => return invalid(format!("Buffer view {} lies outside of its buffer", view))
+		};
+
+		let stride = match index(view_json, "byteStride") {
+			Some(stride) if strided => stride,
+			_ => size * components
+		};
+
+		// The count comes from the file, so never reserve more than the view could possibly hold.
+		let mut values = Vec::with_capacity(count.min(bytes.len() / size) * components);
+
+		for element in 0..count {
+			for component in 0..components {
+				let at = element.checked_mul(stride)
+					.and_then(|at| at.checked_add(offset))
+					.and_then(|at| at.checked_add(component * size));
+
+				let bytes = match at.and_then(|at| bytes.get(at..at.checked_add(size)?)) {
+					Some(bytes) => bytes,
+					None => return invalid(format!("Data in buffer view {} ends after {} of {} elements", view, element, count))
+				};
+
+				values.push(match component_type {
+					BYTE if normalized => (bytes[0] as i8 as f64 / 127.0).max(-1.0),
+					BYTE => bytes[0] as i8 as f64,
+					UNSIGNED_BYTE if normalized => bytes[0] as f64 / 255.0,
+					UNSIGNED_BYTE => bytes[0] as f64,
+					SHORT if normalized => (LittleEndian::read_i16(bytes) as f64 / 32767.0).max(-1.0),
+					SHORT => LittleEndian::read_i16(bytes) as f64,
+					UNSIGNED_SHORT if normalized => LittleEndian::read_u16(bytes) as f64 / 65535.0,
+					UNSIGNED_SHORT => LittleEndian::read_u16(bytes) as f64,
+					UNSIGNED_INT => LittleEndian::read_u32(bytes) as f64,
+					_ => LittleEndian::read_f32(bytes) as f64
+				});
+			}
+		}
+
+		Ok(values)
+	}
+
+	fn attribute(&self, i: Option<usize>, kind: &str, count: usize) -> io::Result<Option<Vec<f32>>> {
+		let i = match i {
+			Some(i) => i,
+			None => return Ok(None)
+		};
+
+		let (values, components) = self.accessor(i, &[kind])?;
+
+		if values.len() / components != count {
+			return invalid(format!("Accessor {} has {} elements, but the positions have {}", i, values.len() / components, count));
+		}
+
+		Ok(Some(values.into_iter().map(|v| v as f32).collect()))
+	}
+
+	fn source(&self, key: SourceKey) -> io::Result<Source> {
+		let positions: Vec<f32> = self.accessor(key.position, &["VEC3"])?.0.into_iter().map(|v| v as f32).collect();
+		let count = positions.len() / 3;
+
+		let mut targets = Vec::with_capacity(key.targets.len());
+
+		for &(position, normal) in &key.targets {
+			targets.push((self.attribute(position, "VEC3", count)?, self.attribute(normal, "VEC3", count)?));
+		}
+
+		Ok(Source {
+			normals: self.attribute(key.normal, "VEC3", count)?,
+			texcoords: self.attribute(key.texcoord, "VEC2", count)?,
+			positions,
+			targets,
+			key
+		})
+	}
+
+	/// The transform of a node relative to its parent at a point in the animation.
+	fn local(&self, node: usize, time: f32) -> io::Result<Matrix4<f32>> {
+		let json = self.element("nodes", node)?;
+
+		if let Some(matrix) = floats(json, "matrix") {
+			if matrix.len() != 16 {
+				return invalid(format!("Node {} has a matrix with {} elements", node, matrix.len()));
+			}
+
+			let m = &matrix;
+
+			return Ok(Matrix4::new(
+				m[0], m[1], m[2], m[3],
+				m[4], m[5], m[6], m[7],
+				m[8], m[9], m[10], m[11],
+				m[12], m[13], m[14], m[15]
+			));
+		}
+
+		let mut translation = floats(json, "translation").unwrap_or_else(|| vec![0.0, 0.0, 0.0]);
+		let mut rotation = floats(json, "rotation").unwrap_or_else(|| vec![0.0, 0.0, 0.0, 1.0]);
+		let mut scale = floats(json, "scale").unwrap_or_else(|| vec![1.0, 1.0, 1.0]);
+
+		for channel in self.channels.iter().filter(|channel| channel.node == node) {
+			match channel.property {
+				Property::Translation => translation = channel.sample(time),
+				Property::Rotation => rotation = channel.sample(time),
+				Property::Scale => scale = channel.sample(time),
+				Property::Weights => ()
+			}
+		}
+
+		if translation.len() != 3 || rotation.len() != 4 || scale.len() != 3 {
+			return invalid(format!("Node {} has a malformed translation, rotation or scale", node));
+		}
+
+		Ok(Parts {
+			translation: Vector3::new(translation[0], translation[1], translation[2]),
+			rotation: Quaternion::new(rotation[3], rotation[0], rotation[1], rotation[2]).normalize(),
+			scale: Vector3::new(scale[0], scale[1], scale[2])
+		}.recompose())
+	}
+
+	fn world(&self, node: usize, time: f32) -> io::Result<Matrix4<f32>> {
+		let mut matrix = self.local(node, time)?;
+		let mut parent = self.parents[node];
+
+		while let Some(node) = parent {
+			matrix = self.local(node, time)? * matrix;
+			parent = self.parents[node];
+		}
+
+		Ok(matrix)
+	}
+
+	fn name(&self, node: usize) -> io::Result<&str> {
+		Ok(self.element("nodes", node)?.get("name").and_then(Value::as_str).unwrap_or(""))
+	}
+
+	fn scene(&self) -> io::Result<Scene<V2>> {
+		let nodes = list(&self.json, "nodes");
+		let scenes = list(&self.json, "scenes");
+
+		let roots: Vec<usize> = if scenes.is_empty() {
+			(0..nodes.len()).filter(|&node| self.parents[node].is_none()).collect()
+		} else {
+			let scene = self.element("scenes", index(&self.json, "scene").unwrap_or(0))?;
+
+			list(scene, "nodes").iter().filter_map(Value::as_usize).collect()
+		};
+
+		let mut lods = HashSet::new();
+
+		for node in nodes {
+			if let Some(lod) = node.get("extensions").and_then(|extensions| extensions.get("MSFT_lod")) {
+				lods.extend(list(lod, "ids").iter().filter_map(Value::as_usize));
+			}
+		}
+
+		let mut visited = vec![false; nodes.len()];
+		let mut parts = Vec::new();
+		let mut tags = Vec::new();
+
+		for root in roots {
+			self.walk(root, &lods, &mut visited, &mut parts, &mut tags)?;
+		}
+
+		if parts.is_empty() {
+			return invalid("The glTF scene contains no meshes".to_string());
+		}
+
+		let mut root = parts.remove(0);
+		root.children.extend(parts);
+		root.tags.extend(tags);
+
+		self.convert(&root)
+	}
+
+	fn walk(&self, node: usize, lods: &HashSet<usize>, visited: &mut [bool], parts: &mut Vec<Part>, tags: &mut Vec<usize>) -> io::Result<()> {
+		if lods.contains(&node) {
+			return Ok(());
+		}
+
+		match visited.get_mut(node) {
+			Some(&mut true) => return invalid(format!("Node {} appears more than once in the hierarchy", node)),
+			Some(seen) => *seen = true,
+			None => return invalid(format!("Reference to nodes {}, which does not exist", node))
+		}
+
+		let json = self.element("nodes", node)?;
+		let children: Vec<usize> = list(json, "children").iter().filter_map(Value::as_usize).collect();
+
+		if json.get("skin").is_some() {
+			return invalid(format!("Node {} is skinned, which cannot be represented; bake the skinning into morph targets or node animation", node));
+		}
+
+		if self.name(node)?.starts_with(TAG_POINT_PREFIX) {
+			tags.push(node);
+
+			for child in children {
+				self.walk(child, lods, visited, parts, tags)?;
+			}
+		} else if json.get("mesh").is_some() {
+			let mut part = Part { node, tags: Vec::new(), children: Vec::new() };
+
+			for child in children {
+				self.walk(child, lods, visited, &mut part.children, &mut part.tags)?;
+			}
+
+			parts.push(part);
+		} else {
+			for child in children {
+				self.walk(child, lods, visited, parts, tags)?;
+			}
+		}
+
+		Ok(())
+	}
+
+	fn convert(&self, part: &Part) -> io::Result<Scene<V2>> {
+		let name = match self.name(part.node)? {
+			"" => format!("node {}", part.node),
+			name => name.to_string()
+		};
+
+		let mut scene = Scene::single(name, self.model(part)?);
+
+		for child in &part.children {
+			scene.children.push(self.convert(child)?);
+		}
+
+		Ok(scene)
+	}
+
+	fn model(&self, part: &Part) -> io::Result<V2> {
+		let node = self.element("nodes", part.node)?;
+		let mut meshes = vec![index(node, "mesh").unwrap_or(usize::MAX)];
+
+		if let Some(lod) = node.get("extensions").and_then(|extensions| extensions.get("MSFT_lod")) {
+			for id in list(lod, "ids").iter().filter_map(Value::as_usize) {
+				match index(self.element("nodes", id)?, "mesh") {
+					Some(mesh) => meshes.push(mesh),
+					None => return invalid(format!("LOD node {} of node {} has no mesh", id, part.node))
+				}
+			}
+		}
+
+		let mut sources: Vec<Source> = Vec::new();
+		let mut groups: Vec<Group> = Vec::new();
+
+		for (lod, &mesh) in meshes.iter().enumerate() {
+			for (i, primitive) in list(self.element("meshes", mesh)?, "primitives").iter().enumerate() {
+				let mode = index(primitive, "mode").unwrap_or(TRIANGLES);
+
+				if mode != TRIANGLES {
+					return invalid(format!("Primitive {} of mesh {} uses mode {}, but only triangle lists can be represented", i, mesh, mode));
+				}
+
+				let attributes = primitive.get("attributes").unwrap_or(&Value::Null);
+
+				let key = SourceKey {
+					position: match index(attributes, "POSITION") {
+						Some(position) => position,
+						None => return invalid(format!("Primitive {} of mesh {} has no POSITION attribute", i, mesh))
+					},
+					normal: index(attributes, "NORMAL"),
+					texcoord: index(attributes, "TEXCOORD_0"),
+					targets: list(primitive, "targets").iter().map(|target| (index(target, "POSITION"), index(target, "NORMAL"))).collect()
+				};
+
+				let source = match sources.iter().position(|source| source.key == key) {
+					Some(source) => source,
+					None => {
+						sources.push(self.source(key)?);
+						sources.len() - 1
+					}
+				};
+
+				let count = sources[source].positions.len() / 3;
+
+				let indices: Vec<u32> = match index(primitive, "indices") {
+					Some(indices) => self.accessor(indices, &["SCALAR"])?.0.into_iter().map(|i| i as u32).collect(),
+					None => (0..count as u32).collect()
+				};
+
+				if let Some(&vertex) = indices.iter().find(|&&vertex| vertex as usize >= count) {
+					return invalid(format!("Primitive {} of mesh {} refers to vertex {}, but there are only {}", i, mesh, vertex, count));
+				}
+
+				let material = index(primitive, "material");
+
+				let group = match groups.iter().position(|group| group.material == material) {
+					Some(group) => group,
+					None => {
+						groups.push(Group {
+							material,
+							vertices: Vec::new(),
+							lookup: HashMap::new(),
+							triangles: vec![Vec::new(); meshes.len()]
+						});
+
+						groups.len() - 1
+					}
+				};
+
+				let group = &mut groups[group];
+
+				for triangle in indices.chunks_exact(3) {
+					let mut local = [0; 3];
+
+					for (corner, &vertex) in local.iter_mut().zip(triangle) {
+						let next = group.vertices.len() as u32;
+						*corner = *group.lookup.entry((source, vertex)).or_insert(next);
+
+						if *corner == next {
+							group.vertices.push((source, vertex));
+						}
+					}
+
+					group.triangles[lod].push((local[0], local[1], local[2]));
+				}
+			}
+		}
+
+		if groups.is_empty() {
+			return invalid(format!("The mesh of node {} has no primitives", part.node));
+		}
+
+		let targets = sources.iter().map(|source| source.targets.len()).max().unwrap_or(0);
+
+		if let Some(source) = sources.iter().find(|source| !source.targets.is_empty() && source.targets.len() != targets) {
+			return invalid(format!("The primitives of node {} have different numbers of morph targets ({} and {})", part.node, source.targets.len(), targets));
+		}
+
+		// Lay out the vertices and triangles of each material one after another.
+		let mut lod_levels = vec![Vec::new(); meshes.len()];
+		let mut materials = Vec::with_capacity(groups.len());
+		let mut vertex_offset = 0;
+
+		for group in &groups {
+			let mut selections = Vec::with_capacity(meshes.len());
+
+			for (triangles, level) in group.triangles.iter().zip(&mut lod_levels) {
+				selections.push(TriangleSelection { offset: level.len() as u32, len: triangles.len() as u32 });
+				level.extend(triangles.iter().map(|t| (t.0 + vertex_offset, t.1 + vertex_offset, t.2 + vertex_offset)));
+			}
+
+			let (name, texture_name) = self.material(group.material)?;
+
+			materials.push(Material {
+				name,
+				texture: 0,
+				triangles: selections,
+				vertex_offset,
+				vertex_count: group.vertices.len() as u32,
+				texture_name
+			});
+
+			vertex_offset += group.vertices.len() as u32;
+		}
+
+		let vertices: Vec<(usize, u32)> = groups.iter().flat_map(|group| group.vertices.iter().cloned()).collect();
+
+		let mut frames = Vec::new();
+
+		for (time, weights) in self.poses(part, targets)? {
+			let transform = self.world(part.node, time)?;
+
+			let inverse = match transform.invert() {
+				Some(inverse) => inverse,
+				None => return invalid(format!("The transform of node {} cannot be inverted", part.node))
+			};
+
+			let mut tag_points = Vec::with_capacity(part.tags.len());
+
+			for &tag in &part.tags {
+				tag_points.push(inverse.transform_point(Point3::from_vec(self.world(tag, time)?.w.truncate())));
+			}
+
+			frames.push((pose(&sources, &vertices, &lod_levels, &weights), tag_points, transform));
+		}
+
+		let mut center = CenterBuilder::begin();
+
+		for vertex in frames.iter().flat_map(|frame| frame.0.iter()) {
+			center.update(vertex.position);
+		}
+
+		let center = center.build();
+
+		let mut tag_points = Vec::with_capacity(part.tags.len());
+
+		for &tag in &part.tags {
+			tag_points.push(self.name(tag)?[TAG_POINT_PREFIX.len()..].to_string());
+		}
+
+		Ok(V2 {
+			center,
+			lod_levels,
+			materials,
+			tag_points,
+			frames: frames.into_iter().map(|(vertices, tag_points, transform)| {
+				let mut frame = Frame::from_vertices(vertices, tag_points, center);
+				frame.transform = transform;
+
+				frame
+			}).collect()
+		})
+	}
+
+	/// The time and morph target weights of each frame.
+	fn poses(&self, part: &Part, targets: usize) -> io::Result<Vec<(f32, Vec<f32>)>> {
+		let node = self.element("nodes", part.node)?;
+		let mesh = self.element("meshes", index(node, "mesh").unwrap_or(usize::MAX))?;
+
+		let mut rest = floats(node, "weights").or_else(|| floats(mesh, "weights")).unwrap_or_default();
+		rest.resize(targets, 0.0);
+
+		// Every node whose movement affects the transform of the model or its tag points.
+		let mut moving = HashSet::new();
+
+		for &start in Some(&part.node).into_iter().chain(&part.tags) {
+			let mut node = Some(start);
+
+			while let Some(current) = node {
+				moving.insert(current);
+				node = self.parents[current];
+			}
+		}
+
+		let weights = self.channels.iter().find(|channel| channel.node == part.node && channel.property == Property::Weights);
+
+		let mut times: Vec<f32> = self.channels.iter()
+			.filter(|channel| if channel.property == Property::Weights { channel.node == part.node } else { moving.contains(&channel.node) })
+			.flat_map(|channel| channel.times.iter().cloned())
+			.collect();
+
+		times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(::std::cmp::Ordering::Equal));
+		times.dedup();
+
+		if times.is_empty() {
+			let mut poses = vec![(0.0, rest)];
+
+			for target in 0..targets {
+				let mut weights = vec![0.0; targets];
+				weights[target] = 1.0;
+
+				poses.push((0.0, weights));
+			}
+
+			return Ok(poses);
+		}
+
+		Ok(times.into_iter().map(|time| {
+			let mut weights = weights.map(|channel| channel.sample(time)).unwrap_or_else(|| rest.clone());
+			weights.resize(targets, 0.0);
+
+			(time, weights)
+		}).collect())
+	}
+
+	/// The name and texture name of a material.
+	fn material(&self, material: Option<usize>) -> io::Result<(String, String)> {
+		let material = match material {
+			Some(material) => material,
+			None => return Ok(("default".to_string(), String::new()))
+		};
+
+		let json = self.element("materials", material)?;
+
+		let name = match json.get("name").and_then(Value::as_str) {
+			Some(name) => name.to_string(),
+			None => format!("material {}", material)
+		};
+
+		let texture = json.get("pbrMetallicRoughness")
+			.and_then(|pbr| pbr.get("baseColorTexture"))
+			.and_then(|texture| index(texture, "index"));
+
+		let texture_name = match texture {
+			Some(texture) => {
+				let image = self.element("images", index(self.element("textures", texture)?, "source").unwrap_or(usize::MAX))?;
+
+				match image.get("uri").and_then(Value::as_str) {
+					Some(uri) if !uri.starts_with("data:") => uri_decode(uri),
+					// Embedded images have no file name of their own.
+					_ => image.get("name").and_then(Value::as_str).unwrap_or("").to_string()
+				}
+			},
+			None => String::new()
+		};
+
+		Ok((name, texture_name))
+	}
+}
+
+/// Splits a GLB file into its JSON chunk and binary chunk.
+fn glb(data: &[u8]) -> io::Result<(&[u8], Option<Vec<u8>>)> {
+	let version = LittleEndian::read_u32(&data[4..]);
+
+	if version != 2 {
+		return invalid(format!("Unsupported GLB version {}", version));
+	}
+
+	let length = (LittleEndian::read_u32(&data[8..]) as usize).min(data.len());
+	let mut offset = 12;
+	let mut json = None;
+	let mut bin = None;
+
+	while offset + 8 <= length {
+		let chunk_length = LittleEndian::read_u32(&data[offset..]) as usize;
+		let kind = LittleEndian::read_u32(&data[offset + 4..]);
+		let start = offset + 8;
+
+		let chunk = match data[..length].get(start..start + chunk_length) {
+			Some(chunk) => chunk,
+			None => return invalid(format!("GLB chunk at offset {} is truncated", offset))
+		};
+
+		match kind {
+			GLB_JSON if json.is_none() => json = Some(chunk),
+			GLB_BIN if bin.is_none() => bin = Some(chunk.to_vec()),
+			_ => ()
+		}
+
+		offset = start + chunk_length;
+	}
+
+	match json {
+		Some(json) => Ok((json, bin)),
+		None => invalid("GLB file has no JSON chunk".to_string())
+	}
+}
+
+/// Builds the vertices of one frame, applying morph targets and filling in normals that the file left out.
+fn pose(sources: &[Source], vertices: &[(usize, u32)], lod_levels: &[Vec<Triangle>], weights: &[f32]) -> Vec<Vertex> {
+	let component = |data: &[f32], i: usize| Vector3::new(data[i * 3], data[i * 3 + 1], data[i * 3 + 2]);
+	let mut missing_normals = false;
+
+	let mut posed: Vec<Vertex> = vertices.iter().map(|&(source, i)| {
+		let source = &sources[source];
+		let i = i as usize;
+
+		let mut position = component(&source.positions, i);
+		let mut normal = source.normals.as_ref().map(|normals| component(normals, i)).unwrap_or_else(|| Vector3::new(0.0, 0.0, 0.0));
+
+		for ((positions, normals), &weight) in source.targets.iter().zip(weights) {
+			if weight == 0.0 {
+				continue;
+			}
+
+			if let Some(positions) = positions {
+				position += component(positions, i) * weight;
+			}
+
+			if let Some(normals) = normals {
+				normal += component(normals, i) * weight;
+			}
+		}
+
+		if source.normals.is_none() {
+			missing_normals = true;
+		} else if !source.targets.is_empty() && normal.magnitude2() > 0.0 {
+			normal = normal.normalize();
+		}
+
+		Vertex {
+			position: Point3::from_vec(position),
+			normal,
+			texture: source.texcoords.as_ref().map(|texcoords| Point2::new(texcoords[i * 2], texcoords[i * 2 + 1])).unwrap_or_else(|| Point2::new(0.0, 0.0))
+		}
+	}).collect();
+
+	if missing_normals {
+		let mut accumulated = vec![Vector3::new(0.0, 0.0, 0.0); posed.len()];
+
+		for triangle in lod_levels.iter().flat_map(|triangles| triangles.iter()) {
+			let (a, b, c) = (triangle.0 as usize, triangle.1 as usize, triangle.2 as usize);
+			let face = (posed[b].position - posed[a].position).cross(posed[c].position - posed[a].position);
+
+			accumulated[a] += face;
+			accumulated[b] += face;
+			accumulated[c] += face;
+		}
+
+		for ((vertex, normal), &(source, _)) in posed.iter_mut().zip(accumulated).zip(vertices) {
+			if sources[source].normals.is_none() && normal.magnitude2() > 0.0 {
+				vertex.normal = normal.normalize();
+			}
+		}
+	}
+
+	posed
+}
+
+#[cfg(test)]
+mod tests {
+	use gltf::base64_encode;
+	use super::Document;
+
+	/// A document with 64 bytes of zeros and one animation, whose input and output accessors have the given counts.
+	fn document(interpolation: &str, times: usize, output_type: &str, outputs: usize) -> String {
+		format!(r#"{{
+			"asset": {{"version": "2.0"}},
+			"buffers": [{{"byteLength": 64, "uri": "data:application/octet-stream;base64,{}"}}],
+			"bufferViews": [{{"buffer": 0, "byteLength": 64}}],
+			"accessors": [
+				{{"bufferView": 0, "componentType": 5126, "count": {}, "type": "SCALAR"}},
+				{{"bufferView": 0, "componentType": 5126, "count": {}, "type": "{}"}}
+			],
+			"animations": [{{
+				"samplers": [{{"input": 0, "output": 1, "interpolation": "{}"}}],
+				"channels": [{{"sampler": 0, "target": {{"node": 0, "path": "translation"}}}}]
+			}}]
+		}}"#, base64_encode(&[0; 64]), times, outputs, output_type, interpolation)
+	}
+
+	fn channels(json: &str) -> ::std::io::Result<usize> {
+		Document::parse(json.as_bytes(), |_| unreachable!())?.channels().map(|channels| channels.len())
+	}
+
+	fn hierarchy(nodes: &str) -> String {
+		format!(r#"{{"asset": {{"version": "2.0"}}, "scenes": [{{"nodes": [0]}}], "nodes": {}}}"#, nodes)
+	}
+
+	#[test]
+	fn cyclic_hierarchy_is_rejected() {
+		let error = Document::parse(hierarchy(r#"[{"children": [1]}, {"children": [0]}]"#).as_bytes(), |_| unreachable!()).err().unwrap();
+		assert!(error.to_string().contains("its own ancestor"), "{}", error);
+
+		assert!(Document::parse(hierarchy(r#"[{"children": [0]}]"#).as_bytes(), |_| unreachable!()).is_err());
+	}
+
+	#[test]
+	fn node_with_two_parents_is_rejected() {
+		let error = Document::parse(hierarchy(r#"[{"children": [2]}, {"children": [2]}, {}]"#).as_bytes(), |_| unreachable!()).err().unwrap();
+		assert!(error.to_string().contains("child of both"), "{}", error);
+	}
+
+	#[test]
+	fn tree_is_accepted() {
+		assert!(Document::parse(hierarchy(r#"[{"children": [1, 2]}, {"children": [3]}, {}, {}]"#).as_bytes(), |_| unreachable!()).is_ok());
+	}
+
+	#[test]
+	fn cubic_spline_keyframes_are_read() {
+		assert_eq!(channels(&document("CUBICSPLINE", 2, "SCALAR", 6)).unwrap(), 1);
+	}
+
+	#[test]
+	fn cubic_spline_without_room_for_tangents_is_rejected() {
+		assert!(channels(&document("CUBICSPLINE", 1, "SCALAR", 2)).is_err());
+	}
+
+	#[test]
+	fn cubic_spline_with_a_short_last_keyframe_is_rejected() {
+		assert!(channels(&document("CUBICSPLINE", 2, "SCALAR", 7)).is_err());
+	}
+
+	#[test]
+	fn huge_counts_are_rejected_without_allocating() {
+		assert!(channels(&document("LINEAR", usize::MAX / 2, "SCALAR", 1)).is_err());
+		assert!(channels(&document("LINEAR", 1, "VEC4", 1 << 60)).is_err());
+	}
+}
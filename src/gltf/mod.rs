@@ -0,0 +1,111 @@
+//! Conversion between scenes and glTF 2.0 documents.
+
+mod export;
+mod import;
+
+pub use self::export::{Export, ExportOptions};
+pub use self::import::{import, import_file};
+
+/// Tag points are represented by empty nodes whose names start with this prefix, followed by the tag point name.
+pub const TAG_POINT_PREFIX: &str = "tag:";
+
+const BYTE: u32 = 5120;
+const UNSIGNED_BYTE: u32 = 5121;
+const SHORT: u32 = 5122;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_JSON: u32 = 0x4E4F_534A;
+const GLB_BIN: u32 = 0x004E_4942;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+	let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+
+	for chunk in data.chunks(3) {
+		let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+		let group = ((bytes[0] as u32) << 16) | ((bytes[1] as u32) << 8) | bytes[2] as u32;
+
+		for i in 0..4 {
+			if i <= chunk.len() {
+				encoded.push(BASE64[(group >> (18 - i * 6)) as usize & 63] as char);
+			} else {
+				encoded.push('=');
+			}
+		}
+	}
+
+	encoded
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+	let mut decoded = Vec::with_capacity(text.len() / 4 * 3);
+	let mut group = 0u32;
+	let mut bits = 0;
+
+	for byte in text.bytes().filter(|&byte| byte != b'=' && !byte.is_ascii_whitespace()) {
+		let value = BASE64.iter().position(|&c| c == byte)? as u32;
+
+		group = (group << 6) | value;
+		bits += 6;
+
+		if bits >= 8 {
+			bits -= 8;
+			decoded.push((group >> bits) as u8);
+		}
+	}
+
+	Some(decoded)
+}
+
+/// Percent-encodes a file name for use as a relative URI.
+pub(crate) fn uri_encode(name: &str) -> String {
+	let mut encoded = String::with_capacity(name.len());
+
+	for c in name.chars() {
+		match c {
+			'A'..='Z' | 'a'..='z' | '0'..='9' | '-' | '_' | '.' | '~' | '/' => encoded.push(c),
+			'\\' => encoded.push('/'),
+			_ => {
+				let mut buffer = [0; 4];
+
+				for byte in c.encode_utf8(&mut buffer).bytes() {
+					encoded.push_str(&format!("%{:02X}", byte));
+				}
+			}
+		}
+	}
+
+	encoded
+}
+
+/// Decodes a relative URI back into a file name. Invalid escapes are kept as they are.
+fn uri_decode(uri: &str) -> String {
+	let bytes = uri.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+
+	while i < bytes.len() {
+		let escaped = if bytes[i] == b'%' && i + 2 < bytes.len() {
+			::std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())
+		} else {
+			None
+		};
+
+		match escaped {
+			Some(byte) => {
+				decoded.push(byte);
+				i += 3;
+			},
+			None => {
+				decoded.push(bytes[i]);
+				i += 1;
+			}
+		}
+	}
+
+	String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,317 @@
+//! Reads the largest mipmap level of DXT1, DXT3 and DXT5 compressed textures, and of uncompressed textures
+//! described by bit masks. Cube maps, volume textures and the DX10 header extension are not supported.
+
+use std::io::{self, Read, Cursor};
+use byteorder::{ReadBytesExt, LittleEndian};
+use super::Image;
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: u32 = 124;
+
+/// Pixel format flags.
+const ALPHA_PIXELS: u32 = 0x1;
+const FOUR_CC: u32 = 0x4;
+const RGB: u32 = 0x40;
+const LUMINANCE: u32 = 0x2_0000;
+
+/// Reads a DDS texture.
+pub fn read<R>(r: &mut R) -> io::Result<Image> where R: Read {
+	let mut data = Vec::new();
+	r.read_to_end(&mut data)?;
+
+	let mut c = Cursor::new(&data[..]);
+
+	let mut magic = [0; 4];
+	c.read_exact(&mut magic)?;
+
+	if &magic != MAGIC {
+		return invalid("Not a DDS file: missing the DDS signature".to_string());
+	}
+
+	let size = c.read_u32::<LittleEndian>()?;
+
+	if size != HEADER_SIZE {
+		return invalid(format!("Unexpected DDS header size {}, expected {}", size, HEADER_SIZE));
+	}
+
+	let _flags = c.read_u32::<LittleEndian>()?;
+	let height = c.read_u32::<LittleEndian>()?;
+	let width = c.read_u32::<LittleEndian>()?;
+
+	// Skip the pitch, depth, mipmap count and reserved space, up to the pixel format.
+	c.set_position(4 + 72);
+
+	let _format_size = c.read_u32::<LittleEndian>()?;
+	let format_flags = c.read_u32::<LittleEndian>()?;
+
+	let mut four_cc = [0; 4];
+	c.read_exact(&mut four_cc)?;
+
+	let bit_count = c.read_u32::<LittleEndian>()?;
+	let masks = [c.read_u32::<LittleEndian>()?, c.read_u32::<LittleEndian>()?, c.read_u32::<LittleEndian>()?, c.read_u32::<LittleEndian>()?];
+
+	let pixels = &data[(4 + HEADER_SIZE as usize).min(data.len())..];
+
+	if format_flags & FOUR_CC != 0 {
+		match &four_cc {
+			b"DXT1" => blocks(pixels, width, height, 8, |block, colors| bc1(block, colors, true)),
+			b"DXT3" => blocks(pixels, width, height, 16, bc2),
+			b"DXT5" => blocks(pixels, width, height, 16, bc3),
+			_ => invalid(format!("Unsupported DDS compression '{}'", String::from_utf8_lossy(&four_cc)))
+		}
+	} else if format_flags & (RGB | LUMINANCE) != 0 {
+		let alpha_mask = if format_flags & ALPHA_PIXELS != 0 { masks[3] } else { 0 };
+		let masks = if format_flags & LUMINANCE != 0 { [masks[0], masks[0], masks[0], alpha_mask] } else { [masks[0], masks[1], masks[2], alpha_mask] };
+
+		uncompressed(pixels, width, height, bit_count, masks)
+	} else {
+		invalid(format!("Unsupported DDS pixel format with flags {:#x}", format_flags))
+	}
+}
+
+/// Decodes 4x4 blocks of `block_size` bytes, each into 16 colors from left to right and top to bottom.
+fn blocks<F>(data: &[u8], width: u32, height: u32, block_size: usize, decode: F) -> io::Result<Image> where F: Fn(&[u8], &mut [[u8; 4]; 16]) {
+	let (columns, rows) = (width.div_ceil(4), height.div_ceil(4));
+	let needed = match (columns as usize).checked_mul(rows as usize).and_then(|count| count.checked_mul(block_size)) {
+		Some(needed) => needed,
+		None => return invalid(format!("A {}x{} DDS texture is too large", width, height))
+	};
+
+	if data.len() < needed {
+		return invalid(format!("A {}x{} DDS texture needs {} bytes of blocks, but only {} are present", width, height, needed, data.len()));
+	}
+
+	let mut image = Image::new(width, height, [0, 0, 0, 0])?;
+	let mut colors = [[0; 4]; 16];
+
+	for (i, block) in data[..needed].chunks(block_size).enumerate() {
+		decode(block, &mut colors);
+
+		let (left, top) = (i as u32 % columns * 4, i as u32 / columns * 4);
+
+		for (j, &color) in colors.iter().enumerate() {
+			let (x, y) = (left + j as u32 % 4, top + j as u32 / 4);
+
+			// Blocks on the right and bottom edges may hang over the edge of the image.
+			if x < width && y < height {
+				image.set_pixel(x, y, color);
+			}
+		}
+	}
+
+	Ok(image)
+}
+
+/// Decodes the color part of a block. DXT1 blocks whose first color is not greater than the second use
+/// the last index for transparent black, while DXT3 and DXT5 always use four colors.
+fn bc1(block: &[u8], colors: &mut [[u8; 4]; 16], punch_through: bool) {
+	let (a, b) = (block[0] as u16 | (block[1] as u16) << 8, block[2] as u16 | (block[3] as u16) << 8);
+	let (c0, c1) = (rgb565(a), rgb565(b));
+
+	let mix = |weight0: u16, weight1: u16| {
+		let total = weight0 + weight1;
+		let channel = |i: usize| ((c0[i] as u16 * weight0 + c1[i] as u16 * weight1) / total) as u8;
+
+		[channel(0), channel(1), channel(2), 255]
+	};
+
+	let palette = if a > b || !punch_through {
+		[c0, c1, mix(2, 1), mix(1, 2)]
+	} else {
+		[c0, c1, mix(1, 1), [0, 0, 0, 0]]
+	};
+
+	let indices = block[4] as u32 | (block[5] as u32) << 8 | (block[6] as u32) << 16 | (block[7] as u32) << 24;
+
+	for (i, color) in colors.iter_mut().enumerate() {
+		*color = palette[(indices >> (2 * i)) as usize & 3];
+	}
+}
+
+/// DXT3: explicit 4-bit alpha followed by a color block.
+fn bc2(block: &[u8], colors: &mut [[u8; 4]; 16]) {
+	bc1(&block[8..], colors, false);
+
+	for (i, color) in colors.iter_mut().enumerate() {
+		let alpha = (block[i / 2] >> (4 * (i % 2))) & 0xF;
+		color[3] = alpha * 17;
+	}
+}
+
+/// DXT5: two alpha endpoints with 3-bit interpolation indices, followed by a color block.
+fn bc3(block: &[u8], colors: &mut [[u8; 4]; 16]) {
+	bc1(&block[8..], colors, false);
+
+	let (a0, a1) = (block[0] as u16, block[1] as u16);
+	let mut alphas = [0u8; 8];
+
+	alphas[0] = a0 as u8;
+	alphas[1] = a1 as u8;
+
+	if a0 > a1 {
+		for i in 1..7 {
+			alphas[i + 1] = (((7 - i as u16) * a0 + i as u16 * a1) / 7) as u8;
+		}
+	} else {
+		for i in 1..5 {
+			alphas[i + 1] = (((5 - i as u16) * a0 + i as u16 * a1) / 5) as u8;
+		}
+
+		alphas[6] = 0;
+		alphas[7] = 255;
+	}
+
+	let indices = block[2..8].iter().rev().fold(0u64, |indices, &byte| indices << 8 | byte as u64);
+
+	for (i, color) in colors.iter_mut().enumerate() {
+		color[3] = alphas[(indices >> (3 * i)) as usize & 7];
+	}
+}
+
+fn rgb565(value: u16) -> [u8; 4] {
+	let (r, g, b) = ((value >> 11) & 0x1F, (value >> 5) & 0x3F, value & 0x1F);
+
+	[((r << 3) | (r >> 2)) as u8, ((g << 2) | (g >> 4)) as u8, ((b << 3) | (b >> 2)) as u8, 255]
+}
+
+/// Decodes pixels of 8 to 32 bits, taking each channel from the bits selected by its mask.
+fn uncompressed(data: &[u8], width: u32, height: u32, bit_count: u32, masks: [u32; 4]) -> io::Result<Image> {
+	if bit_count == 0 || bit_count > 32 || !bit_count.is_multiple_of(8) {
+		return invalid(format!("Unsupported DDS pixel depth of {} bits", bit_count));
+	}
+
+	let pixel_size = bit_count as usize / 8;
+	let needed = match (width as usize).checked_mul(height as usize).and_then(|count| count.checked_mul(pixel_size)) {
+		Some(needed) => needed,
+		None => return invalid(format!("A {}x{} DDS texture is too large", width, height))
+	};
+
+	if data.len() < needed {
+		return invalid(format!("A {}x{} DDS texture needs {} bytes of pixels, but only {} are present", width, height, needed, data.len()));
+	}
+
+	let channel = |value: u32, mask: u32| -> Option<u8> {
+		if mask == 0 {
+			return None;
+		}
+
+		let max = mask >> mask.trailing_zeros();
+		Some((((value & mask) >> mask.trailing_zeros()) as u64 * 255 / max as u64) as u8)
+	};
+
+	let mut image = Image::new(width, height, [0, 0, 0, 0])?;
+
+	for (i, pixel) in data[..needed].chunks(pixel_size).enumerate() {
+		let value = pixel.iter().rev().fold(0u32, |value, &byte| value << 8 | byte as u32);
+
+		image.set_pixel(i as u32 % width.max(1), i as u32 / width.max(1), [
+			channel(value, masks[0]).unwrap_or(0),
+			channel(value, masks[1]).unwrap_or(0),
+			channel(value, masks[2]).unwrap_or(0),
+			channel(value, masks[3]).unwrap_or(255)
+		]);
+	}
+
+	Ok(image)
+}
+
+fn invalid<T>(message: String) -> io::Result<T> {
+	Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use image::Image;
+	use super::read;
+
+	fn header(size: u32, format_flags: u32, four_cc: &[u8; 4], bit_count: u32) -> Vec<u8> {
+		let mut data = b"DDS ".to_vec();
+
+		for &value in &[124, 0, size, size] {
+			data.extend_from_slice(&value.to_le_bytes());
+		}
+
+		data.resize(76, 0);
+
+		for &value in &[32, format_flags, u32::from_le_bytes(*four_cc), bit_count, 0xff, 0xff00, 0xff_0000, 0] {
+			data.extend_from_slice(&value.to_le_bytes());
+		}
+
+		data.resize(128, 0);
+		data
+	}
+
+	/// A 4x4 texture made of a single compressed block.
+	fn compressed(four_cc: &[u8; 4], block: &[u8]) -> Image {
+		let mut data = header(4, 0x4, four_cc, 0);
+		data.extend_from_slice(block);
+
+		read(&mut &data[..]).unwrap()
+	}
+
+	fn first_row(image: &Image) -> [[u8; 4]; 4] {
+		[image.pixel(0, 0), image.pixel(1, 0), image.pixel(2, 0), image.pixel(3, 0)]
+	}
+
+	/// Colors white and black, with the first row using indices 0 to 3 and the rest index 0.
+	const WHITE_BLACK: [u8; 8] = [0xff, 0xff, 0, 0, 0xE4, 0, 0, 0];
+	const BLACK_WHITE: [u8; 8] = [0, 0, 0xff, 0xff, 0xE4, 0, 0, 0];
+
+	#[test]
+	fn dxt1_interpolates_four_colors() {
+		let image = compressed(b"DXT1", &WHITE_BLACK);
+
+		assert_eq!(first_row(&image), [[255, 255, 255, 255], [0, 0, 0, 255], [170, 170, 170, 255], [85, 85, 85, 255]]);
+	}
+
+	#[test]
+	fn dxt1_punch_through_makes_the_last_color_transparent() {
+		let image = compressed(b"DXT1", &BLACK_WHITE);
+
+		assert_eq!(first_row(&image), [[0, 0, 0, 255], [255, 255, 255, 255], [127, 127, 127, 255], [0, 0, 0, 0]]);
+	}
+
+	#[test]
+	fn dxt3_alpha_is_explicit() {
+		let mut block = vec![0xF0, 0x08, 0, 0, 0, 0, 0, 0xA0];
+		// Without punch through, even a first color below the second gives four opaque colors.
+		block.extend_from_slice(&BLACK_WHITE);
+
+		let image = compressed(b"DXT3", &block);
+		let alphas: Vec<u8> = first_row(&image).iter().map(|color| color[3]).collect();
+
+		assert_eq!(alphas, vec![0, 255, 136, 0]);
+		assert_eq!(image.pixel(3, 0), [170, 170, 170, 0]);
+		assert_eq!(image.pixel(3, 3), [0, 0, 0, 170]);
+	}
+
+	#[test]
+	fn dxt5_interpolates_eight_alphas() {
+		// Indices 0, 1, 2 and 7 for the first row.
+		let mut block = vec![255, 0, 0x88, 0x0E, 0, 0, 0, 0];
+		block.extend_from_slice(&WHITE_BLACK);
+
+		let alphas: Vec<u8> = first_row(&compressed(b"DXT5", &block)).iter().map(|color| color[3]).collect();
+		assert_eq!(alphas, vec![255, 0, 218, 36]);
+	}
+
+	#[test]
+	fn dxt5_interpolates_six_alphas_with_zero_and_full() {
+		// Indices 6, 7, 2 and 5 for the first row.
+		let mut block = vec![0, 255, 0xBE, 0x0A, 0, 0, 0, 0];
+		block.extend_from_slice(&WHITE_BLACK);
+
+		let alphas: Vec<u8> = first_row(&compressed(b"DXT5", &block)).iter().map(|color| color[3]).collect();
+		assert_eq!(alphas, vec![0, 255, 51, 204]);
+	}
+
+	#[test]
+	fn sizes_that_overflow_are_rejected() {
+		for data in &[header(u32::MAX, 0x4, b"DXT5", 0), header(u32::MAX, 0x40, b"\0\0\0\0", 32)] {
+			let error = read(&mut &data[..]).unwrap_err();
+			assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+			assert!(error.to_string().contains("too large"), "{}", error);
+		}
+	}
+}
@@ -0,0 +1,293 @@
+//! A small zlib compressor for PNG data, using LZ77 matching and the fixed Huffman codes of deflate.
+//! It compresses less than a full implementation with dynamic codes, but needs no tables in the output.
+
+/// Matches can refer back at most this far.
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// How many earlier positions with the same hash are tried before settling for the best match so far.
+const MAX_CHAIN: usize = 64;
+
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+/// Compresses data into a zlib stream.
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+	// Deflate with a 32 KiB window and no preset dictionary, at the fastest level.
+	let mut bits = Bits { out: vec![0x78, 0x01], buffer: 0, count: 0 };
+
+	// A single final block with fixed codes.
+	bits.write(1, 1);
+	bits.write(1, 2);
+
+	let mut head = vec![usize::MAX; 1 << HASH_BITS];
+	// Earlier positions with the same hash, kept only for the window, indexed by position modulo its size.
+	let mut previous = vec![usize::MAX; WINDOW];
+	let mut i = 0;
+
+	while i < data.len() {
+		let (length, distance) = longest_match(data, i, &head, &previous);
+
+		if length >= MIN_MATCH {
+			bits.length(length);
+			bits.distance(distance);
+		} else {
+			bits.symbol(data[i] as u16);
+		}
+
+		for position in i..i + length.max(1) {
+			if position + MIN_MATCH <= data.len() {
+				let hash = hash(&data[position..]);
+
+				previous[position & (WINDOW - 1)] = head[hash];
+				head[hash] = position;
+			}
+		}
+
+		i += length.max(1);
+	}
+
+	bits.symbol(256);
+	bits.flush();
+
+	let mut out = bits.out;
+	out.extend_from_slice(&adler32(data).to_be_bytes());
+	out
+}
+
+fn hash(data: &[u8]) -> usize {
+	let value = (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32;
+
+	(value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+fn longest_match(data: &[u8], i: usize, head: &[usize], previous: &[usize]) -> (usize, usize) {
+	if i + MIN_MATCH > data.len() {
+		return (0, 0);
+	}
+
+	let limit = (data.len() - i).min(MAX_MATCH);
+	let mut best = (0, 0);
+	let mut candidate = head[hash(&data[i..])];
+	let mut chain = 0;
+
+	while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+		let length = data[candidate..].iter().zip(&data[i..i + limit]).take_while(|&(a, b)| a == b).count();
+
+		if length > best.0 {
+			best = (length, i - candidate);
+
+			if length == limit {
+				break;
+			}
+		}
+
+		candidate = previous[candidate & (WINDOW - 1)];
+		chain += 1;
+	}
+
+	best
+}
+
+fn adler32(data: &[u8]) -> u32 {
+	let (mut a, mut b) = (1u32, 0u32);
+
+	for chunk in data.chunks(5552) {
+		for &byte in chunk {
+			a += byte as u32;
+			b += a;
+		}
+
+		a %= 65521;
+		b %= 65521;
+	}
+
+	(b << 16) | a
+}
+
+/// Writes bits starting from the least significant bit of each byte, as deflate requires.
+struct Bits {
+	out: Vec<u8>,
+	buffer: u32,
+	count: u32
+}
+
+impl Bits {
+	fn write(&mut self, value: u32, bits: u32) {
+		self.buffer |= value << self.count;
+		self.count += bits;
+
+		while self.count >= 8 {
+			self.out.push(self.buffer as u8);
+			self.buffer >>= 8;
+			self.count -= 8;
+		}
+	}
+
+	/// Huffman codes are stored starting from their most significant bit.
+	fn code(&mut self, code: u32, bits: u32) {
+		self.write(code.reverse_bits() >> (32 - bits), bits);
+	}
+
+	/// Writes a literal byte, the end of block marker, or a length code, using the fixed literal/length code.
+	fn symbol(&mut self, symbol: u16) {
+		let symbol = symbol as u32;
+
+		match symbol {
+			0..=143 => self.code(0x30 + symbol, 8),
+			144..=255 => self.code(0x190 + symbol - 144, 9),
+			256..=279 => self.code(symbol - 256, 7),
+			_ => self.code(0xC0 + symbol - 280, 8)
+		}
+	}
+
+	fn length(&mut self, length: usize) {
+		let index = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap_or(0);
+
+		self.symbol(257 + index as u16);
+		self.write((length - LENGTH_BASE[index] as usize) as u32, LENGTH_EXTRA[index] as u32);
+	}
+
+	fn distance(&mut self, distance: usize) {
+		let index = DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap_or(0);
+
+		// Distance codes are all 5 bits long.
+		self.code(index as u32, 5);
+		self.write((distance - DISTANCE_BASE[index] as usize) as u32, DISTANCE_EXTRA[index] as u32);
+	}
+
+	fn flush(&mut self) {
+		if self.count > 0 {
+			self.out.push(self.buffer as u8);
+		}
+
+		self.buffer = 0;
+		self.count = 0;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{zlib, adler32, LENGTH_BASE, LENGTH_EXTRA, DISTANCE_BASE, DISTANCE_EXTRA};
+
+	/// Reads bits in the order `Bits` writes them.
+	struct Reader<'d> {
+		data: &'d [u8],
+		position: usize
+	}
+
+	impl<'d> Reader<'d> {
+		fn bits(&mut self, count: u32) -> u32 {
+			let mut value = 0;
+
+			for i in 0..count {
+				let bit = (self.data[self.position / 8] >> (self.position % 8)) & 1;
+				value |= (bit as u32) << i;
+				self.position += 1;
+			}
+
+			value
+		}
+
+		fn code_bit(&mut self, code: u32) -> u32 {
+			(code << 1) | self.bits(1)
+		}
+
+		/// Decodes a symbol of the fixed literal/length code.
+		fn symbol(&mut self) -> u32 {
+			let mut code = 0;
+
+			for _ in 0..7 {
+				code = self.code_bit(code);
+			}
+
+			if code <= 0x17 {
+				return 256 + code;
+			}
+
+			code = self.code_bit(code);
+
+			match code {
+				0x30..=0xBF => code - 0x30,
+				0xC0..=0xC7 => 280 + code - 0xC0,
+				_ => 144 + self.code_bit(code) - 0x190
+			}
+		}
+	}
+
+	/// Inflates a zlib stream made of a single block with fixed codes, checking the header and checksum.
+	fn inflate(stream: &[u8]) -> Vec<u8> {
+		assert_eq!(((stream[0] as u16) << 8 | stream[1] as u16) % 31, 0);
+		assert_eq!(stream[0] & 0x0f, 8);
+
+		let mut reader = Reader { data: &stream[2..stream.len() - 4], position: 0 };
+		assert_eq!(reader.bits(1), 1);
+		assert_eq!(reader.bits(2), 1);
+
+		let mut out: Vec<u8> = Vec::new();
+
+		loop {
+			match reader.symbol() {
+				literal @ 0..=255 => out.push(literal as u8),
+				256 => break,
+				symbol => {
+					let index = (symbol - 257) as usize;
+					let length = LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32) as usize;
+
+					let mut code = 0;
+					for _ in 0..5 {
+						code = reader.code_bit(code);
+					}
+
+					let distance = DISTANCE_BASE[code as usize] as usize + reader.bits(DISTANCE_EXTRA[code as usize] as u32) as usize;
+					assert!(distance <= out.len() && distance <= 32768);
+
+					for _ in 0..length {
+						let byte = out[out.len() - distance];
+						out.push(byte);
+					}
+				}
+			}
+		}
+
+		assert_eq!(&stream[stream.len() - 4..], &adler32(&out).to_be_bytes());
+		out
+	}
+
+	#[test]
+	fn adler32_matches_known_values() {
+		assert_eq!(adler32(b""), 1);
+		assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+		assert_eq!(adler32(&[0xff; 100_000]), 0x149A_302C);
+	}
+
+	#[test]
+	fn compressed_data_inflates_to_the_original() {
+		// Noise repeated from almost a window back, with the second copy past the end of the first window.
+		let mut state = 1u32;
+		let noise: Vec<u8> = (0..30_000).map(|_| {
+			state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+			(state >> 16) as u8
+		}).collect();
+
+		let inputs: Vec<Vec<u8>> = vec![
+			Vec::new(),
+			b"a".to_vec(),
+			b"abcabcabcabcabcabcabcabcabcabc hello hello hello".to_vec(),
+			vec![0; 1000],
+			(0..=255).cycle().take(70_000).collect(),
+			[&noise[..], &noise[..]].concat()
+		];
+
+		for input in &inputs {
+			let compressed = zlib(input);
+			assert_eq!(&inflate(&compressed), input, "{} bytes", input.len());
+		}
+
+		assert!(zlib(&inputs[5]).len() < 35_000);
+	}
+}
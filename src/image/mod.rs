@@ -0,0 +1,77 @@
+//! Images are 8-bit RGBA, stored row by row from the top left, which is also where Empire Earth puts the
+//! origin of texture coordinates.
+
+use std::io;
+
+/// PNG encoding.
+pub mod png;
+
+/// TGA decoding, the texture format of Empire Earth.
+pub mod tga;
+
+/// DDS decoding, the texture format of Empires: Dawn of the Modern World.
+pub mod dds;
+
+mod deflate;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Image {
+	pub width: u32,
+	pub height: u32,
+	/// Four bytes per pixel, row by row.
+	pub pixels: Vec<u8>
+}
+
+impl Image {
+	/// Creates an image filled with one color. Fails with `InvalidData` if the pixels would not fit in memory.
+	pub fn new(width: u32, height: u32, color: [u8; 4]) -> io::Result<Self> {
+		let count = (width as usize).checked_mul(height as usize).filter(|count| count.checked_mul(4).is_some())
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("A {}x{} image is too large", width, height)))?;
+
+		let mut pixels = Vec::with_capacity(count * 4);
+
+		for _ in 0..count {
+			pixels.extend_from_slice(&color);
+		}
+
+		Ok(Image {
+			width,
+			height,
+			pixels
+		})
+	}
+
+	fn offset(&self, x: u32, y: u32) -> usize {
+		(y as usize * self.width as usize + x as usize) * 4
+	}
+
+	/// Panics if the pixel is outside of the image.
+	pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+		assert!(x < self.width && y < self.height, "Pixel ({}, {}) is outside of a {}x{} image", x, y, self.width, self.height);
+
+		let offset = self.offset(x, y);
+		[self.pixels[offset], self.pixels[offset + 1], self.pixels[offset + 2], self.pixels[offset + 3]]
+	}
+
+	/// Panics if the pixel is outside of the image.
+	pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+		assert!(x < self.width && y < self.height, "Pixel ({}, {}) is outside of a {}x{} image", x, y, self.width, self.height);
+
+		let offset = self.offset(x, y);
+		self.pixels[offset..offset + 4].copy_from_slice(&color);
+	}
+
+	/// Samples the pixel nearest to texture coordinates, repeating the image outside of the range 0 to 1.
+	pub fn sample(&self, u: f32, v: f32) -> [u8; 4] {
+		if self.width == 0 || self.height == 0 {
+			return [0, 0, 0, 0];
+		}
+
+		let wrap = |c: f32, size: u32| {
+			let c = (c - c.floor()) * size as f32;
+			(c as u32).min(size - 1)
+		};
+
+		self.pixel(wrap(u, self.width), wrap(v, self.height))
+	}
+}
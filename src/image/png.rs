@@ -0,0 +1,221 @@
+//! Images are written as 8-bit RGBA without interlacing. Each row uses whichever filter makes it smallest
+//! by the usual heuristic of the least sum of absolute differences.
+//!
+//! Animations are written as APNG, which shows the first frame in viewers that do not support animation.
+//! Every frame replaces the whole image.
+
+use std::io::{self, Write};
+use super::Image;
+use super::deflate::zlib;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// Writes an image as a PNG file.
+pub fn write<W>(w: &mut W, image: &Image) -> io::Result<()> where W: Write {
+	check(image)?;
+
+	w.write_all(&SIGNATURE)?;
+	chunk(w, b"IHDR", &header(image))?;
+	chunk(w, b"IDAT", &zlib(&filter(image)))?;
+	chunk(w, b"IEND", &[])
+}
+
+/// Writes images of the same size as an APNG animation that loops forever, showing `frame_rate` images per second.
+pub fn write_animated<W>(w: &mut W, images: &[Image], frame_rate: f32) -> io::Result<()> where W: Write {
+	let first = match images.first() {
+		Some(image) => image,
+		None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "An animation needs at least one image"))
+	};
+
+	for image in images {
+		check(image)?;
+
+		if image.width != first.width || image.height != first.height {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Every image of an animation must be {}x{}, got {}x{}", first.width, first.height, image.width, image.height)));
+		}
+	}
+
+	if frame_rate.is_nan() || frame_rate <= 0.0 {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid frame rate {}", frame_rate)));
+	}
+
+	// The delay is stored as a fraction of seconds, here in milliseconds.
+	let delay = (1000.0 / frame_rate).round().clamp(1.0, u16::MAX as f32) as u16;
+
+	w.write_all(&SIGNATURE)?;
+	chunk(w, b"IHDR", &header(first))?;
+
+	// Number of frames, and 0 to loop forever.
+	let mut control = Vec::with_capacity(8);
+	control.extend_from_slice(&(images.len() as u32).to_be_bytes());
+	control.extend_from_slice(&0u32.to_be_bytes());
+	chunk(w, b"acTL", &control)?;
+
+	// Frame controls and frame data share one sequence of numbers.
+	let mut sequence = 0u32;
+
+	for (i, image) in images.iter().enumerate() {
+		let mut control = Vec::with_capacity(26);
+		control.extend_from_slice(&sequence.to_be_bytes());
+		control.extend_from_slice(&image.width.to_be_bytes());
+		control.extend_from_slice(&image.height.to_be_bytes());
+		control.extend_from_slice(&[0; 8]);
+		control.extend_from_slice(&delay.to_be_bytes());
+		control.extend_from_slice(&1000u16.to_be_bytes());
+		// Leave the frame in place when done, and replace the pixels below it instead of blending.
+		control.extend_from_slice(&[0, 0]);
+
+		chunk(w, b"fcTL", &control)?;
+		sequence += 1;
+
+		let data = zlib(&filter(image));
+
+		if i == 0 {
+			chunk(w, b"IDAT", &data)?;
+		} else {
+			let mut frame = Vec::with_capacity(4 + data.len());
+			frame.extend_from_slice(&sequence.to_be_bytes());
+			frame.extend_from_slice(&data);
+
+			chunk(w, b"fdAT", &frame)?;
+			sequence += 1;
+		}
+	}
+
+	chunk(w, b"IEND", &[])
+}
+
+fn check(image: &Image) -> io::Result<()> {
+	if image.width == 0 || image.height == 0 || image.width > i32::MAX as u32 || image.height > i32::MAX as u32 {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("PNG cannot hold a {}x{} image", image.width, image.height)));
+	}
+
+	if image.pixels.len() != image.width as usize * image.height as usize * 4 {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("A {}x{} image needs {} bytes of pixels, but has {}", image.width, image.height, image.width as usize * image.height as usize * 4, image.pixels.len())));
+	}
+
+	Ok(())
+}
+
+fn header(image: &Image) -> Vec<u8> {
+	let mut header = Vec::with_capacity(13);
+
+	header.extend_from_slice(&image.width.to_be_bytes());
+	header.extend_from_slice(&image.height.to_be_bytes());
+	// Bit depth 8, color type 6 (RGBA), deflate compression, adaptive filtering, no interlacing.
+	header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+	header
+}
+
+/// Filters every row, prefixing it with the filter type.
+fn filter(image: &Image) -> Vec<u8> {
+	let stride = image.width as usize * 4;
+	let mut filtered = Vec::with_capacity((stride + 1) * image.height as usize);
+	let zero = vec![0; stride];
+	let mut candidates: Vec<Vec<u8>> = (0..5).map(|_| Vec::with_capacity(stride)).collect();
+
+	for (y, row) in image.pixels.chunks(stride).enumerate() {
+		let above = if y == 0 { &zero[..] } else { &image.pixels[(y - 1) * stride..y * stride] };
+
+		for (kind, candidate) in candidates.iter_mut().enumerate() {
+			candidate.clear();
+
+			for x in 0..stride {
+				let left = if x >= 4 { row[x - 4] } else { 0 };
+				let upper_left = if x >= 4 { above[x - 4] } else { 0 };
+
+				let prediction = match kind {
+					0 => 0,
+					1 => left,
+					2 => above[x],
+					3 => ((left as u16 + above[x] as u16) / 2) as u8,
+					_ => paeth(left, above[x], upper_left)
+				};
+
+				candidate.push(row[x].wrapping_sub(prediction));
+			}
+		}
+
+		let cost = |candidate: &Vec<u8>| candidate.iter().map(|&byte| (byte as i8).unsigned_abs() as u32).sum::<u32>();
+		let best = (0..5).min_by_key(|&kind| cost(&candidates[kind])).unwrap_or(0);
+
+		filtered.push(best as u8);
+		filtered.extend_from_slice(&candidates[best]);
+	}
+
+	filtered
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+	let p = a as i16 + b as i16 - c as i16;
+	let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+
+	if pa <= pb && pa <= pc {
+		a
+	} else if pb <= pc {
+		b
+	} else {
+		c
+	}
+}
+
+fn chunk<W>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> where W: Write {
+	w.write_all(&(data.len() as u32).to_be_bytes())?;
+	w.write_all(kind)?;
+	w.write_all(data)?;
+
+	let crc = crc32(crc32(!0, kind), data);
+	w.write_all(&(!crc).to_be_bytes())
+}
+
+/// Continues a CRC-32 over more data. Start with all bits set, and invert the result.
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+	for &byte in data {
+		crc ^= byte as u32;
+
+		for _ in 0..8 {
+			crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+		}
+	}
+
+	crc
+}
+
+#[cfg(test)]
+mod tests {
+	use image::Image;
+	use super::{write, crc32, SIGNATURE};
+
+	#[test]
+	fn crc32_matches_known_values() {
+		assert_eq!(!crc32(!0, b""), 0);
+		assert_eq!(!crc32(!0, b"123456789"), 0xCBF4_3926);
+		assert_eq!(!crc32(crc32(!0, b"1234"), b"56789"), 0xCBF4_3926);
+	}
+
+	#[test]
+	fn chunks_carry_their_checksums() {
+		let mut png = Vec::new();
+		write(&mut png, &Image::new(3, 2, [255, 0, 0, 255]).unwrap()).unwrap();
+
+		assert_eq!(&png[..8], &SIGNATURE);
+		// Every PNG ends with the same empty IEND chunk.
+		assert_eq!(&png[png.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+
+		let mut rest = &png[8..];
+		let mut kinds = Vec::new();
+
+		while !rest.is_empty() {
+			let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+			let (kind, data) = (&rest[4..8], &rest[8..8 + len]);
+			let crc = u32::from_be_bytes([rest[8 + len], rest[9 + len], rest[10 + len], rest[11 + len]]);
+
+			assert_eq!(!crc32(crc32(!0, kind), data), crc);
+			kinds.push(String::from_utf8(kind.to_vec()).unwrap());
+			rest = &rest[12 + len..];
+		}
+
+		assert_eq!(kinds, vec!["IHDR", "IDAT", "IEND"]);
+	}
+}
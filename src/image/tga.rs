@@ -0,0 +1,235 @@
+//! Reads true color, grayscale and color mapped images, with or without run length encoding, at any of the
+//! bit depths TGA allows. 16-bit pixels only use their attribute bit as alpha when the header says the image
+//! has an alpha channel, since many tools leave that bit clear on opaque images.
+
+use std::io::{self, Read};
+use super::Image;
+
+const HEADER_SIZE: usize = 18;
+
+/// Reads a TGA image.
+pub fn read<R>(r: &mut R) -> io::Result<Image> where R: Read {
+	let mut data = Vec::new();
+	r.read_to_end(&mut data)?;
+
+	if data.len() < HEADER_SIZE {
+		return invalid("The TGA file is too short to hold a header".to_string());
+	}
+
+	let u16_at = |offset: usize| data[offset] as u16 | (data[offset + 1] as u16) << 8;
+
+	let id_length = data[0] as usize;
+	let color_map_type = data[1];
+	let image_type = data[2];
+	let (map_first, map_length, map_entry_bits) = (u16_at(3) as usize, u16_at(5) as usize, data[7]);
+	let (width, height) = (u16_at(12) as u32, u16_at(14) as u32);
+	let bits = data[16];
+	let descriptor = data[17];
+	let has_alpha = descriptor & 0x0F != 0;
+
+	let (rle, kind) = match image_type {
+		1..=3 => (false, image_type),
+		9..=11 => (true, image_type - 8),
+		_ => return invalid(format!("Unsupported TGA image type {}", image_type))
+	};
+
+	let mut offset = HEADER_SIZE + id_length;
+
+	// The color map comes before the pixels, and is present even in true color images that do not use it.
+	let mut palette = Vec::new();
+
+	if color_map_type == 1 {
+		match map_entry_bits {
+			15 | 16 | 24 | 32 => (),
+			_ => return invalid(format!("Unsupported TGA color map entry depth of {} bits", map_entry_bits))
+		}
+
+		let entry_size = (map_entry_bits as usize).div_ceil(8);
+		let end = offset + entry_size * map_length;
+
+		if end > data.len() {
+			return invalid("The TGA color map extends past the end of the file".to_string());
+		}
+
+		for entry in data[offset..end].chunks(entry_size) {
+			palette.push(color(entry, map_entry_bits, has_alpha)?);
+		}
+
+		offset = end;
+	}
+
+	if kind == 1 && palette.is_empty() {
+		return invalid("The color mapped TGA image has no color map".to_string());
+	}
+
+	let pixel_size = (bits as usize).div_ceil(8);
+
+	if pixel_size == 0 || pixel_size > 4 {
+		return invalid(format!("Unsupported TGA pixel depth of {} bits", bits));
+	}
+
+	let count = width as usize * height as usize;
+	let raw = if rle { decompress(data.get(offset..).unwrap_or(&[]), pixel_size, count)? } else {
+		let end = offset + pixel_size * count;
+
+		if end > data.len() {
+			return invalid("The TGA pixels extend past the end of the file".to_string());
+		}
+
+		data[offset..end].to_vec()
+	};
+
+	let mut image = Image::new(width, height, [0, 0, 0, 0])?;
+
+	for (i, pixel) in raw.chunks(pixel_size).enumerate() {
+		let color = match kind {
+			1 => {
+				let index = pixel.iter().rev().fold(0usize, |index, &byte| index << 8 | byte as usize);
+
+				match index.checked_sub(map_first).and_then(|index| palette.get(index)) {
+					Some(&color) => color,
+					None => return invalid(format!("TGA pixel refers to color {}, which is not in the color map", index))
+				}
+			},
+			3 => [pixel[0], pixel[0], pixel[0], if pixel_size > 1 { pixel[1] } else { 255 }],
+			_ => color(pixel, bits, has_alpha)?
+		};
+
+		// Rows are stored from the bottom unless the descriptor says otherwise, and columns from the left.
+		let (mut x, mut y) = (i as u32 % width, i as u32 / width);
+
+		if descriptor & 0x10 != 0 {
+			x = width - 1 - x;
+		}
+
+		if descriptor & 0x20 == 0 {
+			y = height - 1 - y;
+		}
+
+		image.set_pixel(x, y, color);
+	}
+
+	Ok(image)
+}
+
+/// Converts a true color pixel, stored as BGR(A), to RGBA.
+fn color(pixel: &[u8], bits: u8, has_alpha: bool) -> io::Result<[u8; 4]> {
+	match bits {
+		15 | 16 => {
+			let value = pixel[0] as u16 | (pixel[1] as u16) << 8;
+			let expand = |channel: u16| ((channel << 3) | (channel >> 2)) as u8;
+
+			Ok([
+				expand((value >> 10) & 0x1F),
+				expand((value >> 5) & 0x1F),
+				expand(value & 0x1F),
+				if bits == 16 && has_alpha && value & 0x8000 == 0 { 0 } else { 255 }
+			])
+		},
+		24 => Ok([pixel[2], pixel[1], pixel[0], 255]),
+		32 => Ok([pixel[2], pixel[1], pixel[0], pixel[3]]),
+		_ => invalid(format!("Unsupported TGA color depth of {} bits", bits))
+	}
+}
+
+/// Expands run length encoded packets, which may run across the ends of rows.
+fn decompress(data: &[u8], pixel_size: usize, count: usize) -> io::Result<Vec<u8>> {
+	let mut pixels = Vec::new();
+	let mut offset = 0;
+
+	while pixels.len() < pixel_size * count {
+		let header = match data.get(offset) {
+			Some(&header) => header,
+			None => return invalid("The TGA run length encoded pixels end early".to_string())
+		};
+
+		let length = (header & 0x7F) as usize + 1;
+		offset += 1;
+
+		if header & 0x80 != 0 {
+			let pixel = data.get(offset..offset + pixel_size).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "The TGA run length encoded pixels end early"))?;
+
+			for _ in 0..length {
+				pixels.extend_from_slice(pixel);
+			}
+
+			offset += pixel_size;
+		} else {
+			let raw = data.get(offset..offset + pixel_size * length).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "The TGA run length encoded pixels end early"))?;
+
+			pixels.extend_from_slice(raw);
+			offset += pixel_size * length;
+		}
+	}
+
+	// The last packet may cover more pixels than the image has.
+	pixels.truncate(pixel_size * count);
+
+	Ok(pixels)
+}
+
+fn invalid<T>(message: String) -> io::Result<T> {
+	Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::read;
+
+	/// A 1x1 color mapped image with a single color map entry of the given depth.
+	fn color_mapped(entry_bits: u8, entry: &[u8]) -> Vec<u8> {
+		let mut data = vec![0, 1, 1, 0, 0, 1, 0, entry_bits, 0, 0, 0, 0, 1, 0, 1, 0, 8, 0];
+		data.extend_from_slice(entry);
+		data.push(0);
+		data
+	}
+
+	/// A 3x2 24-bit run length encoded image: a run of four red pixels that crosses into the second row,
+	/// followed by a raw packet of a blue and a green pixel.
+	fn run_length_encoded(descriptor: u8) -> Vec<u8> {
+		let mut data = vec![0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 2, 0, 24, descriptor];
+		data.extend_from_slice(&[0x83, 0, 0, 255]);
+		data.extend_from_slice(&[0x01, 255, 0, 0, 0, 255, 0]);
+		data
+	}
+
+	#[test]
+	fn runs_cross_rows_from_the_bottom() {
+		let image = read(&mut &run_length_encoded(0)[..]).unwrap();
+		let (red, blue, green) = ([255, 0, 0, 255], [0, 0, 255, 255], [0, 255, 0, 255]);
+
+		assert_eq!([image.pixel(0, 1), image.pixel(1, 1), image.pixel(2, 1)], [red, red, red]);
+		assert_eq!([image.pixel(0, 0), image.pixel(1, 0), image.pixel(2, 0)], [red, blue, green]);
+	}
+
+	#[test]
+	fn runs_cross_rows_from_the_top() {
+		let image = read(&mut &run_length_encoded(0x20)[..]).unwrap();
+		let (red, blue, green) = ([255, 0, 0, 255], [0, 0, 255, 255], [0, 255, 0, 255]);
+
+		assert_eq!([image.pixel(0, 0), image.pixel(1, 0), image.pixel(2, 0)], [red, red, red]);
+		assert_eq!([image.pixel(0, 1), image.pixel(1, 1), image.pixel(2, 1)], [red, blue, green]);
+	}
+
+	#[test]
+	fn truncated_runs_are_rejected() {
+		let data = run_length_encoded(0);
+
+		for len in 18..data.len() {
+			assert!(read(&mut &data[..len]).is_err(), "{} bytes", len);
+		}
+	}
+
+	#[test]
+	fn color_mapped_pixels_are_looked_up() {
+		let image = read(&mut &color_mapped(24, &[1, 2, 3])[..]).unwrap();
+
+		assert_eq!(image.pixel(0, 0), [3, 2, 1, 255]);
+	}
+
+	#[test]
+	fn undecodable_color_map_depths_are_rejected() {
+		assert!(read(&mut &color_mapped(0, &[])[..]).is_err());
+		assert!(read(&mut &color_mapped(8, &[1])[..]).is_err());
+	}
+}
@@ -0,0 +1,394 @@
+//! A minimal JSON document model, just enough for the interchange formats.
+
+use std::fmt::{self, Write};
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Value>),
+	/// Members are kept in insertion order, so that output is deterministic.
+	Object(Vec<(String, Value)>)
+}
+
+impl Value {
+	pub fn object() -> Self {
+		Value::Object(Vec::new())
+	}
+
+	/// Adds a member to an object, returning the object. Has no effect on other values.
+	pub fn with<V>(mut self, key: &str, value: V) -> Self where V: Into<Value> {
+		self.insert(key, value);
+		self
+	}
+
+	pub fn insert<V>(&mut self, key: &str, value: V) where V: Into<Value> {
+		if let Value::Object(ref mut members) = *self {
+			members.push((key.to_string(), value.into()));
+		}
+	}
+
+	/// Looks up a member of an object.
+	pub fn get(&self, key: &str) -> Option<&Value> {
+		match *self {
+			Value::Object(ref members) => members.iter().find(|member| member.0 == key).map(|member| &member.1),
+			_ => None
+		}
+	}
+
+	pub fn as_f64(&self) -> Option<f64> {
+		match *self {
+			Value::Number(value) => Some(value),
+			_ => None
+		}
+	}
+
+	pub fn as_usize(&self) -> Option<usize> {
+		match *self {
+			Value::Number(value) if value >= 0.0 && value.fract() == 0.0 => Some(value as usize),
+			_ => None
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match *self {
+			Value::String(ref value) => Some(value),
+			_ => None
+		}
+	}
+
+	/// Returns the elements of an array, or an empty slice for anything else.
+	pub fn elements(&self) -> &[Value] {
+		match *self {
+			Value::Array(ref elements) => elements,
+			_ => &[]
+		}
+	}
+
+	/// Parses a complete JSON document.
+	pub fn parse(text: &str) -> Result<Value, String> {
+		let mut parser = Parser { chars: text.char_indices().peekable(), depth: 0 };
+		let value = parser.value()?;
+
+		parser.whitespace();
+
+		match parser.chars.next() {
+			None => Ok(value),
+			Some((at, c)) => Err(format!("Unexpected '{}' at byte {} after the end of the document", c, at))
+		}
+	}
+}
+
+/// Arrays and objects nested deeper than this are rejected, since each level of nesting recurses.
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'t> {
+	chars: Peekable<CharIndices<'t>>,
+	/// Number of arrays and objects that enclose the current value.
+	depth: usize
+}
+
+impl<'t> Parser<'t> {
+	fn whitespace(&mut self) {
+		while let Some(&(_, c)) = self.chars.peek() {
+			if !c.is_whitespace() {
+				break;
+			}
+
+			self.chars.next();
+		}
+	}
+
+	fn expect(&mut self, expected: char) -> Result<(), String> {
+		match self.chars.next() {
+			Some((_, c)) if c == expected => Ok(()),
+			Some((at, c)) => Err(format!("Expected '{}' at byte {}, got '{}'", expected, at, c)),
+			None => Err(format!("Expected '{}', got the end of the document", expected))
+		}
+	}
+
+	fn literal(&mut self, literal: &str, value: Value) -> Result<Value, String> {
+		for expected in literal.chars() {
+			self.expect(expected)?;
+		}
+
+		Ok(value)
+	}
+
+	fn value(&mut self) -> Result<Value, String> {
+		self.whitespace();
+
+		let c = match self.chars.peek() {
+			Some(&(_, c)) => c,
+			None => return Err("Unexpected end of the document".to_string())
+		};
+
+		match c {
+			'n' => self.literal("null", Value::Null),
+			't' => self.literal("true", Value::Bool(true)),
+			'f' => self.literal("false", Value::Bool(false)),
+			'"' => self.string().map(Value::String),
+			'[' | '{' => {
+				if self.depth == MAX_DEPTH {
+					return Err("Nesting too deep".to_string());
+				}
+
+				self.depth += 1;
+				let value = if c == '[' { self.array() } else { self.object() };
+				self.depth -= 1;
+
+				value
+			},
+			_ => self.number()
+		}
+	}
+
+	fn array(&mut self) -> Result<Value, String> {
+		self.chars.next();
+
+		let mut elements = Vec::new();
+		self.whitespace();
+
+		if let Some(&(_, ']')) = self.chars.peek() {
+			self.chars.next();
+			return Ok(Value::Array(elements));
+		}
+
+		loop {
+			elements.push(self.value()?);
+			self.whitespace();
+
+			match self.chars.next() {
+				Some((_, ',')) => continue,
+				Some((_, ']')) => return Ok(Value::Array(elements)),
+				Some((at, c)) => return Err(format!("Expected ',' or ']' at byte {}, got '{}'", at, c)),
+				None => return Err("Unterminated array".to_string())
+			}
+		}
+	}
+
+	fn object(&mut self) -> Result<Value, String> {
+		self.chars.next();
+
+		let mut members = Vec::new();
+		self.whitespace();
+
+		if let Some(&(_, '}')) = self.chars.peek() {
+			self.chars.next();
+			return Ok(Value::Object(members));
+		}
+
+		loop {
+			self.whitespace();
+			let key = self.string()?;
+
+			self.whitespace();
+			self.expect(':')?;
+
+			members.push((key, self.value()?));
+			self.whitespace();
+
+			match self.chars.next() {
+				Some((_, ',')) => continue,
+				Some((_, '}')) => return Ok(Value::Object(members)),
+				Some((at, c)) => return Err(format!("Expected ',' or '}}' at byte {}, got '{}'", at, c)),
+				None => return Err("Unterminated object".to_string())
+			}
+		}
+	}
+
+	fn number(&mut self) -> Result<Value, String> {
+		let mut number = String::new();
+
+		while let Some(&(_, c)) = self.chars.peek() {
+			match c {
+				'0'..='9' | '-' | '+' | '.' | 'e' | 'E' => number.push(c),
+				_ => break
+			}
+
+			self.chars.next();
+		}
+
+		number.parse().map(Value::Number).map_err(|_| format!("Invalid number '{}'", number))
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		self.expect('"')?;
+
+		let mut string = String::new();
+
+		loop {
+			match self.chars.next() {
+				Some((_, '"')) => return Ok(string),
+				Some((_, '\\')) => match self.chars.next() {
+					Some((_, '"')) => string.push('"'),
+					Some((_, '\\')) => string.push('\\'),
+					Some((_, '/')) => string.push('/'),
+					Some((_, 'b')) => string.push('\u{8}'),
+					Some((_, 'f')) => string.push('\u{c}'),
+					Some((_, 'n')) => string.push('\n'),
+					Some((_, 'r')) => string.push('\r'),
+					Some((_, 't')) => string.push('\t'),
+					Some((_, 'u')) => {
+						let high = self.hex4()?;
+
+						let code = if (0xD800..0xDC00).contains(&high) {
+							self.expect('\\')?;
+							self.expect('u')?;
+
+							let low = self.hex4()?;
+							0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)
+						} else {
+							high
+						};
+
+						string.push(::std::char::from_u32(code).unwrap_or('\u{FFFD}'));
+					},
+					other => return Err(format!("Invalid escape sequence '\\{}'", other.map(|(_, c)| c).unwrap_or(' ')))
+				},
+				Some((_, c)) => string.push(c),
+				None => return Err("Unterminated string".to_string())
+			}
+		}
+	}
+
+	fn hex4(&mut self) -> Result<u32, String> {
+		let digits: String = self.chars.by_ref().take(4).map(|(_, c)| c).collect();
+
+		u32::from_str_radix(&digits, 16).map_err(|_| format!("Invalid unicode escape '\\u{}'", digits))
+	}
+}
+
+impl From<bool> for Value {
+	fn from(value: bool) -> Self {
+		Value::Bool(value)
+	}
+}
+
+impl From<u32> for Value {
+	fn from(value: u32) -> Self {
+		Value::Number(value as f64)
+	}
+}
+
+impl From<usize> for Value {
+	fn from(value: usize) -> Self {
+		Value::Number(value as f64)
+	}
+}
+
+impl From<f32> for Value {
+	fn from(value: f32) -> Self {
+		Value::Number(value as f64)
+	}
+}
+
+impl<'a> From<&'a str> for Value {
+	fn from(value: &'a str) -> Self {
+		Value::String(value.to_string())
+	}
+}
+
+impl From<String> for Value {
+	fn from(value: String) -> Self {
+		Value::String(value)
+	}
+}
+
+impl<T> From<Vec<T>> for Value where T: Into<Value> {
+	fn from(value: Vec<T>) -> Self {
+		Value::Array(value.into_iter().map(Into::into).collect())
+	}
+}
+
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Value::Null => f.write_str("null"),
+			Value::Bool(value) => write!(f, "{}", value),
+			Value::Number(value) => write_number(f, value),
+			Value::String(ref value) => write_string(f, value),
+			Value::Array(ref elements) => {
+				f.write_char('[')?;
+
+				for (i, element) in elements.iter().enumerate() {
+					if i != 0 {
+						f.write_char(',')?;
+					}
+
+					write!(f, "{}", element)?;
+				}
+
+				f.write_char(']')
+			},
+			Value::Object(ref members) => {
+				f.write_char('{')?;
+
+				for (i, (key, value)) in members.iter().enumerate() {
+					if i != 0 {
+						f.write_char(',')?;
+					}
+
+					write_string(f, key)?;
+					write!(f, ":{}", value)?;
+				}
+
+				f.write_char('}')
+			}
+		}
+	}
+}
+
+/// Writes integers without a fractional part, and values that came from an f32 with the shortest f32 representation.
+fn write_number(f: &mut fmt::Formatter, value: f64) -> fmt::Result {
+	if !value.is_finite() {
+		// JSON has no representation for these.
+		f.write_str("null")
+	} else if value.fract() == 0.0 && value.abs() < 9007199254740992.0 {
+		write!(f, "{}", value as i64)
+	} else if (value as f32) as f64 == value {
+		write!(f, "{:?}", value as f32)
+	} else {
+		write!(f, "{:?}", value)
+	}
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+	f.write_char('"')?;
+
+	for c in value.chars() {
+		match c {
+			'"' => f.write_str("\\\"")?,
+			'\\' => f.write_str("\\\\")?,
+			'\n' => f.write_str("\\n")?,
+			'\r' => f.write_str("\\r")?,
+			'\t' => f.write_str("\\t")?,
+			c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+			c => f.write_char(c)?
+		}
+	}
+
+	f.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Value, MAX_DEPTH};
+
+	#[test]
+	fn nesting_up_to_the_limit_is_parsed() {
+		let text = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+
+		assert!(Value::parse(&text).is_ok());
+	}
+
+	#[test]
+	fn deep_nesting_is_rejected() {
+		assert_eq!(Value::parse(&"[".repeat(100_000)), Err("Nesting too deep".to_string()));
+		assert_eq!(Value::parse(&"{\"a\":".repeat(100_000)), Err("Nesting too deep".to_string()));
+	}
+}
@@ -1,6 +1,16 @@
 extern crate byteorder;
 extern crate cgmath;
 
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
+
+#[cfg(all(test, feature = "serde"))]
+extern crate serde;
+
+#[cfg(all(test, feature = "serde"))]
+extern crate bincode;
+
 pub mod scene;
 
 /// V1 model format. Found rarely in Empire Earth 1, but not the native format of any released game.
@@ -17,10 +27,79 @@ pub mod v5;
 
 pub mod collider;
 
+/// Decomposition of frame transforms into translation, rotation and scale.
+pub mod transform;
+
+/// Tag points by name, across the frames of a model.
+pub mod tags;
+
+/// Diff-friendly text representation of model scenes.
+pub mod text;
+
+/// Byte-coverage tracing, for reverse engineering the unknown parts of the format.
+pub mod trace;
+
+/// Annotated hex dumps of model files.
+pub mod dump;
+
+/// Canonical content hashes, for finding duplicate models.
+pub mod fingerprint;
+
+/// Recovery of truncated or corrupted models.
+pub mod recover;
+
+/// glTF 2.0 interchange.
+pub mod gltf;
+
+/// Wavefront OBJ interchange.
+pub mod obj;
+
+/// COLLADA 1.4 export, for older modding tools.
+pub mod collada;
+
+/// MDD and PC2 point caches of vertex animation.
+pub mod pointcache;
+
+/// Quake MD2 and MD3 interchange.
+pub mod quake;
+
+/// PLY export of single frames, for scanning and analysis tools.
+pub mod ply;
+
+/// STL export of single frames, for 3D printing.
+pub mod stl;
+
+/// Orthographic rendering of frames, without a GPU.
+pub mod render;
+
+/// RGBA images, for rendering and textures.
+pub mod image;
+
+/// Lookup of material textures in game data directories.
+pub mod textures;
+
+/// SSA archives, which hold the game data files.
+pub mod ssa;
+
+mod json;
+
 mod encode;
 
+#[cfg(test)]
+mod samples;
+
 use std::io::{self, Read, Write};
-use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
+use byteorder::{WriteBytesExt, LittleEndian};
+use trace::{Fields, Untraced};
+
+/// Upper bound on how many elements are allocated up front for a count read from a file, so that a corrupt count
+/// cannot exhaust memory. Longer lists still grow as they are read.
+const MAX_PREALLOCATION: usize = 4096;
+
+/// Allocates a vector for a number of elements read from a file, within `MAX_PREALLOCATION`.
+fn preallocate<T>(count: usize) -> Vec<T> {
+	Vec::with_capacity(count.min(MAX_PREALLOCATION))
+}
 
 /// The expected magic number for all CEM models. If this does not match, then
 /// this file is almost certainly not a CEM file.
@@ -38,20 +117,27 @@ pub use scene::{Scene, Model};
 
 /// The header, contains the magic number and revision. The current revision is 2.
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ModelHeader {
 	pub magic: u32,
 	pub major: u16,
 	pub minor: u16
 }
 
-impl Encode for ModelHeader {
-	fn read<R>(r: &mut R) -> io::Result<Self> where R: Read {
+impl ModelHeader {
+	pub fn read_fields<F>(r: &mut F) -> io::Result<Self> where F: Fields {
 		Ok(ModelHeader {
-			magic: r.read_u32::<LittleEndian>()?,
-			major: r.read_u16::<LittleEndian>()?,
-			minor: r.read_u16::<LittleEndian>()?
+			magic: r.u32("magic")?,
+			major: r.u16("major")?,
+			minor: r.u16("minor")?
 		})
 	}
+}
+
+impl Encode for ModelHeader {
+	fn read<R>(r: &mut R) -> io::Result<Self> where R: Read {
+		Self::read_fields(&mut Untraced(r))
+	}
 
 	fn write<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
 		w.write_u32::<LittleEndian>(self.magic)?;
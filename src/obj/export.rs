@@ -0,0 +1,244 @@
+use std::io::{self, Write, BufWriter};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use cgmath::{Matrix4, SquareMatrix, Matrix, InnerSpace, Transform};
+use scene::Scene;
+use v2::{V2, Frame, Special};
+use gltf::TAG_POINT_PREFIX;
+use super::sanitize;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ExportOptions {
+	/// LOD level to write. Models with fewer levels use their last one.
+	pub lod: usize,
+	/// Apply the frame transform to positions and normals, so that the geometry appears as it does in game.
+	pub apply_transform: bool,
+	/// Write V as `1 - v`.
+	pub flip_v: bool
+}
+
+impl Default for ExportOptions {
+	fn default() -> Self {
+		ExportOptions {
+			lod: 0,
+			apply_transform: true,
+			flip_v: true
+		}
+	}
+}
+
+/// A flattened list of models, with unique material names shared between the OBJ and MTL files.
+#[derive(Debug)]
+pub struct Export<'s> {
+	nodes: Vec<(&'s str, &'s V2)>,
+	/// Name of each material of each node in the MTL file.
+	material_names: Vec<Vec<String>>,
+	/// Name, texture and special meaning of each entry in the MTL file.
+	library: Vec<(String, &'s str, Option<Special>)>,
+	options: ExportOptions
+}
+
+impl<'s> Export<'s> {
+	/// Flattens a scene, writing every node as its own object.
+	pub fn new(scene: &'s Scene<V2>, options: &ExportOptions) -> Self {
+		let mut nodes = Vec::new();
+		flatten(scene, &mut nodes);
+
+		Self::from_nodes(nodes, options)
+	}
+
+	/// Exports a single model.
+	pub fn model(name: &'s str, model: &'s V2, options: &ExportOptions) -> Self {
+		Self::from_nodes(vec![(name, model)], options)
+	}
+
+	fn from_nodes(nodes: Vec<(&'s str, &'s V2)>, options: &ExportOptions) -> Self {
+		let mut library: Vec<(String, &'s str, Option<Special>)> = Vec::new();
+
+		let material_names = nodes.iter().map(|&(_, model)| model.materials.iter().map(|material| {
+			let base = sanitize(&material.name);
+			let mut name = base.clone();
+			let mut suffix = 1;
+
+			// Materials with the same name and texture share an entry, others get a numbered name.
+			loop {
+				match library.iter().find(|entry| entry.0 == name) {
+					Some(entry) if entry.1 == material.texture_name => return name,
+					Some(_) => {
+						name = format!("{}.{}", base, suffix);
+						suffix += 1;
+					},
+					None => {
+						library.push((name.clone(), &material.texture_name, material.special()));
+						return name;
+					}
+				}
+			}
+		}).collect()).collect();
+
+		Export {
+			nodes,
+			material_names,
+			library,
+			options: *options
+		}
+	}
+
+	/// The number of frames in the longest animation. Models with fewer frames hold their last frame.
+	pub fn frames(&self) -> usize {
+		self.nodes.iter().map(|&(_, model)| model.frames.len()).max().unwrap_or(0)
+	}
+
+	/// Writes the geometry of one frame. If `mtllib` is given, the file refers to that material library.
+	pub fn write_obj<W>(&self, w: &mut W, frame: usize, mtllib: Option<&str>) -> io::Result<()> where W: Write {
+		if let Some(mtllib) = mtllib {
+			writeln!(w, "mtllib {}", mtllib)?;
+		}
+
+		// OBJ indices are 1-based and count across the whole file. Tag points add positions but no normals or
+		// texture coordinates, so positions are counted separately.
+		let mut base = 1;
+		let mut position_base = 1;
+
+		for (&(name, model), material_names) in self.nodes.iter().zip(&self.material_names) {
+			let frame = match model.frames.get(frame).or_else(|| model.frames.last()) {
+				Some(frame) if !frame.vertices.is_empty() => frame,
+				_ => continue
+			};
+
+			writeln!(w, "o {}", sanitize(name))?;
+			self.write_vertices(w, frame)?;
+
+			let lod = self.options.lod.min(model.lod_levels.len().saturating_sub(1));
+
+			for (material, material_name) in model.materials.iter().zip(material_names) {
+				let triangles = model.selection(material, lod);
+
+				if triangles.is_empty() {
+					continue;
+				}
+
+				writeln!(w, "usemtl {}", material_name)?;
+
+				for triangle in triangles {
+					let corners = [triangle.0 as usize, triangle.1 as usize, triangle.2 as usize];
+
+					write!(w, "f")?;
+
+					for &corner in &corners {
+						write!(w, " {}/{1}/{1}", position_base + corner, base + corner)?;
+					}
+
+					writeln!(w)?;
+				}
+			}
+
+			base += frame.vertices.len();
+			position_base += frame.vertices.len();
+
+			// Tag points missing from the frame are left out.
+			for (tag_point, &position) in model.tag_points.iter().zip(&frame.tag_points) {
+				let p = if self.options.apply_transform { frame.transform.transform_point(position) } else { position };
+
+				writeln!(w, "o {}{}", TAG_POINT_PREFIX, sanitize(tag_point))?;
+				writeln!(w, "v {} {} {}", p.x, p.y, p.z)?;
+				writeln!(w, "p {}", position_base)?;
+
+				position_base += 1;
+			}
+		}
+
+		Ok(())
+	}
+
+	fn write_vertices<W>(&self, w: &mut W, frame: &Frame) -> io::Result<()> where W: Write {
+		let (transform, normal_transform) = if self.options.apply_transform {
+			// Normals are transformed by the inverse transpose, so that they stay perpendicular under non-uniform scale.
+			(frame.transform, frame.transform.invert().unwrap_or_else(Matrix4::identity).transpose())
+		} else {
+			(Matrix4::identity(), Matrix4::identity())
+		};
+
+		for vertex in &frame.vertices {
+			let p = transform.transform_point(vertex.position);
+			writeln!(w, "v {} {} {}", p.x, p.y, p.z)?;
+		}
+
+		for vertex in &frame.vertices {
+			let mut n = normal_transform.transform_vector(vertex.normal);
+
+			if self.options.apply_transform && n.magnitude2() > 0.0 {
+				n = n.normalize();
+			}
+
+			writeln!(w, "vn {} {} {}", n.x, n.y, n.z)?;
+		}
+
+		for vertex in &frame.vertices {
+			let v = if self.options.flip_v { 1.0 - vertex.texture.y } else { vertex.texture.y };
+			writeln!(w, "vt {} {}", vertex.texture.x, v)?;
+		}
+
+		Ok(())
+	}
+
+	/// Writes the material library, with the texture of each material as its diffuse map. MTL has no way to
+	/// mark special materials such as player color, so their meaning is written in a comment before them.
+	pub fn write_mtl<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
+		for (i, (name, texture_name, special)) in self.library.iter().enumerate() {
+			if i != 0 {
+				writeln!(w)?;
+			}
+
+			if let Some(special) = special {
+				writeln!(w, "# special: {}", special.name())?;
+			}
+
+			writeln!(w, "newmtl {}", name)?;
+			writeln!(w, "Ka 0 0 0")?;
+			writeln!(w, "Kd 1 1 1")?;
+			writeln!(w, "Ks 0 0 0")?;
+			writeln!(w, "illum 1")?;
+
+			if !texture_name.is_empty() {
+				writeln!(w, "map_Kd {}", texture_name.replace('\\', "/"))?;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Writes `<stem>.mtl` and one numbered `<stem>_0000.obj` file per frame into a directory, returning the paths written.
+	pub fn write_sequence(&self, directory: &Path, stem: &str) -> io::Result<Vec<PathBuf>> {
+		let mtl_name = format!("{}.mtl", stem);
+		let mtl_path = directory.join(&mtl_name);
+
+		let mut w = BufWriter::new(File::create(&mtl_path)?);
+		self.write_mtl(&mut w)?;
+		w.flush()?;
+
+		let frames = self.frames();
+		let width = frames.saturating_sub(1).to_string().len().max(4);
+		let mut paths = vec![mtl_path];
+
+		for frame in 0..frames {
+			let path = directory.join(format!("{}_{:02$}.obj", stem, frame, width));
+			let mut w = BufWriter::new(File::create(&path)?);
+
+			self.write_obj(&mut w, frame, Some(&mtl_name))?;
+			w.flush()?;
+
+			paths.push(path);
+		}
+
+		Ok(paths)
+	}
+}
+
+fn flatten<'s>(scene: &'s Scene<V2>, nodes: &mut Vec<(&'s str, &'s V2)>) {
+	nodes.push((&scene.name, &scene.model));
+
+	for child in &scene.children {
+		flatten(child, nodes);
+	}
+}
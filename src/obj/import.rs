@@ -0,0 +1,329 @@
+use std::io;
+use std::fs;
+use std::path::Path;
+use std::collections::HashMap;
+use cgmath::{Point2, Point3, Vector3, InnerSpace};
+use collider::CenterBuilder;
+use v2::{V2, Material, TriangleSelection, Frame, Vertex, Triangle};
+use gltf::TAG_POINT_PREFIX;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ImportOptions {
+	/// Read V as `1 - v`, undoing the flip done on export.
+	pub flip_v: bool
+}
+
+impl Default for ImportOptions {
+	fn default() -> Self {
+		ImportOptions {
+			flip_v: true
+		}
+	}
+}
+
+/// Reads an OBJ file into a single frame model. Material libraries named by `mtllib` are passed to `load`.
+/// Corners are split into unique vertices, and triangles are sorted by material so that each material
+/// selects a contiguous range of triangles and vertices. Objects named with `TAG_POINT_PREFIX` become tag points at
+/// their first point element.
+pub fn import<F>(data: &[u8], mut load: F, options: &ImportOptions) -> io::Result<V2> where F: FnMut(&str) -> io::Result<Vec<u8>> {
+	let mut positions = Vec::new();
+	let mut normals = Vec::new();
+	let mut texcoords = Vec::new();
+	let mut textures = HashMap::new();
+	let mut groups: Vec<Group> = Vec::new();
+	let mut current = None;
+	let mut object = String::new();
+	let mut tag_points = Vec::new();
+	let mut tag_positions = Vec::new();
+
+	for (number, line) in lines(data) {
+		let error = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("OBJ line {}: {}", number, message));
+		let mut tokens = line.split_whitespace();
+
+		match tokens.next() {
+			Some("v") => positions.push(Point3::from(floats::<3>(tokens).map_err(error)?)),
+			Some("vn") => normals.push(Vector3::from(floats::<3>(tokens).map_err(error)?)),
+			Some("vt") => {
+				// The V coordinate is optional.
+				let mut tokens = tokens.chain(Some("0"));
+				let [u, v] = floats::<2>(&mut tokens).map_err(error)?;
+
+				texcoords.push(Point2::new(u, if options.flip_v { 1.0 - v } else { v }));
+			},
+			Some("f") => {
+				let mut corners = Vec::new();
+
+				for token in tokens {
+					corners.push(corner(token, positions.len(), texcoords.len(), normals.len()).map_err(error)?);
+				}
+
+				if corners.len() < 3 {
+					return Err(error(format!("A face needs at least 3 corners, got {}", corners.len())));
+				}
+
+				let group = match current {
+					Some(group) => group,
+					None => {
+						groups.push(Group::new("default".to_string()));
+						current = Some(groups.len() - 1);
+						groups.len() - 1
+					}
+				};
+
+				let group = &mut groups[group];
+				let indices: Vec<u32> = corners.into_iter().map(|corner| group.vertex(corner)).collect();
+
+				// Polygons are split into a fan around the first corner.
+				for i in 1..indices.len() - 1 {
+					group.triangles.push((indices[0], indices[i], indices[i + 1]));
+				}
+			},
+			Some("o") => object = rest(&line, "o").to_string(),
+			Some("p") if object.starts_with(TAG_POINT_PREFIX) => {
+				let token = tokens.next().ok_or_else(|| error("A point needs a position".to_string()))?;
+				let (position, _, _) = corner(token, positions.len(), texcoords.len(), normals.len()).map_err(error)?;
+
+				tag_points.push(object[TAG_POINT_PREFIX.len()..].to_string());
+				tag_positions.push(positions[position]);
+
+				// Later points of the same object are not separate tag points.
+				object.clear();
+			},
+			Some("usemtl") => {
+				let name = rest(&line, "usemtl");
+
+				current = match groups.iter().position(|group| group.name == name) {
+					Some(group) => Some(group),
+					None => {
+						groups.push(Group::new(name.to_string()));
+						Some(groups.len() - 1)
+					}
+				};
+			},
+			Some("mtllib") => for library in tokens {
+				let data = load(library).map_err(|e| io::Error::new(e.kind(), format!("Failed to load material library '{}': {}", library, e)))?;
+				read_mtl(&data, &mut textures);
+			},
+			_ => ()
+		}
+	}
+
+	if groups.iter().all(|group| group.triangles.is_empty()) {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, "The OBJ file contains no faces"));
+	}
+
+	let mut triangles = Vec::new();
+	let mut vertices = Vec::new();
+	let mut materials = Vec::new();
+
+	for group in groups.into_iter().filter(|group| !group.triangles.is_empty()) {
+		let vertex_offset = vertices.len() as u32;
+
+		materials.push(Material {
+			texture: 0,
+			triangles: vec![TriangleSelection { offset: triangles.len() as u32, len: group.triangles.len() as u32 }],
+			vertex_offset,
+			vertex_count: group.corners.len() as u32,
+			texture_name: textures.get(&group.name).cloned().unwrap_or_default(),
+			name: group.name
+		});
+
+		let mut group_vertices: Vec<Vertex> = group.corners.iter().map(|&(position, texcoord, normal)| Vertex {
+			position: positions[position],
+			normal: normal.map(|normal| normals[normal]).unwrap_or_else(|| Vector3::new(0.0, 0.0, 0.0)),
+			texture: texcoord.map(|texcoord| texcoords[texcoord]).unwrap_or_else(|| Point2::new(0.0, 0.0))
+		}).collect();
+
+		// Corners without a normal get the average of the faces around them.
+		for triangle in &group.triangles {
+			let (a, b, c) = (triangle.0 as usize, triangle.1 as usize, triangle.2 as usize);
+			let face = (group_vertices[b].position - group_vertices[a].position).cross(group_vertices[c].position - group_vertices[a].position);
+
+			for &corner in &[a, b, c] {
+				if group.corners[corner].2.is_none() {
+					group_vertices[corner].normal += face;
+				}
+			}
+		}
+
+		for (vertex, corner) in group_vertices.iter_mut().zip(&group.corners) {
+			if corner.2.is_none() && vertex.normal.magnitude2() > 0.0 {
+				vertex.normal = vertex.normal.normalize();
+			}
+		}
+
+		triangles.extend(group.triangles.iter().map(|t| (t.0 + vertex_offset, t.1 + vertex_offset, t.2 + vertex_offset)));
+		vertices.extend(group_vertices);
+	}
+
+	let mut center = CenterBuilder::begin();
+
+	for vertex in &vertices {
+		center.update(vertex.position);
+	}
+
+	let center = center.build();
+
+	Ok(V2 {
+		center,
+		lod_levels: vec![triangles],
+		materials,
+		tag_points,
+		frames: vec![Frame::from_vertices(vertices, tag_positions, center)]
+	})
+}
+
+/// Reads an OBJ file from disk, loading material libraries relative to it.
+pub fn import_file<P>(path: P, options: &ImportOptions) -> io::Result<V2> where P: AsRef<Path> {
+	let path = path.as_ref();
+	let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+	import(&fs::read(path)?, |name| fs::read(directory.join(name)), options)
+}
+
+/// Indices of the position, texture coordinate and normal of a corner.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// The faces using one material.
+struct Group {
+	name: String,
+	/// Each unique corner, which becomes a vertex.
+	corners: Vec<Corner>,
+	lookup: HashMap<Corner, u32>,
+	triangles: Vec<Triangle>
+}
+
+impl Group {
+	fn new(name: String) -> Self {
+		Group {
+			name,
+			corners: Vec::new(),
+			lookup: HashMap::new(),
+			triangles: Vec::new()
+		}
+	}
+
+	fn vertex(&mut self, corner: Corner) -> u32 {
+		let next = self.corners.len() as u32;
+		let index = *self.lookup.entry(corner).or_insert(next);
+
+		if index == next {
+			self.corners.push(corner);
+		}
+
+		index
+	}
+}
+
+/// Splits a file into numbered lines, without comments and with continued lines joined.
+fn lines(data: &[u8]) -> Vec<(usize, String)> {
+	let text = String::from_utf8_lossy(data);
+	let mut lines = Vec::new();
+	let mut pending = String::new();
+	let mut start = 0;
+
+	for (i, line) in text.lines().enumerate() {
+		if pending.is_empty() {
+			start = i + 1;
+		}
+
+		let line = line.split('#').next().unwrap_or("");
+
+		match line.strip_suffix('\\') {
+			Some(line) => {
+				pending.push_str(line);
+				pending.push(' ');
+			},
+			None => {
+				pending.push_str(line);
+				lines.push((start, pending.trim().to_string()));
+				pending.clear();
+			}
+		}
+	}
+
+	if !pending.is_empty() {
+		lines.push((start, pending.trim().to_string()));
+	}
+
+	lines
+}
+
+/// Everything after the keyword, for names that may contain spaces.
+fn rest<'l>(line: &'l str, keyword: &str) -> &'l str {
+	line[keyword.len()..].trim()
+}
+
+fn floats<'t, const N: usize>(mut tokens: impl Iterator<Item = &'t str>) -> Result<[f32; N], String> {
+	let mut values = [0.0; N];
+
+	for value in values.iter_mut() {
+		let token = tokens.next().ok_or_else(|| format!("Expected {} numbers", N))?;
+		*value = token.parse().map_err(|_| format!("Invalid number '{}'", token))?;
+	}
+
+	Ok(values)
+}
+
+/// Parses a face corner such as `1/2/3`, `1//3` or `-1`, resolving relative indices.
+fn corner(token: &str, positions: usize, texcoords: usize, normals: usize) -> Result<Corner, String> {
+	let resolve = |part: Option<&str>, count: usize, kind: &str| -> Result<Option<usize>, String> {
+		let part = match part {
+			Some(part) if !part.is_empty() => part,
+			_ => return Ok(None)
+		};
+
+		let index: i64 = part.parse().map_err(|_| format!("Invalid {} index '{}'", kind, part))?;
+
+		let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+
+		if resolved < 0 || resolved >= count as i64 {
+			return Err(format!("Face refers to {} {}, but only {} are defined", kind, index, count));
+		}
+
+		Ok(Some(resolved as usize))
+	};
+
+	let mut parts = token.split('/');
+	let position = resolve(parts.next(), positions, "position")?;
+	let texcoord = resolve(parts.next(), texcoords, "texture coordinate")?;
+	let normal = resolve(parts.next(), normals, "normal")?;
+
+	match position {
+		Some(position) => Ok((position, texcoord, normal)),
+		None => Err(format!("Face corner '{}' has no position", token))
+	}
+}
+
+/// Collects the diffuse texture of each material in a material library.
+fn read_mtl(data: &[u8], textures: &mut HashMap<String, String>) {
+	let mut current = None;
+
+	for (_, line) in lines(data) {
+		match line.split_whitespace().next() {
+			Some("newmtl") => current = Some(rest(&line, "newmtl").to_string()),
+			Some("map_Kd") => if let Some(ref name) = current {
+				textures.insert(name.clone(), texture_file(rest(&line, "map_Kd")));
+			},
+			_ => ()
+		}
+	}
+}
+
+/// Skips the options in front of the file name of a texture map, such as `-s 1 1 1` or `-clamp on`.
+fn texture_file(arguments: &str) -> String {
+	let mut tokens = arguments.split_whitespace().peekable();
+
+	while let Some(option) = tokens.peek().cloned().filter(|token| token.starts_with('-')) {
+		tokens.next();
+
+		match option {
+			"-imfchan" | "-type" => { tokens.next(); },
+			_ => while tokens.peek().map(|&token| token.parse::<f32>().is_ok() || token == "on" || token == "off").unwrap_or(false) {
+				tokens.next();
+			}
+		}
+	}
+
+	tokens.collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,84 @@
+//! Conversion between scenes and Wavefront OBJ files with their MTL material libraries.
+//! Texture coordinates are flipped vertically by default, since OBJ puts the origin at the bottom left.
+//! Tag points become objects named with `gltf::TAG_POINT_PREFIX`, each holding a single point element.
+
+mod export;
+mod import;
+
+pub use self::export::{Export, ExportOptions};
+pub use self::import::{ImportOptions, import, import_file};
+
+/// Replaces characters that OBJ and MTL files cannot hold in a name.
+fn sanitize(name: &str) -> String {
+	let sanitized: String = name.chars().map(|c| if c.is_whitespace() || c.is_control() || c == '#' { '_' } else { c }).collect();
+
+	if sanitized.is_empty() {
+		"unnamed".to_string()
+	} else {
+		sanitized
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Point2, Point3, Vector3};
+	use scene::Scene;
+	use v2::{V2, Frame, Material, TriangleSelection, Vertex};
+	use super::{Export, ExportOptions, ImportOptions, import};
+
+	fn model(material: &str, texture_name: &str, tag_point: &str, offset: f32) -> V2 {
+		let vertices = (0..3).map(|i| Vertex {
+			position: Point3::new(offset + i as f32, 0.5 * i as f32, -1.0),
+			normal: Vector3::new(0.0, 0.0, 1.0),
+			texture: Point2::new(0.5, 0.25 * i as f32)
+		}).collect();
+
+		V2 {
+			center: Point3::new(offset, 0.0, 0.0),
+			lod_levels: vec![vec![(0, 1, 2)]],
+			materials: vec![Material {
+				name: material.to_string(),
+				texture: 0,
+				triangles: vec![TriangleSelection { offset: 0, len: 1 }],
+				vertex_offset: 0,
+				vertex_count: 3,
+				texture_name: texture_name.to_string()
+			}],
+			tag_points: vec![tag_point.to_string()],
+			frames: vec![Frame::from_vertices(vertices, vec![Point3::new(offset, 2.0, 3.0)], Point3::new(offset, 0.0, 0.0))]
+		}
+	}
+
+	#[test]
+	fn export_and_import_keep_materials_and_tag_points() {
+		let mut scene = Scene::root(model("stone", "textures\\stone.tga", "weapon", 0.0));
+		scene.children.push(Scene::single("child".to_string(), model("wood", "wood.tga", "fire", 10.0)));
+
+		let export = Export::new(&scene, &ExportOptions::default());
+		let mut obj = Vec::new();
+		let mut mtl = Vec::new();
+
+		export.write_obj(&mut obj, 0, Some("scene.mtl")).unwrap();
+		export.write_mtl(&mut mtl).unwrap();
+
+		let imported = import(&obj, |name| {
+			assert_eq!(name, "scene.mtl");
+			Ok(mtl.clone())
+		}, &ImportOptions::default()).unwrap();
+
+		let names: Vec<_> = imported.materials.iter().map(|material| (material.name.as_str(), material.texture_name.as_str())).collect();
+		assert_eq!(names, [("stone", "textures/stone.tga"), ("wood", "wood.tga")]);
+		assert_eq!(imported.lod_levels, [vec![(0, 1, 2), (3, 4, 5)]]);
+
+		let frame = &imported.frames[0];
+		let positions: Vec<_> = frame.vertices.iter().map(|vertex| vertex.position).collect();
+		let texcoords: Vec<_> = frame.vertices.iter().map(|vertex| vertex.texture).collect();
+
+		assert_eq!(positions[3], Point3::new(10.0, 0.0, -1.0));
+		assert_eq!(positions[5], Point3::new(12.0, 1.0, -1.0));
+		assert_eq!(texcoords[2], Point2::new(0.5, 0.5));
+
+		assert_eq!(imported.tag_points, ["weapon", "fire"]);
+		assert_eq!(frame.tag_points, [Point3::new(0.0, 2.0, 3.0), Point3::new(10.0, 2.0, 3.0)]);
+	}
+}
@@ -0,0 +1,178 @@
+//! Every vertex of the frame is written with its position, normal and texture coordinates as the properties
+//! `x y z nx ny nz s t`, followed by the triangles that the materials select from the chosen LOD level.
+//! PLY has no materials, so faces from all materials are written into a single list.
+
+use std::io::{self, Write};
+use byteorder::{WriteBytesExt, LittleEndian};
+use cgmath::{Matrix4, SquareMatrix, Matrix, InnerSpace, Transform};
+use v2::V2;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+	Ascii,
+	BinaryLittleEndian
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ExportOptions {
+	pub format: Format,
+	/// LOD level to write. Models with fewer levels use their last one.
+	pub lod: usize,
+	/// Apply the frame transform to positions and normals, so that the geometry appears as it does in game.
+	pub apply_transform: bool,
+	/// Write V as `1 - v`.
+	pub flip_v: bool
+}
+
+impl Default for ExportOptions {
+	fn default() -> Self {
+		ExportOptions {
+			format: Format::BinaryLittleEndian,
+			lod: 0,
+			apply_transform: true,
+			flip_v: true
+		}
+	}
+}
+
+/// Writes one frame of a model as a PLY file.
+pub fn export<W>(w: &mut W, model: &V2, frame: usize, options: &ExportOptions) -> io::Result<()> where W: Write {
+	let frame = model.frames.get(frame).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("The model has no frame {}, only {}", frame, model.frames.len())))?;
+
+	let lod = options.lod.min(model.lod_levels.len().saturating_sub(1));
+	let triangles: Vec<_> = model.materials.iter().flat_map(|material| model.selection(material, lod)).collect();
+
+	if let Some(triangle) = triangles.iter().find(|t| t.0 as usize >= frame.vertices.len() || t.1 as usize >= frame.vertices.len() || t.2 as usize >= frame.vertices.len()) {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Triangle {:?} refers to a vertex that does not exist", triangle)));
+	}
+
+	let (transform, normal_transform) = if options.apply_transform {
+		// Normals are transformed by the inverse transpose, so that they stay perpendicular under non-uniform scale.
+		(frame.transform, frame.transform.invert().unwrap_or_else(Matrix4::identity).transpose())
+	} else {
+		(Matrix4::identity(), Matrix4::identity())
+	};
+
+	writeln!(w, "ply")?;
+	writeln!(w, "format {} 1.0", match options.format {
+		Format::Ascii => "ascii",
+		Format::BinaryLittleEndian => "binary_little_endian"
+	})?;
+	writeln!(w, "comment Empire Earth model")?;
+	writeln!(w, "element vertex {}", frame.vertices.len())?;
+
+	for property in &["x", "y", "z", "nx", "ny", "nz", "s", "t"] {
+		writeln!(w, "property float {}", property)?;
+	}
+
+	writeln!(w, "element face {}", triangles.len())?;
+	writeln!(w, "property list uchar uint vertex_indices")?;
+	writeln!(w, "end_header")?;
+
+	for vertex in &frame.vertices {
+		let p = transform.transform_point(vertex.position);
+		let mut n = normal_transform.transform_vector(vertex.normal);
+
+		if options.apply_transform && n.magnitude2() > 0.0 {
+			n = n.normalize();
+		}
+
+		let v = if options.flip_v { 1.0 - vertex.texture.y } else { vertex.texture.y };
+		let values = [p.x, p.y, p.z, n.x, n.y, n.z, vertex.texture.x, v];
+
+		match options.format {
+			Format::Ascii => writeln!(w, "{} {} {} {} {} {} {} {}", values[0], values[1], values[2], values[3], values[4], values[5], values[6], values[7])?,
+			Format::BinaryLittleEndian => for &value in &values {
+				w.write_f32::<LittleEndian>(value)?;
+			}
+		}
+	}
+
+	for triangle in triangles {
+		match options.format {
+			Format::Ascii => writeln!(w, "3 {} {} {}", triangle.0, triangle.1, triangle.2)?,
+			Format::BinaryLittleEndian => {
+				w.write_u8(3)?;
+				w.write_u32::<LittleEndian>(triangle.0)?;
+				w.write_u32::<LittleEndian>(triangle.1)?;
+				w.write_u32::<LittleEndian>(triangle.2)?;
+			}
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use byteorder::{ReadBytesExt, LittleEndian};
+	use cgmath::{Point2, Point3, Vector3};
+	use v2::{V2, Frame, Material, TriangleSelection, Vertex};
+	use super::{Format, ExportOptions, export};
+
+	fn triangle() -> V2 {
+		let vertices = (0..3).map(|i| Vertex {
+			position: Point3::new(i as f32, 2.0, -1.0),
+			normal: Vector3::new(0.0, 0.0, 1.0),
+			texture: Point2::new(0.5, 0.25 * i as f32)
+		}).collect();
+
+		V2 {
+			center: Point3::new(1.0, 2.0, -1.0),
+			lod_levels: vec![vec![(0, 1, 2)]],
+			materials: vec![Material {
+				name: "stone".to_string(),
+				texture: 0,
+				triangles: vec![TriangleSelection { offset: 0, len: 1 }],
+				vertex_offset: 0,
+				vertex_count: 3,
+				texture_name: String::new()
+			}],
+			tag_points: Vec::new(),
+			frames: vec![Frame::from_vertices(vertices, Vec::new(), Point3::new(1.0, 2.0, -1.0))]
+		}
+	}
+
+	#[test]
+	fn ascii_lists_vertices_and_faces() {
+		let mut out = Vec::new();
+		export(&mut out, &triangle(), 0, &ExportOptions { format: Format::Ascii, ..ExportOptions::default() }).unwrap();
+
+		let text = String::from_utf8(out).unwrap();
+		let body: Vec<_> = text.split("end_header\n").nth(1).unwrap().lines().collect();
+
+		assert!(text.starts_with("ply\nformat ascii 1.0\n"));
+		assert!(text.contains("element vertex 3\n"));
+		assert!(text.contains("element face 1\n"));
+		assert_eq!(body, ["0 2 -1 0 0 1 0.5 1", "1 2 -1 0 0 1 0.5 0.75", "2 2 -1 0 0 1 0.5 0.5", "3 0 1 2"]);
+	}
+
+	#[test]
+	fn binary_writes_little_endian_values() {
+		let mut out = Vec::new();
+		export(&mut out, &triangle(), 0, &ExportOptions::default()).unwrap();
+
+		let header = b"end_header\n";
+		let start = out.windows(header.len()).position(|window| window == header).unwrap() + header.len();
+		let mut body = &out[start..];
+
+		assert!(out.starts_with(b"ply\nformat binary_little_endian 1.0\n"));
+		assert_eq!(body.len(), 3 * 8 * 4 + 1 + 3 * 4);
+
+		let first: Vec<f32> = (0..8).map(|_| body.read_f32::<LittleEndian>().unwrap()).collect();
+		assert_eq!(first, [0.0, 2.0, -1.0, 0.0, 0.0, 1.0, 0.5, 1.0]);
+
+		let mut faces = &body[2 * 8 * 4..];
+		assert_eq!(faces.read_u8().unwrap(), 3);
+		assert_eq!((0..3).map(|_| faces.read_u32::<LittleEndian>().unwrap()).collect::<Vec<_>>(), [0, 1, 2]);
+	}
+
+	#[test]
+	fn missing_frames_and_vertices_are_errors() {
+		let mut model = triangle();
+		assert!(export(&mut Vec::new(), &model, 1, &ExportOptions::default()).is_err());
+
+		model.lod_levels[0][0].2 = 3;
+		assert!(export(&mut Vec::new(), &model, 0, &ExportOptions::default()).is_err());
+	}
+}
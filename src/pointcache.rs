@@ -0,0 +1,332 @@
+//! PC2 stores little endian samples along with a start frame and the number of frames between samples.
+//! MDD stores big endian samples along with the time of each sample in seconds, which are converted using
+//! a frame rate. Neither format has an axis convention, so positions are written as they are.
+//!
+//! Points are in the same order as the vertices of a frame, which is also the order used by the OBJ export.
+
+use std::io::{self, Read, Write};
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian, BigEndian, ByteOrder};
+use cgmath::{Point3, Vector3, Matrix4, SquareMatrix, InnerSpace, Transform};
+use v2::{V2, Frame, Vertex};
+
+const PC2_MAGIC: &[u8; 12] = b"POINTCACHE2\0";
+
+/// Point counts above this are treated as corrupt.
+const MAX_PLAUSIBLE: u32 = 1 << 24;
+
+/// Positions of every point for a sequence of samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointCache {
+	/// Frame number of the first sample.
+	pub start_frame: f32,
+	/// Frames between samples.
+	pub sample_rate: f32,
+	pub samples: Vec<Vec<Point3<f32>>>
+}
+
+impl PointCache {
+	/// Takes the vertex positions of every frame. If `apply_transform` is set, positions include the frame transform.
+	pub fn from_model(model: &V2, apply_transform: bool) -> Self {
+		PointCache {
+			start_frame: 0.0,
+			sample_rate: 1.0,
+			samples: model.frames.iter().map(|frame| {
+				let transform = if apply_transform { frame.transform } else { Matrix4::identity() };
+
+				frame.vertices.iter().map(|vertex| transform.transform_point(vertex.position)).collect()
+			}).collect()
+		}
+	}
+
+	/// The number of points in each sample.
+	pub fn points(&self) -> usize {
+		self.samples.first().map(Vec::len).unwrap_or(0)
+	}
+
+	/// The number of points in each sample, failing if the samples disagree, since both formats store it once.
+	fn uniform_points(&self) -> io::Result<u32> {
+		let points = self.points();
+
+		match self.samples.iter().position(|sample| sample.len() != points) {
+			Some(i) => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Sample {} has {} points, but the first has {}", i, self.samples[i].len(), points))),
+			None => Ok(points as u32)
+		}
+	}
+
+	/// Replaces the frames of a model with one frame per sample. The topology must match: each sample needs one point per vertex.
+	///
+	/// `includes_transform` says whether the positions include the frame transform, as with `from_model`. If they do, every
+	/// frame gets the identity transform and tag points from the existing frame with the same index, or the last one, with
+	/// its transform applied. Otherwise both the transform and the tag points are kept as they are in that frame. Normals are
+	/// recomputed from the triangles of the first LOD level, while texture coordinates come from the first frame. Colliders
+	/// are recomputed.
+	pub fn apply_to(&self, model: &mut V2, includes_transform: bool) -> io::Result<()> {
+		let base = match model.frames.first() {
+			Some(frame) => frame.vertices.clone(),
+			None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "The model has no frame to take the topology from"))
+		};
+
+		if self.samples.is_empty() {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, "The point cache has no samples"));
+		}
+
+		if let Some(sample) = self.samples.iter().find(|sample| sample.len() != base.len()) {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The point cache has {} points, but the model has {} vertices", sample.len(), base.len())));
+		}
+
+		let triangles = model.lod_levels.first().map(Vec::as_slice).unwrap_or(&[]);
+
+		let frames = self.samples.iter().enumerate().map(|(i, sample)| {
+			let existing = &model.frames[i.min(model.frames.len() - 1)];
+			let (transform, tag_points) = if includes_transform {
+				(Matrix4::identity(), existing.tag_points.iter().map(|&point| existing.transform.transform_point(point)).collect())
+			} else {
+				(existing.transform, existing.tag_points.clone())
+			};
+
+			let mut vertices: Vec<Vertex> = base.iter().zip(sample).map(|(vertex, &position)| Vertex { position, ..*vertex }).collect();
+			let mut normals = vec![Vector3::new(0.0, 0.0, 0.0); vertices.len()];
+
+			for triangle in triangles {
+				let (a, b, c) = (triangle.0 as usize, triangle.1 as usize, triangle.2 as usize);
+
+				if a >= vertices.len() || b >= vertices.len() || c >= vertices.len() {
+					continue;
+				}
+
+				let face = (vertices[b].position - vertices[a].position).cross(vertices[c].position - vertices[a].position);
+
+				normals[a] += face;
+				normals[b] += face;
+				normals[c] += face;
+			}
+
+			// Vertices outside of any triangle keep their normal.
+			for (vertex, normal) in vertices.iter_mut().zip(normals) {
+				if normal.magnitude2() > 0.0 {
+					vertex.normal = normal.normalize();
+				}
+			}
+
+			let mut frame = Frame::from_vertices(vertices, tag_points, model.center);
+			frame.transform = transform;
+			frame
+		}).collect();
+
+		model.frames = frames;
+
+		Ok(())
+	}
+
+	pub fn read_pc2<R>(r: &mut R) -> io::Result<Self> where R: Read {
+		let mut magic = [0; 12];
+		r.read_exact(&mut magic)?;
+
+		if &magic != PC2_MAGIC {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a PC2 file: missing the POINTCACHE2 signature"));
+		}
+
+		let version = r.read_u32::<LittleEndian>()?;
+
+		if version != 1 {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unsupported PC2 version {}", version)));
+		}
+
+		let points = r.read_u32::<LittleEndian>()?;
+		let start_frame = r.read_f32::<LittleEndian>()?;
+		let sample_rate = r.read_f32::<LittleEndian>()?;
+		let samples = r.read_u32::<LittleEndian>()?;
+
+		check_counts(samples, points)?;
+
+		Ok(PointCache {
+			start_frame,
+			sample_rate,
+			samples: read_samples::<R, LittleEndian>(r, samples, points)?
+		})
+	}
+
+	pub fn write_pc2<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
+		let points = self.uniform_points()?;
+
+		w.write_all(PC2_MAGIC)?;
+		w.write_u32::<LittleEndian>(1)?;
+		w.write_u32::<LittleEndian>(points)?;
+		w.write_f32::<LittleEndian>(self.start_frame)?;
+		w.write_f32::<LittleEndian>(self.sample_rate)?;
+		w.write_u32::<LittleEndian>(self.samples.len() as u32)?;
+
+		write_samples::<W, LittleEndian>(w, &self.samples)
+	}
+
+	/// Reads an MDD file, converting sample times to frames with `frame_rate`.
+	pub fn read_mdd<R>(r: &mut R, frame_rate: f32) -> io::Result<Self> where R: Read {
+		let samples = r.read_u32::<BigEndian>()?;
+		let points = r.read_u32::<BigEndian>()?;
+
+		check_counts(samples, points)?;
+
+		let mut times = Vec::with_capacity(samples as usize);
+		for _ in 0..samples {
+			times.push(r.read_f32::<BigEndian>()?);
+		}
+
+		let start_frame = times.first().map(|&time| time * frame_rate).unwrap_or(0.0);
+		let sample_rate = if times.len() > 1 { (times[1] - times[0]) * frame_rate } else { 1.0 };
+
+		Ok(PointCache {
+			start_frame,
+			sample_rate,
+			samples: read_samples::<R, BigEndian>(r, samples, points)?
+		})
+	}
+
+	/// Writes an MDD file, converting frames to sample times with `frame_rate`.
+	pub fn write_mdd<W>(&self, w: &mut W, frame_rate: f32) -> io::Result<()> where W: Write {
+		let points = self.uniform_points()?;
+
+		w.write_u32::<BigEndian>(self.samples.len() as u32)?;
+		w.write_u32::<BigEndian>(points)?;
+
+		for i in 0..self.samples.len() {
+			w.write_f32::<BigEndian>((self.start_frame + i as f32 * self.sample_rate) / frame_rate)?;
+		}
+
+		write_samples::<W, BigEndian>(w, &self.samples)
+	}
+}
+
+/// Rejects counts that would take far more memory or time than the file could justify. Samples without points take
+/// no space at all, so any number of them could be claimed by a tiny file.
+fn check_counts(samples: u32, points: u32) -> io::Result<()> {
+	if samples > MAX_PLAUSIBLE || points > MAX_PLAUSIBLE {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Implausible point cache with {} samples of {} points", samples, points)));
+	}
+
+	if points == 0 && samples > 0 {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, format!("The point cache has {} samples, but no points", samples)));
+	}
+
+	Ok(())
+}
+
+fn read_samples<R, B>(r: &mut R, samples: u32, points: u32) -> io::Result<Vec<Vec<Point3<f32>>>> where R: Read, B: ByteOrder {
+	let mut read = Vec::with_capacity((samples as usize).min(4096));
+
+	for _ in 0..samples {
+		let mut sample = Vec::with_capacity((points as usize).min(4096));
+
+		for _ in 0..points {
+			sample.push(Point3::new(r.read_f32::<B>()?, r.read_f32::<B>()?, r.read_f32::<B>()?));
+		}
+
+		read.push(sample);
+	}
+
+	Ok(read)
+}
+
+fn write_samples<W, B>(w: &mut W, samples: &[Vec<Point3<f32>>]) -> io::Result<()> where W: Write, B: ByteOrder {
+	for sample in samples {
+		for point in sample {
+			w.write_f32::<B>(point.x)?;
+			w.write_f32::<B>(point.y)?;
+			w.write_f32::<B>(point.z)?;
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+	use cgmath::{Point3, Vector3, Matrix4, Transform};
+	use samples::v2_scene;
+	use super::PointCache;
+
+	fn cache() -> PointCache {
+		PointCache {
+			start_frame: 2.0,
+			sample_rate: 0.5,
+			samples: vec![
+				vec![Point3::new(0.0, 1.0, 2.0), Point3::new(-3.5, 4.0, 1e-3)],
+				vec![Point3::new(1.0, 1.0, 1.0), Point3::new(0.0, 0.0, 0.0)]
+			]
+		}
+	}
+
+	#[test]
+	fn pc2_round_trip() {
+		let mut data = Vec::new();
+		cache().write_pc2(&mut data).unwrap();
+
+		assert_eq!(PointCache::read_pc2(&mut Cursor::new(data)).unwrap(), cache());
+	}
+
+	#[test]
+	fn mdd_round_trip() {
+		// Sample times are stored in seconds, so a frame rate that is a power of two keeps them exact.
+		let mut data = Vec::new();
+		cache().write_mdd(&mut data, 32.0).unwrap();
+
+		assert_eq!(PointCache::read_mdd(&mut Cursor::new(data), 32.0).unwrap(), cache());
+	}
+
+	#[test]
+	fn ragged_samples_are_not_written() {
+		let mut cache = cache();
+		cache.samples[1].pop();
+
+		assert!(cache.write_pc2(&mut Vec::new()).is_err());
+		assert!(cache.write_mdd(&mut Vec::new(), 30.0).is_err());
+	}
+
+	#[test]
+	fn samples_without_points_are_rejected() {
+		let mut data = Vec::new();
+		PointCache { start_frame: 0.0, sample_rate: 1.0, samples: Vec::new() }.write_pc2(&mut data).unwrap();
+
+		// The sample count is the last field of the header.
+		let at = data.len() - 4;
+		data[at..].copy_from_slice(&[0xff; 4]);
+
+		assert!(PointCache::read_pc2(&mut Cursor::new(data)).is_err());
+	}
+
+	#[test]
+	fn local_positions_keep_the_transform() {
+		let mut model = v2_scene().model;
+		let transform = Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0));
+		model.frames[0].transform = transform;
+
+		let cache = PointCache::from_model(&model, false);
+		let original: Vec<_> = model.frames[0].vertices.iter().map(|vertex| vertex.position).collect();
+		let tag_points = model.frames[0].tag_points.clone();
+
+		cache.apply_to(&mut model, false).unwrap();
+
+		let frame = &model.frames[0];
+		assert_eq!(frame.transform, transform);
+		assert_eq!(frame.tag_points, tag_points);
+		assert_eq!(frame.vertices.iter().map(|vertex| vertex.position).collect::<Vec<_>>(), original);
+	}
+
+	#[test]
+	fn transformed_positions_bake_the_transform() {
+		let mut model = v2_scene().model;
+		let transform = Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0));
+		model.frames[0].transform = transform;
+
+		let cache = PointCache::from_model(&model, true);
+		let expected: Vec<_> = model.frames[0].vertices.iter().map(|vertex| transform.transform_point(vertex.position)).collect();
+		let tag_point = transform.transform_point(model.frames[0].tag_points[0]);
+
+		cache.apply_to(&mut model, true).unwrap();
+
+		let frame = &model.frames[0];
+		assert_eq!(frame.transform, Matrix4::from_scale(1.0));
+		assert_eq!(frame.tag_points, vec![tag_point]);
+		assert_eq!(frame.vertices.iter().map(|vertex| vertex.position).collect::<Vec<_>>(), expected);
+	}
+}
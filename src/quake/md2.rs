@@ -0,0 +1,481 @@
+//! MD2 has neither named materials nor tags. Every material is written into one triangle list, the texture names
+//! become the skins, and tag points are dropped. On import, the whole model becomes a single material named after
+//! the first skin. Texture coordinates are stored as whole texels of the skin size given in the options.
+
+use std::io::{self, Read, Write, Cursor};
+use std::collections::HashMap;
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
+use cgmath::{Point2, Point3, Vector3, InnerSpace};
+use v2::{V2, Material, TriangleSelection, Vertex};
+use super::{baked, model, read_name, write_name, invalid};
+
+const MAGIC: &[u8; 4] = b"IDP2";
+const VERSION: i32 = 8;
+
+const HEADER_SIZE: usize = 68;
+const SKIN_SIZE: usize = 64;
+const FRAME_HEADER_SIZE: usize = 40;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ExportOptions {
+	/// Size of the skin in texels, used to store texture coordinates.
+	pub skin_width: u32,
+	pub skin_height: u32
+}
+
+impl Default for ExportOptions {
+	fn default() -> Self {
+		ExportOptions {
+			skin_width: 256,
+			skin_height: 256
+		}
+	}
+}
+
+/// Reads an MD2 model. Each unique pair of position and texture coordinate becomes a vertex.
+pub fn read<R>(r: &mut R) -> io::Result<V2> where R: Read {
+	let mut data = Vec::new();
+	r.read_to_end(&mut data)?;
+
+	let mut c = Cursor::new(&data[..]);
+
+	let mut magic = [0; 4];
+	c.read_exact(&mut magic)?;
+
+	if &magic != MAGIC {
+		return invalid("Not an MD2 file: missing the IDP2 signature".to_string());
+	}
+
+	let version = c.read_i32::<LittleEndian>()?;
+
+	if version != VERSION {
+		return invalid(format!("Unsupported MD2 version {}, expected {}", version, VERSION));
+	}
+
+	let mut header = [0; 15];
+	c.read_i32_into::<LittleEndian>(&mut header)?;
+
+	if let Some(&value) = header.iter().find(|&&value| value < 0 || value as usize > data.len()) {
+		return invalid(format!("Implausible value in the MD2 header: {}", value));
+	}
+
+	let header: Vec<usize> = header.iter().map(|&value| value as usize).collect();
+	let (skin_width, skin_height, frame_size) = (header[0], header[1], header[2]);
+	let (skin_count, position_count, st_count, triangle_count, _, frame_count) = (header[3], header[4], header[5], header[6], header[7], header[8]);
+	let (skins_offset, st_offset, triangles_offset, frames_offset) = (header[9], header[10], header[11], header[12]);
+
+	if frame_size < FRAME_HEADER_SIZE + 4 * position_count {
+		return invalid(format!("MD2 frames of {} bytes cannot hold {} vertices", frame_size, position_count));
+	}
+
+	c.set_position(skins_offset as u64);
+
+	let mut skins = Vec::with_capacity(skin_count);
+	for _ in 0..skin_count {
+		let mut skin = [0; SKIN_SIZE];
+		c.read_exact(&mut skin)?;
+		skins.push(read_name(&skin));
+	}
+
+	c.set_position(st_offset as u64);
+
+	let mut texcoords = Vec::with_capacity(st_count);
+	for _ in 0..st_count {
+		let s = c.read_i16::<LittleEndian>()? as f32;
+		let t = c.read_i16::<LittleEndian>()? as f32;
+
+		texcoords.push(Point2::new(s / skin_width.max(1) as f32, t / skin_height.max(1) as f32));
+	}
+
+	c.set_position(triangles_offset as u64);
+
+	// MD2 indexes positions and texture coordinates separately, while V2 vertices hold both.
+	let mut corners = Vec::new();
+	let mut lookup = HashMap::new();
+	let mut triangles = Vec::with_capacity(triangle_count);
+
+	for _ in 0..triangle_count {
+		let mut indices = [0; 6];
+		c.read_u16_into::<LittleEndian>(&mut indices)?;
+
+		let mut triangle = [0; 3];
+
+		for (i, vertex) in triangle.iter_mut().enumerate() {
+			let corner = (indices[i] as usize, indices[i + 3] as usize);
+
+			if corner.0 >= position_count || corner.1 >= st_count {
+				return invalid(format!("An MD2 triangle refers to vertex {} and texture coordinate {}, which do not exist", corner.0, corner.1));
+			}
+
+			let next = corners.len() as u32;
+			*vertex = *lookup.entry(corner).or_insert(next);
+
+			if *vertex == next {
+				corners.push(corner);
+			}
+		}
+
+		triangles.push((triangle[0], triangle[1], triangle[2]));
+	}
+
+	let mut frames = Vec::with_capacity(frame_count);
+
+	for frame in 0..frame_count {
+		c.set_position((frames_offset + frame * frame_size) as u64);
+
+		let scale = Vector3::new(c.read_f32::<LittleEndian>()?, c.read_f32::<LittleEndian>()?, c.read_f32::<LittleEndian>()?);
+		let translate = Vector3::new(c.read_f32::<LittleEndian>()?, c.read_f32::<LittleEndian>()?, c.read_f32::<LittleEndian>()?);
+
+		let mut name = [0; 16];
+		c.read_exact(&mut name)?;
+
+		let mut packed = vec![0; 4 * position_count];
+		c.read_exact(&mut packed)?;
+
+		let vertices = corners.iter().map(|&(position, st)| {
+			let packed = &packed[4 * position..4 * position + 4];
+			let normal = ANORMS.get(packed[3] as usize).cloned().unwrap_or([0.0, 0.0, 1.0]);
+
+			Vertex {
+				position: Point3::new(
+					packed[0] as f32 * scale.x + translate.x,
+					packed[1] as f32 * scale.y + translate.y,
+					packed[2] as f32 * scale.z + translate.z
+				),
+				normal: Vector3::from(normal),
+				texture: texcoords[st]
+			}
+		}).collect();
+
+		frames.push((vertices, Vec::new()));
+	}
+
+	if triangles.is_empty() || frames.is_empty() {
+		return invalid("The MD2 file has no triangles or no frames".to_string());
+	}
+
+	let texture_name = skins.first().cloned().unwrap_or_default();
+
+	let material = Material {
+		name: if texture_name.is_empty() { "default".to_string() } else { texture_name.clone() },
+		texture: 0,
+		triangles: vec![TriangleSelection { offset: 0, len: triangles.len() as u32 }],
+		vertex_offset: 0,
+		vertex_count: corners.len() as u32,
+		texture_name
+	};
+
+	Ok(model(triangles, vec![material], Vec::new(), frames))
+}
+
+/// Writes the first LOD level of a model as an MD2 file. Each frame is quantized to 8 bits per axis within its own bounds.
+pub fn write<W>(w: &mut W, model: &V2, options: &ExportOptions) -> io::Result<()> where W: Write {
+	let frames: Vec<Vec<Vertex>> = model.frames.iter().map(|frame| baked(frame).0).collect();
+	let vertex_count = frames.first().map(Vec::len).unwrap_or(0);
+
+	if frames.iter().any(|frame| frame.len() != vertex_count) {
+		return invalid("Every frame needs the same number of vertices to be written as MD2".to_string());
+	}
+
+	if vertex_count > u16::MAX as usize {
+		return invalid(format!("The model has {} vertices, but MD2 can store at most {}", vertex_count, u16::MAX));
+	}
+
+	let mut triangles = Vec::new();
+
+	for material in &model.materials {
+		for triangle in model.selection(material, 0) {
+			if [triangle.0, triangle.1, triangle.2].iter().any(|&vertex| vertex as usize >= vertex_count) {
+				return invalid(format!("Material '{}' refers to a vertex that does not exist", material.name));
+			}
+
+			triangles.push(*triangle);
+		}
+	}
+
+	let mut skins: Vec<&str> = Vec::new();
+
+	for material in &model.materials {
+		if !material.texture_name.is_empty() && !skins.contains(&material.texture_name.as_str()) {
+			skins.push(&material.texture_name);
+		}
+	}
+
+	let frame_size = FRAME_HEADER_SIZE + 4 * vertex_count;
+	// Each triangle is a strip of 3 vertices, with a count and 3 values per vertex, followed by a terminating 0.
+	let glcmd_count = 10 * triangles.len() + 1;
+
+	let skins_offset = HEADER_SIZE;
+	let st_offset = skins_offset + SKIN_SIZE * skins.len();
+	let triangles_offset = st_offset + 4 * vertex_count;
+	let frames_offset = triangles_offset + 12 * triangles.len();
+	let glcmds_offset = frames_offset + frame_size * frames.len();
+	let end = glcmds_offset + 4 * glcmd_count;
+
+	w.write_all(MAGIC)?;
+
+	for &value in &[
+		VERSION,
+		options.skin_width as i32,
+		options.skin_height as i32,
+		frame_size as i32,
+		skins.len() as i32,
+		vertex_count as i32,
+		vertex_count as i32,
+		triangles.len() as i32,
+		glcmd_count as i32,
+		frames.len() as i32,
+		skins_offset as i32,
+		st_offset as i32,
+		triangles_offset as i32,
+		frames_offset as i32,
+		glcmds_offset as i32,
+		end as i32
+	] {
+		w.write_i32::<LittleEndian>(value)?;
+	}
+
+	for skin in &skins {
+		write_name(w, skin, SKIN_SIZE)?;
+	}
+
+	let texcoords: Vec<(i16, i16)> = frames.first().map(|frame| frame.iter().map(|vertex| (
+		(vertex.texture.x * options.skin_width as f32).round() as i16,
+		(vertex.texture.y * options.skin_height as f32).round() as i16
+	)).collect()).unwrap_or_default();
+
+	for &(s, t) in &texcoords {
+		w.write_i16::<LittleEndian>(s)?;
+		w.write_i16::<LittleEndian>(t)?;
+	}
+
+	for triangle in &triangles {
+		// Positions and texture coordinates share indices.
+		for _ in 0..2 {
+			w.write_u16::<LittleEndian>(triangle.0 as u16)?;
+			w.write_u16::<LittleEndian>(triangle.1 as u16)?;
+			w.write_u16::<LittleEndian>(triangle.2 as u16)?;
+		}
+	}
+
+	for (i, frame) in frames.iter().enumerate() {
+		let mut lower = frame.first().map(|vertex| vertex.position).unwrap_or_else(|| Point3::new(0.0, 0.0, 0.0));
+		let mut upper = lower;
+
+		for vertex in frame {
+			let p = vertex.position;
+
+			lower = Point3::new(lower.x.min(p.x), lower.y.min(p.y), lower.z.min(p.z));
+			upper = Point3::new(upper.x.max(p.x), upper.y.max(p.y), upper.z.max(p.z));
+		}
+
+		let scale = (upper - lower) / 255.0;
+
+		for c in &[scale.x, scale.y, scale.z, lower.x, lower.y, lower.z] {
+			w.write_f32::<LittleEndian>(*c)?;
+		}
+
+		write_name(w, &format!("frame{}", i), 16)?;
+
+		for vertex in frame {
+			let offset = vertex.position - lower;
+
+			for &(offset, scale) in &[(offset.x, scale.x), (offset.y, scale.y), (offset.z, scale.z)] {
+				w.write_u8(if scale > 0.0 { (offset / scale).round().clamp(0.0, 255.0) as u8 } else { 0 })?;
+			}
+
+			w.write_u8(normal_index(vertex.normal))?;
+		}
+	}
+
+	for triangle in &triangles {
+		w.write_i32::<LittleEndian>(3)?;
+
+		for &vertex in &[triangle.0, triangle.1, triangle.2] {
+			let texture = frames[0][vertex as usize].texture;
+
+			w.write_f32::<LittleEndian>(texture.x)?;
+			w.write_f32::<LittleEndian>(texture.y)?;
+			w.write_i32::<LittleEndian>(vertex as i32)?;
+		}
+	}
+
+	w.write_i32::<LittleEndian>(0)
+}
+
+/// The index of the closest entry in the normal table.
+fn normal_index(normal: Vector3<f32>) -> u8 {
+	let normal = if normal.magnitude2() > 0.0 { normal.normalize() } else { Vector3::new(0.0, 0.0, 1.0) };
+
+	ANORMS.iter()
+		.map(|&entry| Vector3::from(entry).dot(normal))
+		.enumerate()
+		.fold((0, f32::MIN), |best, (i, dot)| if dot > best.1 { (i, dot) } else { best })
+		.0 as u8
+}
+
+/// The fixed table of normals that MD2 vertices refer to, from `anorms.h` of Quake II.
+const ANORMS: [[f32; 3]; 162] = [
+	[-0.525731, 0.000000, 0.850651],
+	[-0.442863, 0.238856, 0.864188],
+	[-0.295242, 0.000000, 0.955423],
+	[-0.309017, 0.500000, 0.809017],
+	[-0.162460, 0.262866, 0.951056],
+	[0.000000, 0.000000, 1.000000],
+	[0.000000, 0.850651, 0.525731],
+	[-0.147621, 0.716567, 0.681718],
+	[0.147621, 0.716567, 0.681718],
+	[0.000000, 0.525731, 0.850651],
+	[0.309017, 0.500000, 0.809017],
+	[0.525731, 0.000000, 0.850651],
+	[0.295242, 0.000000, 0.955423],
+	[0.442863, 0.238856, 0.864188],
+	[0.162460, 0.262866, 0.951056],
+	[-0.681718, 0.147621, 0.716567],
+	[-0.809017, 0.309017, 0.500000],
+	[-0.587785, 0.425325, 0.688191],
+	[-0.850651, 0.525731, 0.000000],
+	[-0.864188, 0.442863, 0.238856],
+	[-0.716567, 0.681718, 0.147621],
+	[-0.688191, 0.587785, 0.425325],
+	[-0.500000, 0.809017, 0.309017],
+	[-0.238856, 0.864188, 0.442863],
+	[-0.425325, 0.688191, 0.587785],
+	[-0.716567, 0.681718, -0.147621],
+	[-0.500000, 0.809017, -0.309017],
+	[-0.525731, 0.850651, 0.000000],
+	[0.000000, 0.850651, -0.525731],
+	[-0.238856, 0.864188, -0.442863],
+	[0.000000, 0.955423, -0.295242],
+	[-0.262866, 0.951056, -0.162460],
+	[0.000000, 1.000000, 0.000000],
+	[0.000000, 0.955423, 0.295242],
+	[-0.262866, 0.951056, 0.162460],
+	[0.238856, 0.864188, 0.442863],
+	[0.262866, 0.951056, 0.162460],
+	[0.500000, 0.809017, 0.309017],
+	[0.238856, 0.864188, -0.442863],
+	[0.262866, 0.951056, -0.162460],
+	[0.500000, 0.809017, -0.309017],
+	[0.850651, 0.525731, 0.000000],
+	[0.716567, 0.681718, 0.147621],
+	[0.716567, 0.681718, -0.147621],
+	[0.525731, 0.850651, 0.000000],
+	[0.425325, 0.688191, 0.587785],
+	[0.864188, 0.442863, 0.238856],
+	[0.688191, 0.587785, 0.425325],
+	[0.809017, 0.309017, 0.500000],
+	[0.681718, 0.147621, 0.716567],
+	[0.587785, 0.425325, 0.688191],
+	[0.955423, 0.295242, 0.000000],
+	[1.000000, 0.000000, 0.000000],
+	[0.951056, 0.162460, 0.262866],
+	[0.850651, -0.525731, 0.000000],
+	[0.955423, -0.295242, 0.000000],
+	[0.864188, -0.442863, 0.238856],
+	[0.951056, -0.162460, 0.262866],
+	[0.809017, -0.309017, 0.500000],
+	[0.681718, -0.147621, 0.716567],
+	[0.850651, 0.000000, 0.525731],
+	[0.864188, 0.442863, -0.238856],
+	[0.809017, 0.309017, -0.500000],
+	[0.951056, 0.162460, -0.262866],
+	[0.525731, 0.000000, -0.850651],
+	[0.681718, 0.147621, -0.716567],
+	[0.681718, -0.147621, -0.716567],
+	[0.850651, 0.000000, -0.525731],
+	[0.809017, -0.309017, -0.500000],
+	[0.864188, -0.442863, -0.238856],
+	[0.951056, -0.162460, -0.262866],
+	[0.147621, 0.716567, -0.681718],
+	[0.309017, 0.500000, -0.809017],
+	[0.425325, 0.688191, -0.587785],
+	[0.442863, 0.238856, -0.864188],
+	[0.587785, 0.425325, -0.688191],
+	[0.688191, 0.587785, -0.425325],
+	[-0.147621, 0.716567, -0.681718],
+	[-0.309017, 0.500000, -0.809017],
+	[0.000000, 0.525731, -0.850651],
+	[-0.525731, 0.000000, -0.850651],
+	[-0.442863, 0.238856, -0.864188],
+	[-0.295242, 0.000000, -0.955423],
+	[-0.162460, 0.262866, -0.951056],
+	[0.000000, 0.000000, -1.000000],
+	[0.295242, 0.000000, -0.955423],
+	[0.162460, 0.262866, -0.951056],
+	[-0.442863, -0.238856, -0.864188],
+	[-0.309017, -0.500000, -0.809017],
+	[-0.162460, -0.262866, -0.951056],
+	[0.000000, -0.850651, -0.525731],
+	[-0.147621, -0.716567, -0.681718],
+	[0.147621, -0.716567, -0.681718],
+	[0.000000, -0.525731, -0.850651],
+	[0.309017, -0.500000, -0.809017],
+	[0.442863, -0.238856, -0.864188],
+	[0.162460, -0.262866, -0.951056],
+	[0.238856, -0.864188, -0.442863],
+	[0.500000, -0.809017, -0.309017],
+	[0.425325, -0.688191, -0.587785],
+	[0.716567, -0.681718, -0.147621],
+	[0.688191, -0.587785, -0.425325],
+	[0.587785, -0.425325, -0.688191],
+	[0.000000, -0.955423, -0.295242],
+	[0.000000, -1.000000, 0.000000],
+	[0.262866, -0.951056, -0.162460],
+	[0.000000, -0.850651, 0.525731],
+	[0.000000, -0.955423, 0.295242],
+	[0.238856, -0.864188, 0.442863],
+	[0.262866, -0.951056, 0.162460],
+	[0.500000, -0.809017, 0.309017],
+	[0.716567, -0.681718, 0.147621],
+	[0.525731, -0.850651, 0.000000],
+	[-0.238856, -0.864188, -0.442863],
+	[-0.500000, -0.809017, -0.309017],
+	[-0.262866, -0.951056, -0.162460],
+	[-0.850651, -0.525731, 0.000000],
+	[-0.716567, -0.681718, -0.147621],
+	[-0.716567, -0.681718, 0.147621],
+	[-0.525731, -0.850651, 0.000000],
+	[-0.500000, -0.809017, 0.309017],
+	[-0.238856, -0.864188, 0.442863],
+	[-0.262866, -0.951056, 0.162460],
+	[-0.864188, -0.442863, 0.238856],
+	[-0.809017, -0.309017, 0.500000],
+	[-0.688191, -0.587785, 0.425325],
+	[-0.681718, -0.147621, 0.716567],
+	[-0.442863, -0.238856, 0.864188],
+	[-0.587785, -0.425325, 0.688191],
+	[-0.309017, -0.500000, 0.809017],
+	[-0.147621, -0.716567, 0.681718],
+	[-0.425325, -0.688191, 0.587785],
+	[-0.162460, -0.262866, 0.951056],
+	[0.442863, -0.238856, 0.864188],
+	[0.162460, -0.262866, 0.951056],
+	[0.309017, -0.500000, 0.809017],
+	[0.147621, -0.716567, 0.681718],
+	[0.000000, -0.525731, 0.850651],
+	[0.425325, -0.688191, 0.587785],
+	[0.587785, -0.425325, 0.688191],
+	[0.688191, -0.587785, 0.425325],
+	[-0.955423, 0.295242, 0.000000],
+	[-0.951056, 0.162460, 0.262866],
+	[-1.000000, 0.000000, 0.000000],
+	[-0.850651, 0.000000, 0.525731],
+	[-0.955423, -0.295242, 0.000000],
+	[-0.951056, -0.162460, 0.262866],
+	[-0.864188, 0.442863, -0.238856],
+	[-0.951056, 0.162460, -0.262866],
+	[-0.809017, 0.309017, -0.500000],
+	[-0.864188, -0.442863, -0.238856],
+	[-0.951056, -0.162460, -0.262866],
+	[-0.809017, -0.309017, -0.500000],
+	[-0.681718, 0.147621, -0.716567],
+	[-0.681718, -0.147621, -0.716567],
+	[-0.850651, 0.000000, -0.525731],
+	[-0.688191, 0.587785, -0.425325],
+	[-0.587785, 0.425325, -0.688191],
+	[-0.425325, 0.688191, -0.587785],
+	[-0.425325, -0.688191, -0.587785],
+	[-0.587785, -0.425325, -0.688191],
+	[-0.688191, -0.587785, -0.425325]
+];
@@ -0,0 +1,392 @@
+//! Each material becomes a surface with the texture name as its shader, and each tag point becomes a tag.
+//! Tags are written with an identity orientation, and their orientation is ignored on import.
+//! Only the first LOD level is written.
+
+use std::io::{self, Read, Write, Cursor};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
+use cgmath::{Point2, Point3, Vector3};
+use v2::{V2, Material, TriangleSelection, Vertex};
+use super::{baked, model, read_name, write_name, invalid};
+
+const MAGIC: &[u8; 4] = b"IDP3";
+const VERSION: i32 = 15;
+
+const HEADER_SIZE: usize = 108;
+const FRAME_SIZE: usize = 56;
+const TAG_SIZE: usize = 112;
+const SURFACE_HEADER_SIZE: usize = 108;
+const SHADER_SIZE: usize = 68;
+
+/// Positions are stored as 16-bit integers in units of 1/64.
+const XYZ_SCALE: f32 = 64.0;
+
+/// Reads an MD3 model. Each surface becomes a material with its own range of vertices.
+pub fn read<R>(r: &mut R) -> io::Result<V2> where R: Read {
+	let mut data = Vec::new();
+	r.read_to_end(&mut data)?;
+
+	let mut c = Cursor::new(&data[..]);
+
+	let mut magic = [0; 4];
+	c.read_exact(&mut magic)?;
+
+	if &magic != MAGIC {
+		return invalid("Not an MD3 file: missing the IDP3 signature".to_string());
+	}
+
+	let version = c.read_i32::<LittleEndian>()?;
+
+	if version != VERSION {
+		return invalid(format!("Unsupported MD3 version {}, expected {}", version, VERSION));
+	}
+
+	c.set_position(4 + 4 + 64 + 4);
+
+	let frame_count = count(&mut c, "frames")?;
+
+	if frame_count == 0 {
+		return invalid("The MD3 file has no frames".to_string());
+	}
+
+	let tag_count = count(&mut c, "tags")?;
+	let surface_count = count(&mut c, "surfaces")?;
+	let _skins = c.read_i32::<LittleEndian>()?;
+	let _frames_offset = c.read_i32::<LittleEndian>()?;
+	let tags_offset = count(&mut c, "tag offset")?;
+	let surfaces_offset = count(&mut c, "surface offset")?;
+
+	// Tags are stored frame by frame.
+	let mut tag_names = Vec::with_capacity(tag_count);
+	let mut tag_points = vec![Vec::with_capacity(tag_count); frame_count];
+
+	c.set_position(tags_offset as u64);
+
+	for frame in tag_points.iter_mut() {
+		for tag in 0..tag_count {
+			let mut name = [0; 64];
+			c.read_exact(&mut name)?;
+
+			if tag_names.len() <= tag {
+				tag_names.push(read_name(&name));
+			}
+
+			frame.push(Point3::new(c.read_f32::<LittleEndian>()?, c.read_f32::<LittleEndian>()?, c.read_f32::<LittleEndian>()?));
+
+			let mut axes = [0.0; 9];
+			c.read_f32_into::<LittleEndian>(&mut axes)?;
+		}
+	}
+
+	let mut triangles = Vec::new();
+	let mut materials = Vec::with_capacity(surface_count);
+	let mut vertices = vec![Vec::new(); frame_count];
+	let mut start = surfaces_offset;
+
+	for surface in 0..surface_count {
+		c.set_position(start as u64);
+		c.read_exact(&mut magic)?;
+
+		if &magic != MAGIC {
+			return invalid(format!("Surface {} is missing the IDP3 signature", surface));
+		}
+
+		let mut name = [0; 64];
+		c.read_exact(&mut name)?;
+
+		let _flags = c.read_i32::<LittleEndian>()?;
+		let surface_frames = count(&mut c, "surface frames")?;
+		let shader_count = count(&mut c, "shaders")?;
+		let vertex_count = count(&mut c, "vertices")?;
+		let triangle_count = count(&mut c, "triangles")?;
+		let triangles_offset = count(&mut c, "triangle offset")?;
+		let shaders_offset = count(&mut c, "shader offset")?;
+		let st_offset = count(&mut c, "texture coordinate offset")?;
+		let xyz_offset = count(&mut c, "vertex offset")?;
+		let end = count(&mut c, "surface end")?;
+
+		if surface_frames != frame_count {
+			return invalid(format!("Surface {} has {} frames, but the model has {}", surface, surface_frames, frame_count));
+		}
+
+		let texture_name = if shader_count > 0 {
+			c.set_position((start + shaders_offset) as u64);
+
+			let mut shader = [0; 64];
+			c.read_exact(&mut shader)?;
+
+			read_name(&shader)
+		} else {
+			String::new()
+		};
+
+		let vertex_offset = vertices.first().map(Vec::len).unwrap_or(0) as u32;
+
+		c.set_position((start + triangles_offset) as u64);
+		let triangle_offset = triangles.len() as u32;
+
+		for _ in 0..triangle_count {
+			let mut corners = [0; 3];
+			c.read_u32_into::<LittleEndian>(&mut corners)?;
+
+			if let Some(&corner) = corners.iter().find(|&&corner| corner as usize >= vertex_count) {
+				return invalid(format!("A triangle of surface {} refers to vertex {}, but there are only {}", surface, corner, vertex_count));
+			}
+
+			triangles.push((corners[0] + vertex_offset, corners[1] + vertex_offset, corners[2] + vertex_offset));
+		}
+
+		c.set_position((start + st_offset) as u64);
+
+		let mut texcoords = Vec::with_capacity(vertex_count);
+		for _ in 0..vertex_count {
+			texcoords.push(Point2::new(c.read_f32::<LittleEndian>()?, c.read_f32::<LittleEndian>()?));
+		}
+
+		c.set_position((start + xyz_offset) as u64);
+
+		for frame in vertices.iter_mut() {
+			for &texture in &texcoords {
+				let x = c.read_i16::<LittleEndian>()? as f32 / XYZ_SCALE;
+				let y = c.read_i16::<LittleEndian>()? as f32 / XYZ_SCALE;
+				let z = c.read_i16::<LittleEndian>()? as f32 / XYZ_SCALE;
+				let normal = c.read_u16::<LittleEndian>()?;
+
+				frame.push(Vertex {
+					position: Point3::new(x, y, z),
+					normal: decode_normal(normal),
+					texture
+				});
+			}
+		}
+
+		materials.push(Material {
+			name: read_name(&name),
+			texture: 0,
+			triangles: vec![TriangleSelection { offset: triangle_offset, len: triangle_count as u32 }],
+			vertex_offset,
+			vertex_count: vertex_count as u32,
+			texture_name
+		});
+
+		start += end;
+	}
+
+	if materials.is_empty() {
+		return invalid("The MD3 file has no surfaces".to_string());
+	}
+
+	Ok(model(triangles, materials, tag_names, vertices.into_iter().zip(tag_points).collect()))
+}
+
+/// Writes the first LOD level of a model as an MD3 file. Positions must lie within ±512 units of the origin,
+/// which is all that MD3 can store.
+pub fn write<W>(w: &mut W, model: &V2, name: &str) -> io::Result<()> where W: Write {
+	let frames: Vec<(Vec<Vertex>, Vec<Point3<f32>>)> = model.frames.iter().map(baked).collect();
+
+	for (i, frame) in frames.iter().enumerate() {
+		for vertex in &frame.0 {
+			let p = vertex.position;
+
+			if [p.x, p.y, p.z].iter().any(|&c| !(c * XYZ_SCALE).round().is_finite() || (c * XYZ_SCALE).round().abs() > i16::MAX as f32) {
+				return invalid(format!("Frame {} has a vertex at {:?}, outside of the range MD3 can store", i, p));
+			}
+		}
+	}
+
+	let mut surfaces = Vec::new();
+
+	for material in &model.materials {
+		let triangles = model.selection(material, 0);
+
+		if !triangles.is_empty() {
+			surfaces.push(surface(material, triangles, &frames)?);
+		}
+	}
+
+	let tags_offset = HEADER_SIZE + FRAME_SIZE * frames.len();
+	let surfaces_offset = tags_offset + TAG_SIZE * frames.len() * model.tag_points.len();
+	let end = surfaces_offset + surfaces.iter().map(Vec::len).sum::<usize>();
+
+	w.write_all(MAGIC)?;
+	w.write_i32::<LittleEndian>(VERSION)?;
+	write_name(w, name, 64)?;
+	w.write_i32::<LittleEndian>(0)?;
+	w.write_i32::<LittleEndian>(frames.len() as i32)?;
+	w.write_i32::<LittleEndian>(model.tag_points.len() as i32)?;
+	w.write_i32::<LittleEndian>(surfaces.len() as i32)?;
+	w.write_i32::<LittleEndian>(0)?;
+	w.write_i32::<LittleEndian>(HEADER_SIZE as i32)?;
+	w.write_i32::<LittleEndian>(tags_offset as i32)?;
+	w.write_i32::<LittleEndian>(surfaces_offset as i32)?;
+	w.write_i32::<LittleEndian>(end as i32)?;
+
+	for (i, frame) in frames.iter().enumerate() {
+		let mut lower = Point3::new(0.0f32, 0.0, 0.0);
+		let mut upper = Point3::new(0.0f32, 0.0, 0.0);
+		let mut radius: f32 = 0.0;
+
+		for (j, vertex) in frame.0.iter().enumerate() {
+			let p = vertex.position;
+
+			if j == 0 {
+				lower = p;
+				upper = p;
+			}
+
+			lower = Point3::new(lower.x.min(p.x), lower.y.min(p.y), lower.z.min(p.z));
+			upper = Point3::new(upper.x.max(p.x), upper.y.max(p.y), upper.z.max(p.z));
+			radius = radius.max((p.x * p.x + p.y * p.y + p.z * p.z).sqrt());
+		}
+
+		for c in &[lower.x, lower.y, lower.z, upper.x, upper.y, upper.z, 0.0, 0.0, 0.0, radius] {
+			w.write_f32::<LittleEndian>(*c)?;
+		}
+
+		write_name(w, &format!("frame {}", i), 16)?;
+	}
+
+	for frame in &frames {
+		for (i, name) in model.tag_points.iter().enumerate() {
+			// Frames missing a tag point put the tag at the origin, as every frame needs every tag.
+			let point = frame.1.get(i).cloned().unwrap_or_else(|| Point3::new(0.0, 0.0, 0.0));
+
+			write_name(w, name, 64)?;
+
+			for c in &[point.x, point.y, point.z, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0] {
+				w.write_f32::<LittleEndian>(*c)?;
+			}
+		}
+	}
+
+	for surface in &surfaces {
+		w.write_all(surface)?;
+	}
+
+	Ok(())
+}
+
+/// Builds a surface from the vertices that the triangles of a material use.
+fn surface(material: &Material, triangles: &[(u32, u32, u32)], frames: &[(Vec<Vertex>, Vec<Point3<f32>>)]) -> io::Result<Vec<u8>> {
+	let mut used = Vec::new();
+	let mut lookup = HashMap::new();
+
+	let mut local = |vertex: u32| -> u32 {
+		let next = used.len() as u32;
+		let index = *lookup.entry(vertex).or_insert(next);
+
+		if index == next {
+			used.push(vertex as usize);
+		}
+
+		index
+	};
+
+	let triangles: Vec<[u32; 3]> = triangles.iter().map(|t| [local(t.0), local(t.1), local(t.2)]).collect();
+
+	if let Some(&vertex) = used.iter().find(|&&vertex| frames.iter().any(|frame| vertex >= frame.0.len())) {
+		return invalid(format!("Material '{}' refers to vertex {}, which does not exist", material.name, vertex));
+	}
+
+	let triangles_offset = SURFACE_HEADER_SIZE + SHADER_SIZE;
+	let st_offset = triangles_offset + 12 * triangles.len();
+	let xyz_offset = st_offset + 8 * used.len();
+	let end = xyz_offset + 8 * used.len() * frames.len();
+
+	let mut w = Vec::with_capacity(end);
+
+	w.write_all(MAGIC)?;
+	write_name(&mut w, &material.name, 64)?;
+	w.write_i32::<LittleEndian>(0)?;
+	w.write_i32::<LittleEndian>(frames.len() as i32)?;
+	w.write_i32::<LittleEndian>(1)?;
+	w.write_i32::<LittleEndian>(used.len() as i32)?;
+	w.write_i32::<LittleEndian>(triangles.len() as i32)?;
+	w.write_i32::<LittleEndian>(triangles_offset as i32)?;
+	w.write_i32::<LittleEndian>(SURFACE_HEADER_SIZE as i32)?;
+	w.write_i32::<LittleEndian>(st_offset as i32)?;
+	w.write_i32::<LittleEndian>(xyz_offset as i32)?;
+	w.write_i32::<LittleEndian>(end as i32)?;
+
+	write_name(&mut w, &material.texture_name, 64)?;
+	w.write_i32::<LittleEndian>(0)?;
+
+	for triangle in &triangles {
+		for &corner in triangle {
+			w.write_u32::<LittleEndian>(corner)?;
+		}
+	}
+
+	for &vertex in &used {
+		let texture = frames[0].0[vertex].texture;
+
+		w.write_f32::<LittleEndian>(texture.x)?;
+		w.write_f32::<LittleEndian>(texture.y)?;
+	}
+
+	for frame in frames {
+		for &vertex in &used {
+			let vertex = frame.0[vertex];
+
+			w.write_i16::<LittleEndian>((vertex.position.x * XYZ_SCALE).round() as i16)?;
+			w.write_i16::<LittleEndian>((vertex.position.y * XYZ_SCALE).round() as i16)?;
+			w.write_i16::<LittleEndian>((vertex.position.z * XYZ_SCALE).round() as i16)?;
+			w.write_u16::<LittleEndian>(encode_normal(vertex.normal))?;
+		}
+	}
+
+	Ok(w)
+}
+
+fn count(c: &mut Cursor<&[u8]>, what: &str) -> io::Result<usize> {
+	let value = c.read_i32::<LittleEndian>()?;
+
+	if value < 0 || value as usize > c.get_ref().len() {
+		return invalid(format!("Implausible number of {}: {}", what, value));
+	}
+
+	Ok(value as usize)
+}
+
+/// Normals are stored as two angles of 8 bits each: the azimuth in the high byte, and the angle from the Z axis in the low byte.
+fn decode_normal(normal: u16) -> Vector3<f32> {
+	let azimuth = (normal >> 8) as f32 * (2.0 * PI / 255.0);
+	let zenith = (normal & 0xFF) as f32 * (2.0 * PI / 255.0);
+
+	Vector3::new(azimuth.cos() * zenith.sin(), azimuth.sin() * zenith.sin(), zenith.cos())
+}
+
+fn encode_normal(normal: Vector3<f32>) -> u16 {
+	// The poles have no azimuth, and are written the same way as by the Quake III tools.
+	if normal.x == 0.0 && normal.y == 0.0 {
+		return if normal.z >= 0.0 { 0 } else { 128 };
+	}
+
+	let azimuth = (normal.y.atan2(normal.x) * 255.0 / (2.0 * PI)).round() as i32 & 0xFF;
+	let zenith = (normal.z.clamp(-1.0, 1.0).acos() * 255.0 / (2.0 * PI)).round() as i32 & 0xFF;
+
+	((azimuth << 8) | zenith) as u16
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+	use byteorder::{ByteOrder, LittleEndian};
+	use cgmath::{Matrix4, SquareMatrix};
+	use samples::v2_scene;
+	use super::{read, write};
+
+	#[test]
+	fn model_without_frames_is_rejected() {
+		let mut model = v2_scene().model;
+		model.frames[0].transform = Matrix4::identity();
+
+		let mut data = Vec::new();
+		write(&mut data, &model, "model").unwrap();
+		assert_eq!(read(&mut Cursor::new(&data)).unwrap().frames.len(), 1);
+
+		LittleEndian::write_i32(&mut data[4 + 4 + 64 + 4..], 0);
+		assert!(read(&mut Cursor::new(&data)).is_err());
+	}
+}
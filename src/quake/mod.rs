@@ -0,0 +1,73 @@
+//! Both formats store compressed vertex positions for every frame, so frames map one to one. Neither has a
+//! per-frame transform for the whole model, so frame transforms are applied to the geometry on export, and
+//! imported models use the identity transform. Texture coordinates are used as they are, since Quake and
+//! Empire Earth both put the origin at the top left.
+
+use std::io::{self, Write};
+use cgmath::{Point3, Matrix, SquareMatrix, InnerSpace, Transform};
+use collider::CenterBuilder;
+use v2::{V2, Material, Frame, Vertex, Triangle};
+
+/// MD2 models, as used by Quake II.
+pub mod md2;
+
+/// MD3 models, as used by Quake III Arena.
+pub mod md3;
+
+/// The vertices and tag points of a frame with its transform applied.
+fn baked(frame: &Frame) -> (Vec<Vertex>, Vec<Point3<f32>>) {
+	let normal_transform = frame.transform.invert().map(|inverse| inverse.transpose()).unwrap_or(frame.transform);
+
+	let vertices = frame.vertices.iter().map(|vertex| {
+		let normal = normal_transform.transform_vector(vertex.normal);
+
+		Vertex {
+			position: frame.transform.transform_point(vertex.position),
+			normal: if normal.magnitude2() > 0.0 { normal.normalize() } else { normal },
+			texture: vertex.texture
+		}
+	}).collect();
+
+	let tag_points = frame.tag_points.iter().map(|&point| frame.transform.transform_point(point)).collect();
+
+	(vertices, tag_points)
+}
+
+/// Builds a model from imported frames, computing the center and colliders.
+fn model(triangles: Vec<Triangle>, materials: Vec<Material>, tag_points: Vec<String>, frames: Vec<(Vec<Vertex>, Vec<Point3<f32>>)>) -> V2 {
+	let mut center = CenterBuilder::begin();
+
+	for vertex in frames.iter().flat_map(|frame| frame.0.iter()) {
+		center.update(vertex.position);
+	}
+
+	let center = center.build();
+
+	V2 {
+		center,
+		lod_levels: vec![triangles],
+		materials,
+		tag_points,
+		frames: frames.into_iter().map(|(vertices, tag_points)| Frame::from_vertices(vertices, tag_points, center)).collect()
+	}
+}
+
+/// Reads a NUL terminated ISO-8859-1 string from a fixed size field.
+fn read_name(bytes: &[u8]) -> String {
+	bytes.iter().take_while(|&&byte| byte != 0).map(|&byte| byte as char).collect()
+}
+
+/// Writes a string into a fixed size field as ISO-8859-1, truncating it to leave room for the terminating NUL.
+fn write_name<W>(w: &mut W, name: &str, len: usize) -> io::Result<()> where W: Write {
+	let mut field = vec![0; len];
+
+	for (byte, c) in field.iter_mut().zip(name.chars().take(len - 1)) {
+		*byte = if c < '\u{100}' { c as u8 } else { b'?' };
+	}
+
+	w.write_all(&field)
+}
+
+fn invalid<T>(message: String) -> io::Result<T> {
+	Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
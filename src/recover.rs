@@ -0,0 +1,361 @@
+//! Unlike `Scene::read`, which discards everything as soon as it hits an error, recovery keeps every part
+//! of the model that decoded cleanly and records what went wrong. The model is read by the same reader as
+//! `Scene::read`, so frames are kept up to the first one that is truncated. Values that the reader accepts but
+//! that look damaged, such as non-finite coordinates, are reported without dropping anything.
+
+use std::io::{self, Read};
+use std::fmt::{Debug, Display};
+use {ModelHeader, Model, V2, Scene};
+use v2::{Frame, Progress};
+use trace::Fields;
+
+/// Something that went wrong while recovering a model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+	/// Offset from the start of the scene where the problem was found.
+	pub offset: u64,
+	/// The part of the model that was being read, such as `frames[3].vertices[7].position`.
+	pub context: String,
+	pub message: String
+}
+
+/// Whatever could be recovered from a possibly damaged V2 model.
+#[derive(Debug)]
+pub struct Recovered {
+	pub header: Option<ModelHeader>,
+	pub name: Option<String>,
+	/// The recovered model. Parts that could not be read are left empty, and the center defaults to the origin.
+	pub model: V2,
+	/// Child scenes, only recovered if the model itself was read completely. Children too damaged to write are dropped.
+	pub children: Vec<Scene<V2>>,
+	/// Number of frames the file claimed to have.
+	pub expected_frames: u32,
+	pub problems: Vec<Problem>
+}
+
+impl Recovered {
+	/// Reads as much of a V2 scene as possible, starting at the model header.
+	pub fn read<R>(r: &mut R) -> Self where R: Read {
+		Self::read_node(&mut Recorder::new(r)).0
+	}
+
+	/// Reads a node and its children, also returning whether the reader got to the end of the node. If it did not,
+	/// the position of anything after it is unknown.
+	fn read_node<R>(r: &mut Recorder<R>) -> (Self, bool) where R: Read {
+		let mut problems = Vec::new();
+		let mut progress = Progress::new();
+
+		r.parts.clear();
+
+		let start = r.position;
+		let header = r.attempt(&mut problems, ModelHeader::read_fields);
+
+		let read = match header {
+			Some(header) if header == V2::HEADER => r.attempt(&mut problems, |r| V2::read_progress(r, &mut progress)).is_some(),
+			Some(header) => {
+				problems.push(Problem {
+					offset: start,
+					context: "header".to_string(),
+					message: format!("Wrong model header: expected {:?}, got {:?}", V2::HEADER, header)
+				});
+
+				false
+			},
+			None => false
+		};
+
+		check(&progress, &r.parts, &mut problems);
+
+		let mut recovered = Recovered {
+			header,
+			name: progress.name,
+			model: progress.model,
+			children: Vec::new(),
+			expected_frames: progress.quantities.map(|quantities| quantities.frames).unwrap_or(0),
+			problems
+		};
+
+		if !read {
+			return (recovered, false);
+		}
+
+		let additional_models = progress.quantities.map(|quantities| quantities.additional_models).unwrap_or(0);
+
+		for i in 0..additional_models {
+			let (child, read) = Self::read_node(r);
+
+			for problem in child.problems {
+				recovered.problems.push(Problem {
+					context: format!("children[{}].{}", i, problem.context),
+					..problem
+				});
+			}
+
+			let mut scene = Scene::single(child.name.unwrap_or_default(), child.model);
+			scene.children = child.children;
+
+			// A child that ran out of data before its materials or frames cannot be written back out.
+			match validate(&scene, "") {
+				Ok(()) => recovered.children.push(scene),
+				Err(message) => recovered.problems.push(Problem {
+					offset: r.position,
+					context: format!("children[{}]", i),
+					message: format!("Dropping the child, since it cannot be written: {}", message)
+				})
+			}
+
+			if !read {
+				return (recovered, false);
+			}
+		}
+
+		(recovered, true)
+	}
+
+	/// Returns true if nothing went wrong.
+	pub fn is_complete(&self) -> bool {
+		self.problems.is_empty()
+	}
+
+	/// Builds a scene from the recovered parts, failing unless enough of every node survived for it to be written
+	/// back out.
+	pub fn into_scene(self) -> io::Result<Scene<V2>> {
+		let mut scene = Scene::single(self.name.unwrap_or_else(|| "Scene Root".to_string()), self.model);
+		scene.children = self.children;
+
+		validate(&scene, "").map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+
+		Ok(scene)
+	}
+}
+
+/// Reports parts of a model that were read, but refer to things that do not exist or hold values that are probably
+/// damaged. Nothing is dropped, since the reader accepted all of it.
+fn check(progress: &Progress, parts: &[(String, u64)], problems: &mut Vec<Problem>) {
+	let vertices = match progress.quantities {
+		Some(quantities) => quantities.vertices,
+		None => return
+	};
+
+	let model = &progress.model;
+
+	let mut problem = |context: String, message: String| {
+		let offset = parts.iter().find(|part| part.0 == context).map(|part| part.1).unwrap_or(0);
+
+		problems.push(Problem { offset, context, message });
+	};
+
+	for (i, triangles) in model.lod_levels.iter().enumerate() {
+		if let Some(triangle) = triangles.iter().find(|t| t.0 >= vertices || t.1 >= vertices || t.2 >= vertices) {
+			problem(format!("lod_levels[{}]", i), format!("Triangle {:?} refers to a vertex past the end ({} vertices)", triangle, vertices));
+		}
+	}
+
+	for (i, material) in model.materials.iter().enumerate() {
+		for (lod, selection) in material.triangles.iter().enumerate() {
+			let available = model.lod_levels.get(lod).map(Vec::len).unwrap_or(0) as u64;
+
+			if selection.offset as u64 + selection.len as u64 > available {
+				problem(format!("materials[{}]", i), format!("Selection for LOD {} ends past the last triangle ({} triangles)", lod, available));
+			}
+		}
+	}
+
+	for (i, frame) in model.frames.iter().enumerate() {
+		if !is_finite(frame) {
+			problem(format!("frames[{}]", i), "Frame contains non-finite values".to_string());
+		}
+	}
+}
+
+/// Checks that a node and all of its children can be written, naming the first one that cannot.
+fn validate(scene: &Scene<V2>, context: &str) -> Result<(), String> {
+	if let Err(e) = scene.model.quantities(0) {
+		return Err(if context.is_empty() { e.to_string() } else { format!("{}: {}", context, e) });
+	}
+
+	for (i, child) in scene.children.iter().enumerate() {
+		let context = if context.is_empty() { format!("children[{}]", i) } else { format!("{}.children[{}]", context, i) };
+
+		validate(child, &context)?;
+	}
+
+	Ok(())
+}
+
+fn is_finite(frame: &Frame) -> bool {
+	let aabb = frame.collider.aabb;
+	let matrix: &[f32; 16] = frame.transform.as_ref();
+
+	frame.collider.radius.is_finite()
+		&& frame.vertices.iter().all(|v| {
+			v.position.x.is_finite() && v.position.y.is_finite() && v.position.z.is_finite()
+				&& v.normal.x.is_finite() && v.normal.y.is_finite() && v.normal.z.is_finite()
+				&& v.texture.x.is_finite() && v.texture.y.is_finite()
+		})
+		&& frame.tag_points.iter().all(|p| p.x.is_finite() && p.y.is_finite() && p.z.is_finite())
+		&& matrix.iter().all(|c| c.is_finite())
+		&& [aabb.lower.x, aabb.lower.y, aabb.lower.z, aabb.upper.x, aabb.upper.y, aabb.upper.z].iter().all(|c| c.is_finite())
+}
+
+/// Reads fields for the model readers, keeping track of how many bytes have been read, where each part of the
+/// current model started, and where reading failed, so that problems can be located in the file.
+struct Recorder<R> {
+	inner: R,
+	position: u64,
+	depth: usize,
+	/// The start of each top level part of the current model, such as `frames[3]`.
+	parts: Vec<(String, u64)>,
+	/// The start and path of the innermost field that failed. The path is built up as the error leaves each group.
+	failure: Option<(u64, String)>
+}
+
+impl<R> Recorder<R> where R: Read {
+	fn new(inner: R) -> Self {
+		Recorder {
+			inner,
+			position: 0,
+			depth: 0,
+			parts: Vec::new(),
+			failure: None
+		}
+	}
+
+	/// Runs a reader, recording a problem if it fails.
+	fn attempt<T, F>(&mut self, problems: &mut Vec<Problem>, read: F) -> Option<T> where F: FnOnce(&mut Self) -> io::Result<T> {
+		match read(self) {
+			Ok(value) => Some(value),
+			Err(e) => {
+				let (offset, context) = self.failure.take().unwrap_or_else(|| (self.position, String::new()));
+
+				problems.push(Problem { offset, context, message: e.to_string() });
+
+				None
+			}
+		}
+	}
+
+	fn blame<N>(&mut self, start: u64, name: N) where N: Display {
+		self.failure = Some(match self.failure.take() {
+			None => (start, name.to_string()),
+			Some((offset, ref path)) if path.starts_with('[') => (offset, format!("{}{}", name, path)),
+			Some((offset, path)) => (offset, format!("{}.{}", name, path))
+		});
+	}
+}
+
+impl<R> Fields for Recorder<R> where R: Read {
+	fn group<T, N, F>(&mut self, name: N, read: F) -> io::Result<T> where N: Display, F: FnOnce(&mut Self) -> io::Result<T> {
+		let start = self.position;
+
+		self.depth += 1;
+		let result = read(self);
+		self.depth -= 1;
+
+		match result {
+			Ok(_) if self.depth == 0 => self.parts.push((name.to_string(), start)),
+			Ok(_) => (),
+			Err(_) => self.blame(start, name)
+		}
+
+		result
+	}
+
+	fn field<T, N, F>(&mut self, name: N, read: F) -> io::Result<T> where N: Display, T: Debug, F: FnOnce(&mut Self) -> io::Result<T> {
+		let start = self.position;
+		let result = read(self);
+
+		if result.is_err() {
+			self.blame(start, name);
+		}
+
+		result
+	}
+}
+
+impl<R> Read for Recorder<R> where R: Read {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let read = self.inner.read(buf)?;
+		self.position += read as u64;
+
+		Ok(read)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+	use samples::v2_scene;
+	use super::Recovered;
+
+	/// The sample scene, whose frame has a NaN in its transform, and the offset where its child starts.
+	fn data() -> (Vec<u8>, usize) {
+		let mut scene = v2_scene();
+		scene.trailing.clear();
+
+		let mut data = Vec::new();
+		scene.write(&mut data).unwrap();
+
+		let mut root = Vec::new();
+		scene.children.clear();
+		scene.write(&mut root).unwrap();
+
+		(data, root.len())
+	}
+
+	#[test]
+	fn non_finite_frames_are_reported_but_kept() {
+		let (data, _) = data();
+		let recovered = Recovered::read(&mut Cursor::new(data));
+
+		assert_eq!(recovered.problems.len(), 1);
+		assert_eq!(recovered.problems[0].context, "frames[0]");
+		assert_eq!(recovered.model.frames.len(), 1);
+		assert_eq!(recovered.children.len(), 1);
+	}
+
+	#[test]
+	fn truncated_frame_is_located_and_the_rest_kept() {
+		let (mut data, child) = data();
+
+		// The AABB is the last thing in the root, with the upper corner in its last 12 bytes.
+		data.truncate(child - 4);
+
+		let recovered = Recovered::read(&mut Cursor::new(data));
+		let problem = &recovered.problems[0];
+
+		assert_eq!(problem.context, "frames[0].aabb.upper");
+		assert_eq!(problem.offset, child as u64 - 12);
+		assert!(recovered.model.frames.is_empty());
+		assert_eq!(recovered.model.materials.len(), 1);
+		assert_eq!(recovered.model.tag_points, vec!["tag".to_string()]);
+		assert_eq!(recovered.expected_frames, 1);
+	}
+
+	#[test]
+	fn truncated_child_is_dropped() {
+		let (mut data, child) = data();
+		data.truncate(child + 40);
+
+		let recovered = Recovered::read(&mut Cursor::new(data));
+
+		assert!(recovered.children.is_empty());
+		assert!(recovered.problems.iter().any(|problem| problem.context == "children[0]"), "{:?}", recovered.problems);
+
+		let mut written = Vec::new();
+		recovered.into_scene().unwrap().write(&mut written).unwrap();
+	}
+
+	#[test]
+	fn unwritable_children_are_rejected() {
+		let (data, _) = data();
+
+		let mut recovered = Recovered::read(&mut Cursor::new(data));
+		assert_eq!(recovered.children.len(), 1);
+
+		recovered.children[0].model.materials.clear();
+
+		let error = recovered.into_scene().unwrap_err();
+		assert!(error.to_string().starts_with("children[0]: "), "{}", error);
+	}
+}
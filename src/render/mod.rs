@@ -0,0 +1,76 @@
+//! Rendering is orthographic. Model space is taken to have Z pointing up, with the front of a model facing -Y,
+//! which is how Empire Earth places models on the map. A view matrix maps model space to view space, where X
+//! points right, Y points up and Z points towards the viewer.
+
+use cgmath::{Matrix4, Rad};
+
+/// Vector drawings of frames.
+pub mod svg;
+
+/// Shaded and textured images of scenes, drawn with a z-buffer.
+pub mod raster;
+
+/// Grids of frames or angles, for reviewing animations at a glance.
+pub mod sheet;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum View {
+	/// Looking along +Y at the front of the model.
+	#[default]
+	Front,
+	/// Looking along -X at the right side of the model.
+	Side,
+	/// Looking down along -Z.
+	Top,
+	/// Any matrix from model space to view space.
+	Custom(Matrix4<f32>)
+}
+
+impl View {
+	/// A view of the front of the model after turning it by `azimuth` around the Z axis, looking down from `elevation` above the horizon.
+	pub fn orbit(azimuth: Rad<f32>, elevation: Rad<f32>) -> Self {
+		View::Custom(Matrix4::from_angle_x(elevation) * View::Front.matrix() * Matrix4::from_angle_z(azimuth))
+	}
+
+	pub fn matrix(&self) -> Matrix4<f32> {
+		// Each line is a column of the matrix.
+		match *self {
+			View::Front => Matrix4::new(
+				1.0, 0.0,  0.0, 0.0,
+				0.0, 0.0, -1.0, 0.0,
+				0.0, 1.0,  0.0, 0.0,
+				0.0, 0.0,  0.0, 1.0
+			),
+			View::Side => Matrix4::new(
+				0.0, 0.0, 1.0, 0.0,
+				1.0, 0.0, 0.0, 0.0,
+				0.0, 1.0, 0.0, 0.0,
+				0.0, 0.0, 0.0, 1.0
+			),
+			View::Top => Matrix4::new(
+				1.0, 0.0, 0.0, 0.0,
+				0.0, 1.0, 0.0, 0.0,
+				0.0, 0.0, 1.0, 0.0,
+				0.0, 0.0, 0.0, 1.0
+			),
+			View::Custom(matrix) => matrix
+		}
+	}
+}
+
+/// Distinct colors given to materials in turn, when no color is chosen for them.
+pub const PALETTE: [[u8; 3]; 8] = [
+	[0x1f, 0x77, 0xb4],
+	[0xff, 0x7f, 0x0e],
+	[0x2c, 0xa0, 0x2c],
+	[0xd6, 0x27, 0x28],
+	[0x94, 0x67, 0xbd],
+	[0x8c, 0x56, 0x4b],
+	[0xe3, 0x77, 0xc2],
+	[0x17, 0xbe, 0xcf]
+];
+
+/// The color of a material, from `colors` if it has an entry, or else from the palette.
+pub fn material_color(colors: &[[u8; 3]], material: usize) -> [u8; 3] {
+	colors.get(material).cloned().unwrap_or(PALETTE[material % PALETTE.len()])
+}
@@ -0,0 +1,269 @@
+//! Every node of the scene is drawn with its frame transform. The camera is placed from the colliders of the
+//! drawn frames, so that their bounding sphere fills the image whatever the view direction, which keeps the
+//! model the same size as it turns. Triangles are lit from both sides, since models are not guaranteed to be
+//! closed, and textures are sampled without filtering. Texels with less than half alpha are left out.
+
+use std::io;
+use std::collections::HashMap;
+use cgmath::{Point2, Point3, Vector3, Matrix4, SquareMatrix, Matrix, EuclideanSpace, InnerSpace, MetricSpace, Transform, Deg};
+use scene::Scene;
+use v2::{V2, Frame};
+use image::Image;
+use super::{View, material_color};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RasterOptions {
+	pub view: View,
+	/// Frame to draw. Models with fewer frames show their last frame.
+	pub frame: usize,
+	/// LOD level to draw. Models with fewer levels use their last one.
+	pub lod: usize,
+	/// Size of the image in pixels.
+	pub width: u32,
+	pub height: u32,
+	/// Color of the pixels that the model does not cover.
+	pub background: [u8; 4],
+	/// Color of each material without a texture, in order. Materials without an entry get a color from the palette.
+	pub colors: Vec<[u8; 3]>,
+	/// Direction towards the light, in view space.
+	pub light: Vector3<f32>,
+	/// Brightness of surfaces facing away from the light, from 0 to 1.
+	pub ambient: f32,
+	/// Fraction of the image left empty on each side of the bounding sphere.
+	pub margin: f32
+}
+
+impl Default for RasterOptions {
+	fn default() -> Self {
+		RasterOptions {
+			view: View::orbit(Deg(30.0).into(), Deg(25.0).into()),
+			frame: 0,
+			lod: 0,
+			width: 256,
+			height: 256,
+			background: [0, 0, 0, 0],
+			colors: Vec::new(),
+			light: Vector3::new(-0.4, 0.6, 0.7),
+			ambient: 0.35,
+			margin: 0.05
+		}
+	}
+}
+
+/// Draws one frame of every node of a scene. Textures are requested from `texture` by name, once each, and
+/// materials whose texture is not available are drawn in a flat color.
+pub fn render<F>(scene: &Scene<V2>, options: &RasterOptions, mut texture: F) -> io::Result<Image> where F: FnMut(&str) -> Option<Image> {
+	let mut nodes = Vec::new();
+	flatten(scene, options.frame, &mut nodes);
+
+	let view = options.view.matrix();
+	let camera = Camera::new(&nodes, &view, options);
+
+	let mut textures: HashMap<&str, Option<Image>> = HashMap::new();
+	let mut target = Target {
+		image: Image::new(options.width, options.height, options.background)?,
+		depth: vec![f32::NEG_INFINITY; options.width as usize * options.height as usize]
+	};
+
+	let light = if options.light.magnitude2() > 0.0 { options.light.normalize() } else { Vector3::new(0.0, 0.0, 1.0) };
+
+	for &(model, frame) in &nodes {
+		let transform = view * frame.transform;
+		let normal_transform = transform.invert().map(|inverse| inverse.transpose()).unwrap_or(transform);
+
+		let corners: Vec<Corner> = frame.vertices.iter().map(|vertex| {
+			let p = camera.apply(transform.transform_point(vertex.position));
+			let normal = normal_transform.transform_vector(vertex.normal);
+
+			Corner {
+				position: p,
+				normal: if normal.magnitude2() > 0.0 { normal.normalize() } else { Vector3::new(0.0, 0.0, 1.0) },
+				texture: vertex.texture
+			}
+		}).collect();
+
+		let lod = options.lod.min(model.lod_levels.len().saturating_sub(1));
+
+		for (i, material) in model.materials.iter().enumerate() {
+			if !material.texture_name.is_empty() && !textures.contains_key(material.texture_name.as_str()) {
+				textures.insert(&material.texture_name, texture(&material.texture_name));
+			}
+
+			let image = textures.get(material.texture_name.as_str()).and_then(Option::as_ref);
+			let color = material_color(&options.colors, i);
+			let shading = Shading { image, color: [color[0], color[1], color[2], 255], light, ambient: options.ambient };
+
+			for triangle in model.selection(material, lod) {
+				let indices = [triangle.0 as usize, triangle.1 as usize, triangle.2 as usize];
+
+				if indices.iter().any(|&index| index >= corners.len()) {
+					return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Material '{}' refers to a vertex that does not exist", material.name)));
+				}
+
+				target.triangle([&corners[indices[0]], &corners[indices[1]], &corners[indices[2]]], &shading);
+			}
+		}
+	}
+
+	Ok(target.image)
+}
+
+fn flatten<'s>(scene: &'s Scene<V2>, frame: usize, nodes: &mut Vec<(&'s V2, &'s Frame)>) {
+	if let Some(frame) = scene.model.frames.get(frame).or_else(|| scene.model.frames.last()) {
+		nodes.push((&scene.model, frame));
+	}
+
+	for child in &scene.children {
+		flatten(child, frame, nodes);
+	}
+}
+
+/// Maps view space to pixels, with the bounding sphere in the middle of the image.
+struct Camera {
+	center: Point3<f32>,
+	scale: f32,
+	width: f32,
+	height: f32
+}
+
+impl Camera {
+	fn new(nodes: &[(&V2, &Frame)], view: &Matrix4<f32>, options: &RasterOptions) -> Self {
+		let mut points = Vec::new();
+
+		for &(_, frame) in nodes {
+			let aabb = frame.collider.aabb;
+			let transform = view * frame.transform;
+
+			for i in 0..8 {
+				points.push(transform.transform_point(Point3::new(
+					if i & 1 == 0 { aabb.lower.x } else { aabb.upper.x },
+					if i & 2 == 0 { aabb.lower.y } else { aabb.upper.y },
+					if i & 4 == 0 { aabb.lower.z } else { aabb.upper.z }
+				)));
+			}
+		}
+
+		// Colliders of models that were built by hand may be left empty, so fall back to the vertices.
+		if points.windows(2).all(|pair| pair[0] == pair[1]) {
+			points = nodes.iter()
+				.flat_map(|&(_, frame)| frame.vertices.iter().map(move |vertex| (view * frame.transform).transform_point(vertex.position)))
+				.collect();
+		}
+
+		let mut lower = points.first().cloned().unwrap_or_else(|| Point3::new(0.0, 0.0, 0.0));
+		let mut upper = lower;
+
+		for p in &points {
+			lower = Point3::new(lower.x.min(p.x), lower.y.min(p.y), lower.z.min(p.z));
+			upper = Point3::new(upper.x.max(p.x), upper.y.max(p.y), upper.z.max(p.z));
+		}
+
+		let center = lower.midpoint(upper);
+		let radius = points.iter().map(|p| p.distance(center)).fold(0.0, f32::max).max(1e-6);
+		let (width, height) = (options.width as f32, options.height as f32);
+
+		Camera {
+			center,
+			scale: width.min(height) * (1.0 - 2.0 * options.margin).max(0.0) / (2.0 * radius),
+			width,
+			height
+		}
+	}
+
+	/// Returns pixel coordinates, keeping Z for depth testing.
+	fn apply(&self, p: Point3<f32>) -> Point3<f32> {
+		Point3::new(
+			self.width / 2.0 + (p.x - self.center.x) * self.scale,
+			self.height / 2.0 - (p.y - self.center.y) * self.scale,
+			p.z
+		)
+	}
+}
+
+struct Corner {
+	position: Point3<f32>,
+	normal: Vector3<f32>,
+	texture: Point2<f32>
+}
+
+struct Shading<'i> {
+	image: Option<&'i Image>,
+	color: [u8; 4],
+	light: Vector3<f32>,
+	ambient: f32
+}
+
+struct Target {
+	image: Image,
+	/// View space Z of the nearest surface drawn at each pixel. Larger values are closer to the viewer.
+	depth: Vec<f32>
+}
+
+impl Target {
+	fn triangle(&mut self, corners: [&Corner; 3], shading: &Shading) {
+		let [a, b, c] = corners;
+		let area = edge(a.position, b.position, c.position);
+
+		if area == 0.0 || !area.is_finite() {
+			return;
+		}
+
+		let (width, height) = (self.image.width as f32, self.image.height as f32);
+		let left = a.position.x.min(b.position.x).min(c.position.x).floor().max(0.0);
+		let right = a.position.x.max(b.position.x).max(c.position.x).ceil().min(width);
+		let top = a.position.y.min(b.position.y).min(c.position.y).floor().max(0.0);
+		let bottom = a.position.y.max(b.position.y).max(c.position.y).ceil().min(height);
+
+		for y in top as u32..bottom as u32 {
+			for x in left as u32..right as u32 {
+				let p = Point3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+
+				// Barycentric weights, which are all positive inside the triangle whichever way it winds.
+				let wa = edge(b.position, c.position, p) / area;
+				let wb = edge(c.position, a.position, p) / area;
+				let wc = 1.0 - wa - wb;
+
+				if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+					continue;
+				}
+
+				let index = y as usize * self.image.width as usize + x as usize;
+				let z = wa * a.position.z + wb * b.position.z + wc * c.position.z;
+
+				if z <= self.depth[index] {
+					continue;
+				}
+
+				let color = match shading.image {
+					Some(image) => image.sample(
+						wa * a.texture.x + wb * b.texture.x + wc * c.texture.x,
+						wa * a.texture.y + wb * b.texture.y + wc * c.texture.y
+					),
+					None => shading.color
+				};
+
+				if color[3] < 128 {
+					continue;
+				}
+
+				let mut normal = a.normal * wa + b.normal * wb + c.normal * wc;
+
+				if normal.z < 0.0 {
+					normal = -normal;
+				}
+
+				let lambert = if normal.magnitude2() > 0.0 { normal.normalize().dot(shading.light).max(0.0) } else { 1.0 };
+				let brightness = shading.ambient + (1.0 - shading.ambient) * lambert;
+				let shade = |channel: u8| (channel as f32 * brightness).round().clamp(0.0, 255.0) as u8;
+
+				self.image.set_pixel(x, y, [shade(color[0]), shade(color[1]), shade(color[2]), 255]);
+				self.depth[index] = z;
+			}
+		}
+	}
+}
+
+/// Twice the signed area of the triangle `a b p`, using only X and Y.
+fn edge(a: Point3<f32>, b: Point3<f32>, p: Point3<f32>) -> f32 {
+	(b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
@@ -0,0 +1,212 @@
+//! A sheet is a grid of images of the same size, read left to right and top to bottom. Each cell is labelled
+//! in its top left corner with a number drawn in a small built-in digit font: the frame number when laying out
+//! frames, or the angle in degrees when turning a model around.
+
+use std::io;
+use std::f32::consts::PI;
+use cgmath::{Matrix4, Rad};
+use scene::Scene;
+use v2::V2;
+use image::Image;
+use super::View;
+use super::raster::{self, RasterOptions};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Sequence {
+	/// Every frame of the longest animation in the scene.
+	Frames,
+	/// One frame, turned around the Z axis in equal steps.
+	Turntable {
+		frame: usize,
+		angles: usize
+	}
+}
+
+/// Renders the images of a sequence, along with the label of each. The view, size and everything else but
+/// the frame come from `options`.
+pub fn render<F>(scene: &Scene<V2>, sequence: Sequence, options: &RasterOptions, mut texture: F) -> io::Result<Vec<(Image, u32)>> where F: FnMut(&str) -> Option<Image> {
+	let mut images = Vec::new();
+
+	match sequence {
+		Sequence::Frames => for frame in 0..frames(scene).max(1) {
+			let options = RasterOptions { frame, ..options.clone() };
+
+			images.push((raster::render(scene, &options, &mut texture)?, frame as u32));
+		},
+		Sequence::Turntable { frame, angles } => for i in 0..angles {
+			let angle = 2.0 * PI * i as f32 / angles as f32;
+			let view = View::Custom(options.view.matrix() * Matrix4::from_angle_z(Rad(angle)));
+			let options = RasterOptions { frame, view, ..options.clone() };
+
+			images.push((raster::render(scene, &options, &mut texture)?, (360 * i / angles) as u32));
+		}
+	}
+
+	Ok(images)
+}
+
+/// Lays out images in a grid, with `columns` images per row, or a roughly square grid if `columns` is zero.
+/// Each image is labelled with its number if `labels` is set.
+pub fn contact_sheet(images: &[(Image, u32)], columns: usize, labels: bool, background: [u8; 4]) -> io::Result<Image> {
+	let (width, height) = match images.first() {
+		Some((image, _)) => (image.width, image.height),
+		None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "A contact sheet needs at least one image"))
+	};
+
+	if images.iter().any(|(image, _)| image.width != width || image.height != height) {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "Every image of a contact sheet must have the same size"));
+	}
+
+	let columns = if columns == 0 { (images.len() as f32).sqrt().ceil() as usize } else { columns };
+	let rows = images.len().div_ceil(columns);
+
+	let cells = |count: usize, size: u32| if count <= u32::MAX as usize { (count as u32).checked_mul(size) } else { None };
+
+	let mut sheet = match (cells(columns, width), cells(rows, height)) {
+		(Some(sheet_width), Some(sheet_height)) => Image::new(sheet_width, sheet_height, background)?,
+		_ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("A grid of {}x{} images of {}x{} is too large for one image", columns, rows, width, height)))
+	};
+
+	for (i, (image, label)) in images.iter().enumerate() {
+		let (left, top) = ((i % columns) as u32 * width, (i / columns) as u32 * height);
+
+		for y in 0..height {
+			let source = y as usize * width as usize * 4;
+			let target = ((top + y) as usize * sheet.width as usize + left as usize) * 4;
+
+			sheet.pixels[target..target + width as usize * 4].copy_from_slice(&image.pixels[source..source + width as usize * 4]);
+		}
+
+		if labels {
+			// Larger cells get larger digits, so that the labels stay legible.
+			let scale = (width.min(height) / 64).max(1);
+			draw_number(&mut sheet, left + scale, top + scale, *label, scale);
+		}
+	}
+
+	Ok(sheet)
+}
+
+/// The number of frames in the longest animation of a scene.
+fn frames(scene: &Scene<V2>) -> usize {
+	scene.children.iter().map(frames).fold(scene.model.frames.len(), usize::max)
+}
+
+const DIGIT_WIDTH: u32 = 3;
+const DIGIT_HEIGHT: u32 = 5;
+
+/// The rows of each digit from top to bottom, with the leftmost pixel in the highest of the 3 bits.
+const DIGITS: [[u8; 5]; 10] = [
+	[0b111, 0b101, 0b101, 0b101, 0b111],
+	[0b010, 0b110, 0b010, 0b010, 0b111],
+	[0b111, 0b001, 0b111, 0b100, 0b111],
+	[0b111, 0b001, 0b111, 0b001, 0b111],
+	[0b101, 0b101, 0b111, 0b001, 0b001],
+	[0b111, 0b100, 0b111, 0b001, 0b111],
+	[0b111, 0b100, 0b111, 0b101, 0b111],
+	[0b111, 0b001, 0b010, 0b010, 0b010],
+	[0b111, 0b101, 0b111, 0b101, 0b111],
+	[0b111, 0b101, 0b111, 0b001, 0b111]
+];
+
+/// Draws white digits on a black box, so that they show up on any image.
+fn draw_number(image: &mut Image, left: u32, top: u32, number: u32, scale: u32) {
+	let digits: Vec<usize> = number.to_string().bytes().map(|digit| (digit - b'0') as usize).collect();
+
+	// One pixel of padding around the digits, and one between each.
+	let width = (digits.len() as u32 * (DIGIT_WIDTH + 1) + 1) * scale;
+	let height = (DIGIT_HEIGHT + 2) * scale;
+
+	let mut fill = |x: u32, y: u32, color: [u8; 4]| {
+		if left + x < image.width && top + y < image.height {
+			image.set_pixel(left + x, top + y, color);
+		}
+	};
+
+	for y in 0..height {
+		for x in 0..width {
+			fill(x, y, [0, 0, 0, 255]);
+		}
+	}
+
+	for (i, &digit) in digits.iter().enumerate() {
+		for (row, bits) in DIGITS[digit].iter().enumerate() {
+			for column in 0..DIGIT_WIDTH {
+				if bits & (0b100 >> column) == 0 {
+					continue;
+				}
+
+				let x = (1 + i as u32 * (DIGIT_WIDTH + 1) + column) * scale;
+				let y = (1 + row as u32) * scale;
+
+				for dy in 0..scale {
+					for dx in 0..scale {
+						fill(x + dx, y + dy, [255, 255, 255, 255]);
+					}
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use image::Image;
+	use super::contact_sheet;
+
+	const RED: [u8; 4] = [255, 0, 0, 255];
+	const GREEN: [u8; 4] = [0, 255, 0, 255];
+	const BLUE: [u8; 4] = [0, 0, 255, 255];
+	const GRAY: [u8; 4] = [9, 9, 9, 9];
+	const BLACK: [u8; 4] = [0, 0, 0, 255];
+	const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+	fn images(size: u32, colors: &[[u8; 4]]) -> Vec<(Image, u32)> {
+		colors.iter().enumerate().map(|(i, &color)| (Image::new(size, size, color).unwrap(), i as u32)).collect()
+	}
+
+	#[test]
+	fn images_fill_rows_left_to_right() {
+		let sheet = contact_sheet(&images(2, &[RED, GREEN, BLUE]), 0, false, GRAY).unwrap();
+
+		assert_eq!((sheet.width, sheet.height), (4, 4));
+		assert_eq!([sheet.pixel(1, 1), sheet.pixel(2, 1), sheet.pixel(1, 2), sheet.pixel(2, 2)], [RED, GREEN, BLUE, GRAY]);
+
+		let sheet = contact_sheet(&images(2, &[RED, GREEN, BLUE]), 3, false, GRAY).unwrap();
+
+		assert_eq!((sheet.width, sheet.height), (6, 2));
+		assert_eq!([sheet.pixel(0, 0), sheet.pixel(3, 1), sheet.pixel(5, 0)], [RED, GREEN, BLUE]);
+	}
+
+	#[test]
+	fn cells_are_labelled_with_their_digits() {
+		let mut labelled = images(12, &[RED, RED]);
+		labelled[0].1 = 1;
+		labelled[1].1 = 10;
+
+		let sheet = contact_sheet(&labelled, 2, true, GRAY).unwrap();
+
+		// The label box starts one pixel in, with one pixel of padding around the digits.
+		assert_eq!([sheet.pixel(0, 0), sheet.pixel(1, 1), sheet.pixel(5, 7), sheet.pixel(6, 1)], [RED, BLACK, BLACK, RED]);
+
+		// The top row of a 1 only has its middle pixel set.
+		assert_eq!([sheet.pixel(2, 2), sheet.pixel(3, 2), sheet.pixel(4, 2)], [BLACK, WHITE, BLACK]);
+
+		// The second cell reads 10: the 1, then the 0, whose top row is full and whose second row is hollow.
+		let (x, y) = (12 + 1, 1);
+		assert_eq!(sheet.pixel(x + 2, y + 1), WHITE);
+		assert_eq!([sheet.pixel(x + 5, y + 1), sheet.pixel(x + 6, y + 1), sheet.pixel(x + 7, y + 1)], [WHITE, WHITE, WHITE]);
+		assert_eq!([sheet.pixel(x + 5, y + 2), sheet.pixel(x + 6, y + 2), sheet.pixel(x + 7, y + 2)], [WHITE, BLACK, WHITE]);
+	}
+
+	#[test]
+	fn oversized_sheets_are_rejected() {
+		let wide = vec![(Image::new(65536, 1, RED).unwrap(), 0)];
+
+		let error = contact_sheet(&wide, 65537, false, GRAY).unwrap_err();
+		assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+		assert_eq!(contact_sheet(&[], 1, false, GRAY).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+	}
+}
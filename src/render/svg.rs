@@ -0,0 +1,290 @@
+//! The drawing is scaled to fit the image, keeping its proportions. Flat shading sorts triangles from back to
+//! front and draws each one whole, so triangles that cut through each other may overlap incorrectly.
+
+use std::io::{self, Write};
+use cgmath::{Point2, Point3, Matrix4, SquareMatrix, InnerSpace, Transform};
+use collada::escape;
+use v2::V2;
+use super::{View, material_color};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Style {
+	/// The outline of every triangle.
+	Wireframe,
+	/// Filled triangles, shaded by how directly they face the viewer.
+	Flat
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+	pub view: View,
+	pub style: Style,
+	/// LOD level to draw. Models with fewer levels use their last one.
+	pub lod: usize,
+	/// Apply the frame transform, so that the geometry appears as it does in game.
+	pub apply_transform: bool,
+	/// Size of the image in pixels.
+	pub width: u32,
+	pub height: u32,
+	/// Color of each material, in order. Materials without an entry get a color from the palette.
+	pub colors: Vec<[u8; 3]>,
+	/// Draw tag points as markers labelled with their names.
+	pub tag_points: bool,
+	/// Draw the bounding box of the collider.
+	pub aabb: bool,
+	/// Draw the bounding sphere of the collider.
+	pub sphere: bool
+}
+
+impl Default for RenderOptions {
+	fn default() -> Self {
+		RenderOptions {
+			view: View::default(),
+			style: Style::Wireframe,
+			lod: 0,
+			apply_transform: true,
+			width: 512,
+			height: 512,
+			colors: Vec::new(),
+			tag_points: true,
+			aabb: false,
+			sphere: false
+		}
+	}
+}
+
+const OVERLAY_COLOR: &str = "#808080";
+const TAG_COLOR: &str = "#e00000";
+
+/// Draws one frame of a model as an SVG image.
+pub fn render<W>(w: &mut W, model: &V2, frame: usize, options: &RenderOptions) -> io::Result<()> where W: Write {
+	let frame = model.frames.get(frame).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("The model has no frame {}, only {}", frame, model.frames.len())))?;
+
+	let transform = options.view.matrix() * if options.apply_transform { frame.transform } else { Matrix4::identity() };
+	let points: Vec<Point3<f32>> = frame.vertices.iter().map(|vertex| transform.transform_point(vertex.position)).collect();
+
+	let lod = options.lod.min(model.lod_levels.len().saturating_sub(1));
+	let mut triangles = Vec::new();
+
+	for (i, material) in model.materials.iter().enumerate() {
+		for triangle in model.selection(material, lod) {
+			let corners = [triangle.0 as usize, triangle.1 as usize, triangle.2 as usize];
+
+			if corners.iter().any(|&corner| corner >= points.len()) {
+				return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Material '{}' refers to a vertex that does not exist", material.name)));
+			}
+
+			triangles.push((i, corners));
+		}
+	}
+
+	let aabb = frame.collider.aabb;
+	let corners: Vec<Point3<f32>> = (0..8).map(|i| transform.transform_point(Point3::new(
+		if i & 1 == 0 { aabb.lower.x } else { aabb.upper.x },
+		if i & 2 == 0 { aabb.lower.y } else { aabb.upper.y },
+		if i & 4 == 0 { aabb.lower.z } else { aabb.upper.z }
+	))).collect();
+
+	// The sphere is drawn as a circle, using the average scale of the transform.
+	let center = transform.transform_point(model.center);
+	let radius = frame.collider.radius * (transform.x.truncate().magnitude() + transform.y.truncate().magnitude() + transform.z.truncate().magnitude()) / 3.0;
+
+	let tag_points: Vec<Point3<f32>> = frame.tag_points.iter().map(|&point| transform.transform_point(point)).collect();
+
+	// Everything that is drawn should fit in the image.
+	let mut extent: Vec<Point2<f32>> = points.iter().map(|p| Point2::new(p.x, p.y)).collect();
+
+	if options.tag_points {
+		extent.extend(tag_points.iter().map(|p| Point2::new(p.x, p.y)));
+	}
+
+	if options.aabb {
+		extent.extend(corners.iter().map(|p| Point2::new(p.x, p.y)));
+	}
+
+	if options.sphere {
+		extent.push(Point2::new(center.x - radius, center.y - radius));
+		extent.push(Point2::new(center.x + radius, center.y + radius));
+	}
+
+	let fit = Fit::new(&extent, options.width as f32, options.height as f32);
+
+	writeln!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+	writeln!(w, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">", options.width, options.height)?;
+
+	match options.style {
+		Style::Wireframe => for (i, _) in model.materials.iter().enumerate() {
+			if !triangles.iter().any(|triangle| triangle.0 == i) {
+				continue;
+			}
+
+			writeln!(w, "\t<g fill=\"none\" stroke=\"{}\" stroke-width=\"1\" stroke-linejoin=\"round\">", hex(material_color(&options.colors, i)))?;
+
+			for &(_, corners) in triangles.iter().filter(|triangle| triangle.0 == i) {
+				writeln!(w, "\t\t<polygon points=\"{}\"/>", fit.polygon(&points, &corners))?;
+			}
+
+			writeln!(w, "\t</g>")?;
+		},
+		Style::Flat => {
+			// Painter's algorithm: the viewer looks down -Z, so the triangles with the lowest Z are drawn first.
+			let depth = |corners: &[usize; 3]| corners.iter().map(|&corner| points[corner].z).sum::<f32>();
+			triangles.sort_by(|a, b| depth(&a.1).partial_cmp(&depth(&b.1)).unwrap_or(::std::cmp::Ordering::Equal));
+
+			writeln!(w, "\t<g stroke-width=\"0.5\" stroke-linejoin=\"round\">")?;
+
+			for &(material, corners) in &triangles {
+				let (a, b, c) = (points[corners[0]], points[corners[1]], points[corners[2]]);
+				let normal = (b - a).cross(c - a);
+
+				if normal.magnitude2() == 0.0 {
+					continue;
+				}
+
+				// Either side may face the viewer, since models are not guaranteed to be closed.
+				let light = 0.3 + 0.7 * normal.normalize().z.abs();
+				let color = material_color(&options.colors, material);
+				let color = hex([
+					(color[0] as f32 * light).round() as u8,
+					(color[1] as f32 * light).round() as u8,
+					(color[2] as f32 * light).round() as u8
+				]);
+
+				// The stroke covers the hairline gaps that anti-aliasing leaves between triangles.
+				writeln!(w, "\t\t<polygon points=\"{}\" fill=\"{1}\" stroke=\"{1}\"/>", fit.polygon(&points, &corners), color)?;
+			}
+
+			writeln!(w, "\t</g>")?;
+		}
+	}
+
+	if options.aabb {
+		writeln!(w, "\t<g fill=\"none\" stroke=\"{}\" stroke-dasharray=\"4 2\">", OVERLAY_COLOR)?;
+
+		for i in 0..8 {
+			for &bit in &[1, 2, 4] {
+				if i & bit == 0 {
+					let (a, b) = (fit.apply(corners[i]), fit.apply(corners[i | bit]));
+					writeln!(w, "\t\t<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\"/>", a.x, a.y, b.x, b.y)?;
+				}
+			}
+		}
+
+		writeln!(w, "\t</g>")?;
+	}
+
+	if options.sphere {
+		let c = fit.apply(center);
+		writeln!(w, "\t<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" fill=\"none\" stroke=\"{}\" stroke-dasharray=\"4 2\"/>", c.x, c.y, radius * fit.scale, OVERLAY_COLOR)?;
+	}
+
+	if options.tag_points && !tag_points.is_empty() {
+		writeln!(w, "\t<g font-family=\"sans-serif\" font-size=\"12\" fill=\"{}\">", TAG_COLOR)?;
+
+		for (i, &point) in tag_points.iter().enumerate() {
+			let p = fit.apply(point);
+			let name = model.tag_points.get(i).map(String::as_str).unwrap_or("");
+
+			writeln!(w, "\t\t<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"3\" stroke=\"#ffffff\"/>", p.x, p.y)?;
+			writeln!(w, "\t\t<text x=\"{:.2}\" y=\"{:.2}\">{}</text>", p.x + 5.0, p.y - 5.0, escape(name))?;
+		}
+
+		writeln!(w, "\t</g>")?;
+	}
+
+	writeln!(w, "</svg>")
+}
+
+/// Maps view space to image coordinates, where Y points down.
+struct Fit {
+	scale: f32,
+	center: Point2<f32>,
+	size: Point2<f32>
+}
+
+impl Fit {
+	fn new(points: &[Point2<f32>], width: f32, height: f32) -> Self {
+		let mut lower = Point2::new(0.0f32, 0.0);
+		let mut upper = Point2::new(0.0f32, 0.0);
+
+		for (i, p) in points.iter().enumerate() {
+			if i == 0 {
+				lower = *p;
+				upper = *p;
+			}
+
+			lower = Point2::new(lower.x.min(p.x), lower.y.min(p.y));
+			upper = Point2::new(upper.x.max(p.x), upper.y.max(p.y));
+		}
+
+		let margin = 0.05 * width.min(height);
+		let extent = Point2::new((upper.x - lower.x).max(1e-6), (upper.y - lower.y).max(1e-6));
+
+		Fit {
+			scale: ((width - 2.0 * margin) / extent.x).min((height - 2.0 * margin) / extent.y).max(0.0),
+			center: Point2::new((lower.x + upper.x) / 2.0, (lower.y + upper.y) / 2.0),
+			size: Point2::new(width, height)
+		}
+	}
+
+	fn apply(&self, p: Point3<f32>) -> Point2<f32> {
+		Point2::new(
+			self.size.x / 2.0 + (p.x - self.center.x) * self.scale,
+			self.size.y / 2.0 - (p.y - self.center.y) * self.scale
+		)
+	}
+
+	fn polygon(&self, points: &[Point3<f32>], corners: &[usize; 3]) -> String {
+		corners.iter().map(|&corner| {
+			let p = self.apply(points[corner]);
+			format!("{:.2},{:.2}", p.x, p.y)
+		}).collect::<Vec<_>>().join(" ")
+	}
+}
+
+fn hex(color: [u8; 3]) -> String {
+	format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use samples::v2_scene;
+	use super::{RenderOptions, Style, render};
+
+	fn options() -> RenderOptions {
+		// The sample transform is not affine.
+		RenderOptions { apply_transform: false, ..RenderOptions::default() }
+	}
+
+	#[test]
+	fn tag_labels_are_escaped() {
+		let mut model = v2_scene().model;
+		model.tag_points[0] = "<fire & \"smoke\">".to_string();
+
+		let mut out = Vec::new();
+		render(&mut out, &model, 0, &options()).unwrap();
+
+		let svg = String::from_utf8(out).unwrap();
+
+		assert!(svg.contains(">&lt;fire &amp; &quot;smoke&quot;&gt;</text>"));
+		assert!(!svg.contains("<fire"));
+	}
+
+	#[test]
+	fn out_of_range_vertices_are_invalid_data() {
+		let mut model = v2_scene().model;
+		model.lod_levels[0][1].2 = 4;
+
+		for &style in &[Style::Wireframe, Style::Flat] {
+			let error = render(&mut Vec::new(), &model, 0, &RenderOptions { style, ..options() }).unwrap_err();
+			assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+		}
+	}
+
+	#[test]
+	fn missing_frames_are_invalid_input() {
+		let error = render(&mut Vec::new(), &v2_scene().model, 1, &options()).unwrap_err();
+		assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+	}
+}
@@ -0,0 +1,108 @@
+//! Small scenes covering every field, for tests.
+
+use cgmath::{Point2, Point3, Vector3, Matrix4, SquareMatrix};
+use collider::Aabb;
+use scene::Scene;
+use v1::{self, V1};
+use v2::{self, V2};
+
+/// A transform whose components do not survive a decimal round trip.
+pub fn awkward_transform() -> Matrix4<f32> {
+	let mut transform = Matrix4::identity();
+
+	transform.x.w = f32::from_bits(0x7fc0_1234);
+	transform.y.z = -0.0;
+	transform.w.x = 1e-45;
+	transform
+}
+
+/// A V2 scene with a child node and trailing data.
+pub fn v2_scene() -> Scene<V2> {
+	let vertices = (0..4).map(|i| v2::Vertex {
+		position: Point3::new(i as f32, 0.1 * i as f32, -1.0),
+		normal: Vector3::new(0.0, 0.0, 1.0),
+		texture: Point2::new(0.5, i as f32 / 3.0)
+	}).collect();
+
+	let mut frame = v2::Frame::from_vertices(vertices, vec![Point3::new(1.0, 2.0, 3.0)], Point3::new(1.5, 0.0, -1.0));
+	frame.transform = awkward_transform();
+
+	let mut scene = Scene::root(V2 {
+		center: Point3::new(1.5, 0.0, -1.0),
+		lod_levels: vec![vec![(0, 1, 2), (1, 2, 3)], vec![(0, 1, 2)]],
+		materials: vec![v2::Material {
+			name: "ma\"t \\ \u{e9}\u{1}".to_string(),
+			texture: 7,
+			triangles: vec![v2::TriangleSelection { offset: 0, len: 2 }, v2::TriangleSelection { offset: 0, len: 1 }],
+			vertex_offset: 0,
+			vertex_count: 4,
+			texture_name: "tex # not a comment".to_string()
+		}],
+		tag_points: vec!["tag".to_string()],
+		frames: vec![frame]
+	});
+
+	scene.children.push(Scene::single("child".to_string(), V2 {
+		center: Point3::new(0.0, 0.0, 0.0),
+		lod_levels: vec![Vec::new()],
+		materials: vec![v2::Material {
+			name: String::new(),
+			texture: 0,
+			triangles: vec![v2::TriangleSelection { offset: 0, len: 0 }],
+			vertex_offset: 0,
+			vertex_count: 0,
+			texture_name: String::new()
+		}],
+		tag_points: Vec::new(),
+		frames: vec![v2::Frame::from_vertices(Vec::new(), Vec::new(), Point3::new(0.0, 0.0, 0.0))]
+	}));
+
+	scene.trailing = vec![0x00, 0xff, 0x10];
+	scene
+}
+
+/// A V1 scene with trailing data.
+pub fn v1_scene() -> Scene<V1> {
+	let vertex = |i: u32| v1::Vertex {
+		unknown0: i,
+		uv: (0.25, 1.0 / 3.0),
+		rgb: (1.0, 0.5, f32::from_bits(0xffc0_0001)),
+		unknown1: [0.0, -0.0, 1.0, 2.0]
+	};
+
+	let model = V1 {
+		quantities: v1::Quantities {
+			frames: 1,
+			materials: 2,
+			vertex_points: 2,
+			triangles: 1,
+			triangle_groups: 1,
+			vertices: 3,
+			tag_points: 1,
+			additional_models: 0
+		},
+		center: Point3::new(0.5, 0.25, 0.0),
+		unknown: 3,
+		points: vec![0, 1],
+		triangles: vec![(vertex(0), vertex(1), vertex(2))],
+		triangle_groups: vec![v1::TriangleGroup { name: "group".to_string(), indices: vec![0] }],
+		materials: vec![
+			v1::Material { indices: vec![0, 1, 2], texture: Some(("stone".to_string(), 9)) },
+			v1::Material { indices: Vec::new(), texture: None }
+		],
+		vertices: vec![(0, 0.5), (1, 1.0), (2, 0.1)],
+		tag_points: vec!["weapon".to_string()],
+		frames: vec![v1::Frame {
+			radius: 2.0,
+			points: vec![Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0)],
+			normals: vec![0, 10085, 42],
+			tag_points: vec![Point3::new(0.0, 1.0, 0.0)],
+			transform: awkward_transform(),
+			bound: Aabb { lower: Point3::new(0.0, 0.0, 0.0), upper: Point3::new(1.0, 1.0, 1.0) }
+		}]
+	};
+
+	let mut scene = Scene::root(model);
+	scene.trailing = vec![1, 2, 3];
+	scene
+}
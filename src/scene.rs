@@ -7,10 +7,16 @@ pub struct NodeData<'a> {
 	pub name: Cow<'a, str>
 }
 
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Scene<M: Model> {
 	pub name:     String,
 	pub model:    M,
-	pub children: Vec<Scene<M>>
+	pub children: Vec<Scene<M>>,
+	/// Any bytes found after the end of the scene by `read_with_trailing`, written back out after it. Only used on the
+	/// root node.
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub trailing: Vec<u8>
 }
 
 impl<M: Model> Scene<M> {
@@ -18,7 +24,8 @@ impl<M: Model> Scene<M> {
 		Scene {
 			name: "Scene Root".to_string(),
 			model,
-			children: Vec::new()
+			children: Vec::new(),
+			trailing: Vec::new()
 		}
 	}
 
@@ -26,21 +33,48 @@ impl<M: Model> Scene<M> {
 		Scene {
 			name,
 			model,
-			children: Vec::new()
+			children: Vec::new(),
+			trailing: Vec::new()
 		}
 	}
 
+	/// Reads a scene and all of its children, stopping at the end of the last child. Anything after that is left in
+	/// the reader, so `trailing` is always empty.
 	pub fn read<R>(r: &mut R) -> io::Result<Self> where R: Read {
+		Self::read_header(r)?;
+		Self::read_without_header(r)
+	}
+
+	pub fn read_without_header<R>(r: &mut R) -> io::Result<Self> where R: Read {
+		Self::read_node(r)
+	}
+
+	/// Reads a scene like `read`, then consumes the rest of the reader into `trailing`, so that writing the scene
+	/// back out reproduces the whole file. Only use this when the scene is the last thing in the reader.
+	pub fn read_with_trailing<R>(r: &mut R) -> io::Result<Self> where R: Read {
+		let mut scene = Self::read(r)?;
+
+		scene.read_trailing(r)?;
+
+		Ok(scene)
+	}
+
+	/// Appends everything left in the reader to `trailing`.
+	pub fn read_trailing<R>(&mut self, r: &mut R) -> io::Result<()> where R: Read {
+		r.read_to_end(&mut self.trailing).map(|_| ())
+	}
+
+	fn read_header<R>(r: &mut R) -> io::Result<()> where R: Read {
 		let header = ModelHeader::read(r)?;
 
 		if header != M::HEADER {
 			return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Wrong model header: expected {:?}, got {:?}", M::HEADER, header)));
 		}
 
-		Self::read_without_header(r)
+		Ok(())
 	}
 
-	pub fn read_without_header<R>(r: &mut R) -> io::Result<Self> where R: Read {
+	fn read_node<R>(r: &mut R) -> io::Result<Self> where R: Read {
 		let (mut scene, additional_models) = {
 			let (model, node) = M::read(r)?;
 
@@ -50,13 +84,25 @@ impl<M: Model> Scene<M> {
 		};
 
 		for _ in 0..additional_models {
-			scene.children.push(Scene::read(r)?);
+			Self::read_header(r)?;
+			scene.children.push(Self::read_node(r)?);
 		}
 
 		Ok(scene)
 	}
 
+	/// Returns true if data was found after the end of the scene when it was read.
+	pub fn has_trailing(&self) -> bool {
+		!self.trailing.is_empty()
+	}
+
 	pub fn write<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
+		self.write_node(w)?;
+
+		w.write_all(&self.trailing)
+	}
+
+	fn write_node<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
 		let node = NodeData {
 			additional_models: self.children.len() as u32,
 			name: Cow::Borrowed(&self.name)
@@ -66,7 +112,7 @@ impl<M: Model> Scene<M> {
 		self.model.write(w, node)?;
 
 		for child in &self.children {
-			child.write(w)?;
+			child.write_node(w)?;
 		}
 
 		Ok(())
@@ -76,7 +122,7 @@ impl<M: Model> Scene<M> {
 pub trait Model: Sized {
 	const HEADER: ModelHeader;
 
-	fn read<R>(r: &mut R) -> io::Result<(Self, NodeData)> where R: Read;
+	fn read<R>(r: &mut R) -> io::Result<(Self, NodeData<'_>)> where R: Read;
 	fn write<W>(&self, w: &mut W, data: NodeData) -> io::Result<()> where W: Write;
 }
 
@@ -101,4 +147,62 @@ impl SceneNode {
 		string::write_string_iso(w, &self.name)?;
 		self.center.write(w)
 	}
-}*/
\ No newline at end of file
+}*/
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Cursor, Read};
+	use samples::v2_scene;
+	use V2;
+	use super::Scene;
+
+	#[test]
+	fn trailing_data_survives_a_round_trip() {
+		let mut data = Vec::new();
+		v2_scene().write(&mut data).unwrap();
+
+		let scene = Scene::<V2>::read_with_trailing(&mut Cursor::new(&data)).unwrap();
+		let mut written = Vec::new();
+		scene.write(&mut written).unwrap();
+
+		assert_eq!(scene.trailing, v2_scene().trailing);
+		assert_eq!(written, data);
+	}
+
+	#[test]
+	fn read_stops_at_the_end_of_the_scene() {
+		let mut data = Vec::new();
+		v2_scene().write(&mut data).unwrap();
+
+		let mut cursor = Cursor::new(&data);
+		let scene = Scene::<V2>::read(&mut cursor).unwrap();
+		let mut rest = Vec::new();
+		cursor.read_to_end(&mut rest).unwrap();
+
+		assert!(!scene.has_trailing());
+		assert_eq!(rest, v2_scene().trailing);
+	}
+
+	/// Serializes and deserializes a scene, comparing the CEM files written from both.
+	#[cfg(feature = "serde")]
+	fn assert_serde_round_trip<M>(scene: &Scene<M>) where M: ::Model + ::serde::Serialize + ::serde::de::DeserializeOwned {
+		let serialized = ::bincode::serialize(scene).unwrap();
+		let deserialized: Scene<M> = ::bincode::deserialize(&serialized).unwrap();
+
+		let (mut expected, mut actual) = (Vec::new(), Vec::new());
+		scene.write(&mut expected).unwrap();
+		deserialized.write(&mut actual).unwrap();
+
+		assert_eq!(actual, expected);
+		assert_eq!(deserialized.trailing, scene.trailing);
+	}
+
+	#[test]
+	#[cfg(feature = "serde")]
+	fn serde_round_trip_keeps_every_field() {
+		use samples::v1_scene;
+
+		assert_serde_round_trip(&v2_scene());
+		assert_serde_round_trip(&v1_scene());
+	}
+}
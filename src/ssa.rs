@@ -0,0 +1,261 @@
+//! An archive starts with the `rass` signature, a version and the size of the directory that follows. Each
+//! directory entry holds the length of the name including its terminating NUL, the name, then the absolute
+//! offsets of the first and last byte of the file and the size of the file. Files follow the directory in order.
+//!
+//! Some stock archives compress their files, which are then marked by a `PK01` signature. The compression
+//! method is not known, so such entries are listed but cannot be opened. Archives are always written
+//! uncompressed, which the game reads just as well.
+
+use std::io::{self, Read, Write, Seek, SeekFrom, Take};
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
+
+const MAGIC: &[u8; 4] = b"rass";
+const VERSION: u32 = 1;
+
+/// Marks entries with compressed contents.
+const COMPRESSED_MAGIC: &[u8; 4] = b"PK01";
+
+/// The signature, the version, a field that is always zero, and the directory size.
+const HEADER_SIZE: u32 = 16;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Entry {
+	/// The path of the file within the game data directory, usually with `\` as the separator.
+	pub name: String,
+	/// The absolute offset of the contents.
+	pub offset: u32,
+	pub size: u32
+}
+
+/// An archive open for reading.
+#[derive(Debug)]
+pub struct Archive<R> {
+	reader: R,
+	entries: Vec<Entry>
+}
+
+impl<R> Archive<R> where R: Read + Seek {
+	/// Reads the directory of an archive. The contents are only read when an entry is opened.
+	pub fn read(mut reader: R) -> io::Result<Self> {
+		let mut magic = [0; 4];
+		reader.read_exact(&mut magic)?;
+
+		if &magic != MAGIC {
+			return invalid("Not an SSA archive: missing the rass signature".to_string());
+		}
+
+		let version = reader.read_u32::<LittleEndian>()?;
+
+		if version != VERSION {
+			return invalid(format!("Unsupported SSA version {}, expected {}", version, VERSION));
+		}
+
+		let _zero = reader.read_u32::<LittleEndian>()?;
+		let directory_size = reader.read_u32::<LittleEndian>()? as usize;
+
+		let mut directory = Vec::new();
+		(&mut reader).take(directory_size as u64).read_to_end(&mut directory)?;
+
+		if directory.len() != directory_size {
+			return invalid("The SSA directory extends past the end of the file".to_string());
+		}
+
+		// Entries are checked against the length up front, so that a truncated archive fails here instead of
+		// silently giving short contents.
+		let len = reader.seek(SeekFrom::End(0))?;
+
+		let mut d = &directory[..];
+		let mut entries = Vec::new();
+
+		while !d.is_empty() {
+			let length = d.read_u32::<LittleEndian>()? as usize;
+
+			if length > d.len() {
+				return invalid(format!("SSA entry {} has a name longer than the directory", entries.len()));
+			}
+
+			let (name, rest) = d.split_at(length);
+			d = rest;
+
+			let name = String::from_utf8_lossy(name.split(|&byte| byte == 0).next().unwrap_or(name)).into_owned();
+			let first = d.read_u32::<LittleEndian>()?;
+			let last = d.read_u32::<LittleEndian>()?;
+			let size = d.read_u32::<LittleEndian>()?;
+
+			if size != 0 && last.checked_sub(first).and_then(|span| span.checked_add(1)) != Some(size) {
+				return invalid(format!("SSA entry '{}' covers bytes {} to {} but has a size of {}", name, first, last, size));
+			}
+
+			if first as u64 + size as u64 > len {
+				return invalid(format!("SSA entry '{}' ends at byte {}, past the end of the archive at {}", name, first as u64 + size as u64, len));
+			}
+
+			entries.push(Entry { name, offset: first, size });
+		}
+
+		Ok(Archive { reader, entries })
+	}
+
+	pub fn entries(&self) -> &[Entry] {
+		&self.entries
+	}
+
+	/// Finds an entry by name, ignoring case and the kind of path separator.
+	pub fn find(&self, name: &str) -> Option<&Entry> {
+		let name = normalize(name);
+
+		self.entries.iter().find(|entry| normalize(&entry.name) == name)
+	}
+
+	/// Opens the contents of an entry as a stream, for example to pass to `Scene::read`.
+	///
+	/// Compressed entries, which start with `PK01`, cannot be opened: they fail with `InvalidData`, since
+	/// the compression method is not known. They are still listed by `entries` and found by `find`.
+	pub fn open(&mut self, name: &str) -> io::Result<Take<&mut R>> {
+		let entry = match self.find(name) {
+			Some(entry) => entry.clone(),
+			None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("No entry named '{}' in the SSA archive", name)))
+		};
+
+		self.reader.seek(SeekFrom::Start(entry.offset as u64))?;
+
+		if entry.size >= 4 {
+			let mut magic = [0; 4];
+			self.reader.read_exact(&mut magic)?;
+
+			if &magic == COMPRESSED_MAGIC {
+				return Err(io::Error::new(io::ErrorKind::InvalidData, format!("SSA entry '{}' is compressed, which is not supported", entry.name)));
+			}
+
+			self.reader.seek(SeekFrom::Start(entry.offset as u64))?;
+		}
+
+		Ok((&mut self.reader).take(entry.size as u64))
+	}
+
+	/// Reads the whole contents of an entry. Fails for compressed entries, like `open`.
+	pub fn read_entry(&mut self, name: &str) -> io::Result<Vec<u8>> {
+		let mut data = Vec::new();
+		self.open(name)?.read_to_end(&mut data)?;
+
+		Ok(data)
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+}
+
+/// Writes an archive holding the given files, in order. Names are written as they are given.
+pub fn write<W>(w: &mut W, files: &[(&str, &[u8])]) -> io::Result<()> where W: Write {
+	let mut directory_size = 0u64;
+
+	for &(name, _) in files {
+		if name.contains('\0') {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("SSA entry name '{}' contains a NUL", name.escape_default())));
+		}
+
+		directory_size += 4 + name.len() as u64 + 1 + 12;
+	}
+
+	let total = files.iter().fold(HEADER_SIZE as u64 + directory_size, |total, &(_, data)| total + data.len() as u64);
+
+	if total > u32::MAX as u64 {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "The SSA archive would be larger than 4 GiB"));
+	}
+
+	w.write_all(MAGIC)?;
+	w.write_u32::<LittleEndian>(VERSION)?;
+	w.write_u32::<LittleEndian>(0)?;
+	w.write_u32::<LittleEndian>(directory_size as u32)?;
+
+	let mut offset = HEADER_SIZE + directory_size as u32;
+
+	for &(name, data) in files {
+		let size = data.len() as u32;
+
+		w.write_u32::<LittleEndian>(name.len() as u32 + 1)?;
+		w.write_all(name.as_bytes())?;
+		w.write_u8(0)?;
+
+		// Empty files have no last byte, so they point at their own offset.
+		w.write_u32::<LittleEndian>(offset)?;
+		w.write_u32::<LittleEndian>(offset + size.saturating_sub(1))?;
+		w.write_u32::<LittleEndian>(size)?;
+
+		offset += size;
+	}
+
+	for &(_, data) in files {
+		w.write_all(data)?;
+	}
+
+	Ok(())
+}
+
+fn normalize(name: &str) -> String {
+	name.trim().replace('/', "\\").to_lowercase()
+}
+
+fn invalid<T>(message: String) -> io::Result<T> {
+	Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{self, Cursor};
+	use byteorder::{ByteOrder, LittleEndian};
+	use super::{Archive, write, HEADER_SIZE};
+
+	fn archive() -> Vec<u8> {
+		let mut data = Vec::new();
+		write(&mut data, &[("models\\a.cem", b"abc"), ("empty", b""), ("b.tga", b"defg")]).unwrap();
+		data
+	}
+
+	#[test]
+	fn entries_round_trip() {
+		let mut archive = Archive::read(Cursor::new(archive())).unwrap();
+
+		assert_eq!(archive.entries().len(), 3);
+		assert_eq!(archive.read_entry("MODELS/A.cem").unwrap(), b"abc");
+		assert_eq!(archive.read_entry("empty").unwrap(), b"");
+		assert_eq!(archive.read_entry("b.tga").unwrap(), b"defg");
+	}
+
+	#[test]
+	fn compressed_entries_are_listed_but_not_opened() {
+		let mut data = Vec::new();
+		write(&mut data, &[("packed.cem", b"PK01\x10\0\0\0compressed"), ("PK01", b"PK0")]).unwrap();
+
+		let mut archive = Archive::read(Cursor::new(data)).unwrap();
+		assert!(archive.find("packed.cem").is_some());
+
+		let error = archive.open("packed.cem").unwrap_err();
+		assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+		assert!(error.to_string().contains("compressed"), "{}", error);
+
+		// Too short to hold the signature, so only the name looks compressed.
+		assert_eq!(archive.read_entry("PK01").unwrap(), b"PK0");
+	}
+
+	#[test]
+	fn truncated_archive_is_rejected() {
+		let mut data = archive();
+		data.pop();
+
+		assert!(Archive::read(Cursor::new(data)).is_err());
+	}
+
+	#[test]
+	fn span_of_the_whole_address_space_is_rejected() {
+		let mut data = archive();
+
+		// The first entry's first and last byte follow its 4 byte name length and its name with the NUL.
+		let at = HEADER_SIZE as usize + 4 + "models\\a.cem".len() + 1;
+		LittleEndian::write_u32(&mut data[at..], 0);
+		LittleEndian::write_u32(&mut data[at + 4..], u32::MAX);
+
+		assert!(Archive::read(Cursor::new(data)).is_err());
+	}
+}
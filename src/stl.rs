@@ -0,0 +1,171 @@
+//! STL stores nothing but triangles, each with its own copy of its corner positions and a facet normal.
+//! Slicers treat a mesh as watertight when neighbouring triangles share exactly the same corner positions,
+//! but V2 models split vertices along texture seams and hard edges. Positions closer together than the
+//! weld tolerance are therefore snapped to one position, and triangles that collapse as a result are dropped.
+
+use std::io::{self, Write};
+use std::collections::HashMap;
+use byteorder::{WriteBytesExt, LittleEndian};
+use cgmath::{Point3, Vector3, Matrix4, SquareMatrix, InnerSpace, Transform};
+use v2::V2;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ExportOptions {
+	/// LOD level to write. Models with fewer levels use their last one.
+	pub lod: usize,
+	/// Apply the frame transform to positions, so that the geometry appears as it does in game.
+	pub apply_transform: bool,
+	/// Positions that round to the same multiple of this distance are welded together. Zero welds only identical positions.
+	pub weld_tolerance: f32
+}
+
+impl Default for ExportOptions {
+	fn default() -> Self {
+		ExportOptions {
+			lod: 0,
+			apply_transform: true,
+			weld_tolerance: 1e-5
+		}
+	}
+}
+
+/// Writes one frame of a model as a binary STL file.
+pub fn export<W>(w: &mut W, model: &V2, frame: usize, options: &ExportOptions) -> io::Result<()> where W: Write {
+	let triangles = weld(model, frame, options)?;
+
+	// The header must not start with "solid", or readers may take the file for ASCII STL.
+	let mut header = [0; 80];
+	let title = b"Empire Earth model";
+	header[..title.len()].copy_from_slice(title);
+
+	w.write_all(&header)?;
+	w.write_u32::<LittleEndian>(triangles.len() as u32)?;
+
+	for triangle in &triangles {
+		let normal = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]).normalize();
+
+		for &value in &[normal.x, normal.y, normal.z] {
+			w.write_f32::<LittleEndian>(value)?;
+		}
+
+		for corner in triangle {
+			w.write_f32::<LittleEndian>(corner.x)?;
+			w.write_f32::<LittleEndian>(corner.y)?;
+			w.write_f32::<LittleEndian>(corner.z)?;
+		}
+
+		// Attribute byte count, which has no agreed meaning.
+		w.write_u16::<LittleEndian>(0)?;
+	}
+
+	Ok(())
+}
+
+/// Returns the corner positions of every triangle after welding, leaving out triangles without area.
+fn weld(model: &V2, frame: usize, options: &ExportOptions) -> io::Result<Vec<[Point3<f32>; 3]>> {
+	let frame = model.frames.get(frame).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("The model has no frame {}, only {}", frame, model.frames.len())))?;
+	let transform = if options.apply_transform { frame.transform } else { Matrix4::identity() };
+
+	// The first position snapped to each cell stands in for every position in that cell.
+	let mut cells = HashMap::new();
+
+	let welded: Vec<Point3<f32>> = frame.vertices.iter().map(|vertex| {
+		let p = transform.transform_point(vertex.position);
+
+		let key = if options.weld_tolerance > 0.0 {
+			let cell = |c: f32| (c / options.weld_tolerance).round() as i64;
+			(cell(p.x), cell(p.y), cell(p.z))
+		} else {
+			// Adding zero turns negative zero into positive zero, so that both weld together.
+			((p.x + 0.0).to_bits() as i64, (p.y + 0.0).to_bits() as i64, (p.z + 0.0).to_bits() as i64)
+		};
+
+		*cells.entry(key).or_insert(p)
+	}).collect();
+
+	let lod = options.lod.min(model.lod_levels.len().saturating_sub(1));
+	let mut triangles = Vec::new();
+
+	for material in &model.materials {
+		for triangle in model.selection(material, lod) {
+			let corners = [triangle.0 as usize, triangle.1 as usize, triangle.2 as usize];
+
+			if corners.iter().any(|&corner| corner >= welded.len()) {
+				return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Material '{}' refers to a vertex that does not exist", material.name)));
+			}
+
+			let triangle = [welded[corners[0]], welded[corners[1]], welded[corners[2]]];
+			let area: Vector3<f32> = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]);
+
+			if area.magnitude2() > 0.0 {
+				triangles.push(triangle);
+			}
+		}
+	}
+
+	Ok(triangles)
+}
+
+#[cfg(test)]
+mod tests {
+	use byteorder::{ReadBytesExt, LittleEndian};
+	use cgmath::{Point2, Point3, Vector3};
+	use v2::{V2, Frame, Material, TriangleSelection, Vertex};
+	use super::{ExportOptions, export, weld};
+
+	/// Two triangles sharing an edge, where the last vertex is a near copy of the first along a seam.
+	fn seam() -> V2 {
+		let positions = [Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0), Point3::new(1e-6, 0.0, 0.0)];
+
+		let vertices = positions.iter().map(|&position| Vertex {
+			position,
+			normal: Vector3::new(0.0, 0.0, 1.0),
+			texture: Point2::new(0.0, 0.0)
+		}).collect();
+
+		V2 {
+			center: Point3::new(0.5, 0.5, 0.0),
+			// The last triangle only has area while the seam is open.
+			lod_levels: vec![vec![(0, 1, 2), (3, 1, 2), (0, 3, 2)]],
+			materials: vec![Material {
+				name: "stone".to_string(),
+				texture: 0,
+				triangles: vec![TriangleSelection { offset: 0, len: 3 }],
+				vertex_offset: 0,
+				vertex_count: 4,
+				texture_name: String::new()
+			}],
+			tag_points: Vec::new(),
+			frames: vec![Frame::from_vertices(vertices, Vec::new(), Point3::new(0.5, 0.5, 0.0))]
+		}
+	}
+
+	#[test]
+	fn close_positions_are_welded_and_degenerate_triangles_dropped() {
+		let triangles = weld(&seam(), 0, &ExportOptions::default()).unwrap();
+
+		assert_eq!(triangles.len(), 2);
+		assert_eq!(triangles[1][0], Point3::new(0.0, 0.0, 0.0));
+	}
+
+	#[test]
+	fn zero_tolerance_keeps_distinct_positions() {
+		let triangles = weld(&seam(), 0, &ExportOptions { weld_tolerance: 0.0, ..ExportOptions::default() }).unwrap();
+
+		assert_eq!(triangles.len(), 3);
+		assert_eq!(triangles[1][0], Point3::new(1e-6, 0.0, 0.0));
+	}
+
+	#[test]
+	fn binary_file_counts_the_welded_triangles() {
+		let mut out = Vec::new();
+		export(&mut out, &seam(), 0, &ExportOptions::default()).unwrap();
+
+		assert_eq!(out.len(), 80 + 4 + 2 * 50);
+		assert!(!out.starts_with(b"solid"));
+		assert_eq!((&out[80..]).read_u32::<LittleEndian>().unwrap(), 2);
+
+		let normal: Vec<f32> = (0..3).map(|i| (&out[84 + 4 * i..]).read_f32::<LittleEndian>().unwrap()).collect();
+		assert_eq!(normal, [0.0, 0.0, 1.0]);
+	}
+}
@@ -0,0 +1,154 @@
+//! A model stores the names of its tag points once, and the position of each in every frame, in the same
+//! order. `TagPoint` joins the two, so that a tag point can be followed through an animation by name.
+//!
+//! Tag point names are written by hand and are not consistent between models, so they are classified by the
+//! words they contain, such as `weapon` in `tag_weapon01`.
+
+use cgmath::{Point3, Transform};
+use v2::V2;
+
+/// What a tag point is used for, going by its name.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Kind {
+	/// Where a held weapon is attached, or where projectiles start.
+	Weapon,
+	/// Where fire is drawn on damaged buildings and units.
+	Fire,
+	/// Where a flag or banner is attached.
+	Flag,
+	/// Where smoke is emitted, as from chimneys and exhausts.
+	Smoke,
+	/// A name with none of the known words.
+	Other
+}
+
+impl Kind {
+	/// Classifies a tag point name, ignoring case.
+	pub fn classify(name: &str) -> Self {
+		let name = name.to_lowercase();
+
+		// Checked in order, so that `weapon fire` is a weapon and not fire.
+		let words: [(&[&str], Kind); 4] = [
+			(&["weapon", "muzzle", "projectile", "gun"], Kind::Weapon),
+			(&["flag", "banner"], Kind::Flag),
+			(&["smoke", "exhaust", "chimney"], Kind::Smoke),
+			(&["fire", "flame", "burn"], Kind::Fire)
+		];
+
+		words.iter()
+			.find(|&&(words, _)| words.iter().any(|word| name.contains(word)))
+			.map(|&(_, kind)| kind)
+			.unwrap_or(Kind::Other)
+	}
+}
+
+/// A tag point of a model, across all of its frames.
+#[derive(Debug, Copy, Clone)]
+pub struct TagPoint<'m> {
+	model: &'m V2,
+	/// The position of the tag point in `V2::tag_points` and in the tag points of each frame.
+	pub index: usize,
+	pub name: &'m str
+}
+
+impl<'m> TagPoint<'m> {
+	/// Every tag point of a model, in order.
+	pub fn all(model: &'m V2) -> Vec<Self> {
+		model.tag_points.iter().enumerate().map(|(index, name)| TagPoint { model, index, name }).collect()
+	}
+
+	/// Finds a tag point by name. An exact match is preferred over one that differs only in case.
+	pub fn find(model: &'m V2, name: &str) -> Option<Self> {
+		let index = model.tag_points.iter().position(|tag_point| tag_point == name)
+			.or_else(|| model.tag_points.iter().position(|tag_point| tag_point.eq_ignore_ascii_case(name)))?;
+
+		Some(TagPoint { model, index, name: &model.tag_points[index] })
+	}
+
+	pub fn kind(&self) -> Kind {
+		Kind::classify(self.name)
+	}
+
+	/// The position of the tag point in a frame, as stored, without the frame transform.
+	pub fn position(&self, frame: usize) -> Option<Point3<f32>> {
+		self.model.frames.get(frame).and_then(|frame| frame.tag_points.get(self.index)).cloned()
+	}
+
+	/// The position of the tag point in a frame with the frame transform applied, which is where it is drawn.
+	pub fn transformed_position(&self, frame: usize) -> Option<Point3<f32>> {
+		let transform = self.model.frames.get(frame)?.transform;
+
+		self.position(frame).map(|position| transform.transform_point(position))
+	}
+
+	/// The transformed position of the tag point in every frame. Returns `None` if a frame does not have the tag
+	/// point, which only happens in models that were not read from a file.
+	pub fn trajectory(&self) -> Option<Vec<Point3<f32>>> {
+		(0..self.model.frames.len()).map(|frame| self.transformed_position(frame)).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Point3, Vector3, Matrix4};
+	use samples::v2_scene;
+	use v2::{V2, Frame};
+	use super::{Kind, TagPoint};
+
+	/// Two frames, where the second is moved and has lost the `fire` tag point.
+	fn model() -> V2 {
+		let mut model = v2_scene().model;
+		model.tag_points = vec!["Fire".to_string(), "fire".to_string()];
+
+		let vertices = model.frames[0].vertices.clone();
+		let mut first = Frame::from_vertices(vertices.clone(), vec![Point3::new(1.0, 2.0, 3.0), Point3::new(4.0, 5.0, 6.0)], model.center);
+		let mut second = Frame::from_vertices(vertices, vec![Point3::new(0.0, 0.0, 0.0)], model.center);
+
+		first.transform = Matrix4::from_translation(Vector3::new(10.0, 0.0, 0.0));
+		second.transform = Matrix4::from_scale(2.0);
+
+		model.frames = vec![first, second];
+		model
+	}
+
+	#[test]
+	fn find_prefers_an_exact_match() {
+		let model = model();
+
+		assert_eq!(TagPoint::find(&model, "fire").map(|tag| tag.index), Some(1));
+		assert_eq!(TagPoint::find(&model, "Fire").map(|tag| tag.index), Some(0));
+		assert_eq!(TagPoint::find(&model, "FIRE").map(|tag| tag.name), Some("Fire"));
+		assert!(TagPoint::find(&model, "smoke").is_none());
+	}
+
+	#[test]
+	fn positions_follow_the_frame_transform() {
+		let model = model();
+		let tag = TagPoint::find(&model, "Fire").unwrap();
+
+		assert_eq!(tag.position(0), Some(Point3::new(1.0, 2.0, 3.0)));
+		assert_eq!(tag.transformed_position(0), Some(Point3::new(11.0, 2.0, 3.0)));
+		assert_eq!(tag.transformed_position(2), None);
+		assert_eq!(tag.trajectory(), Some(vec![Point3::new(11.0, 2.0, 3.0), Point3::new(0.0, 0.0, 0.0)]));
+	}
+
+	#[test]
+	fn trajectory_needs_the_tag_point_in_every_frame() {
+		let model = model();
+		let tag = TagPoint::find(&model, "fire").unwrap();
+
+		assert_eq!(tag.transformed_position(0), Some(Point3::new(14.0, 5.0, 6.0)));
+		assert_eq!(tag.position(1), None);
+		assert_eq!(tag.trajectory(), None);
+	}
+
+	#[test]
+	fn kinds_are_checked_in_order() {
+		assert_eq!(Kind::classify("tag_Weapon01"), Kind::Weapon);
+		assert_eq!(Kind::classify("weapon fire"), Kind::Weapon);
+		assert_eq!(Kind::classify("flag_fire"), Kind::Flag);
+		assert_eq!(Kind::classify("burning smoke"), Kind::Smoke);
+		assert_eq!(Kind::classify("FLAME2"), Kind::Fire);
+		assert_eq!(Kind::classify("hitpoint"), Kind::Other);
+	}
+}
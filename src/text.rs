@@ -0,0 +1,837 @@
+//! Every line is a keyword followed by its arguments, and indentation is purely cosmetic.
+//! Converting a binary file to text and back produces identical bytes: floats that would not survive
+//! a round trip through their decimal form (such as NaNs) are written as raw bit patterns instead.
+//!
+//! V1 and V2 scenes are supported. V5 scenes are not, since `V5` cannot yet read frames or write anything.
+//!
+//! ```text
+//! cemtxt 1 v2
+//! scene "Scene Root"
+//!     center 0.0 1.5 0.0
+//!     lod
+//!         tri 0 1 2
+//!     material "body"
+//!         ...
+//! end
+//! trailing 00ff
+//! ```
+
+use std::io::{self, BufRead, Write};
+use std::fmt::Write as FmtWrite;
+use cgmath::{Point2, Point3, Vector3, Matrix4};
+use collider::{Aabb, Collider};
+use scene::{Scene, Model};
+use v1::{self, V1};
+use v2::{self, V2};
+
+/// The revision of the text format written by this library.
+pub const VERSION: u32 = 1;
+
+const MAGIC: &str = "cemtxt";
+
+/// Number of bytes of trailing data written on each `trailing` line.
+const TRAILING_LINE: usize = 32;
+
+/// A model that can be represented in the text format.
+pub trait TextModel: Model {
+	/// The keyword identifying this model type in the file header.
+	const KIND: &'static str;
+
+	fn write_text<W>(&self, w: &mut TextWriter<W>) -> io::Result<()> where W: Write;
+	/// Reads the model body, stopping before the first `scene` or `end` line.
+	fn read_text<R>(r: &mut TextReader<R>) -> io::Result<Self> where R: BufRead;
+}
+
+impl<M: TextModel> Scene<M> {
+	pub fn write_text<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
+		let mut w = TextWriter { inner: w, depth: 0 };
+
+		w.line(&format!("{} {} {}", MAGIC, VERSION, M::KIND))?;
+		self.write_text_node(&mut w)?;
+
+		for chunk in self.trailing.chunks(TRAILING_LINE) {
+			let hex: String = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+			w.line(&format!("trailing {}", hex))?;
+		}
+
+		Ok(())
+	}
+
+	fn write_text_node<W>(&self, w: &mut TextWriter<W>) -> io::Result<()> where W: Write {
+		w.line(&format!("scene {}", quote(&self.name)))?;
+		w.depth += 1;
+
+		self.model.write_text(w)?;
+
+		for child in &self.children {
+			child.write_text_node(w)?;
+		}
+
+		w.depth -= 1;
+		w.line("end")
+	}
+
+	pub fn read_text<R>(r: &mut R) -> io::Result<Self> where R: BufRead {
+		let mut r = TextReader { inner: r, line: 0, peeked: None };
+
+		let header = r.expect_line()?;
+		match header.as_slice() {
+			[magic, version, kind] if magic == MAGIC => {
+				if version != &VERSION.to_string() {
+					return Err(r.error(format!("Unsupported text format version {}", version)));
+				}
+
+				if kind != M::KIND {
+					return Err(r.error(format!("Wrong model kind: expected {}, got {}", M::KIND, kind)));
+				}
+			},
+			_ => return Err(r.error(format!("Expected a '{} {} {}' header", MAGIC, VERSION, M::KIND)))
+		}
+
+		let mut scene = Self::read_text_node(&mut r)?;
+
+		while let Some(keyword) = r.peek_keyword()? {
+			if keyword != "trailing" {
+				return Err(r.error(format!("Unexpected keyword '{}' after the end of the root scene", keyword)));
+			}
+
+			let hex = r.expect("trailing", 1)?.remove(0);
+
+			if hex.len() % 2 != 0 {
+				return Err(r.error("Trailing data must have an even number of hex digits".to_string()));
+			}
+
+			for i in (0..hex.len()).step_by(2) {
+				let byte = hex.get(i..i + 2).and_then(|digits| u8::from_str_radix(digits, 16).ok());
+
+				scene.trailing.push(byte.ok_or_else(|| r.error(format!("Invalid trailing data '{}'", hex)))?);
+			}
+		}
+
+		Ok(scene)
+	}
+
+	fn read_text_node<R>(r: &mut TextReader<R>) -> io::Result<Self> where R: BufRead {
+		let line = r.expect_line()?;
+		let name = match line.as_slice() {
+			[keyword, name] if keyword == "scene" => name.clone(),
+			_ => return Err(r.error("Expected a scene line".to_string()))
+		};
+
+		let mut scene = Scene::single(name, M::read_text(r)?);
+
+		loop {
+			let keyword = match r.peek()? {
+				Some(tokens) => tokens[0].clone(),
+				None => return Err(r.error("Unexpected end of file, expected 'end'".to_string()))
+			};
+
+			match keyword.as_str() {
+				"scene" => scene.children.push(Self::read_text_node(r)?),
+				"end" => {
+					r.expect_line()?;
+					return Ok(scene);
+				},
+				other => return Err(r.error(format!("Unexpected keyword '{}'", other)))
+			}
+		}
+	}
+}
+
+/// Writes indented lines of the text format.
+pub struct TextWriter<'w, W: 'w> {
+	inner: &'w mut W,
+	depth: usize
+}
+
+impl<'w, W> TextWriter<'w, W> where W: Write {
+	pub fn line(&mut self, line: &str) -> io::Result<()> {
+		for _ in 0..self.depth {
+			self.inner.write_all(b"\t")?;
+		}
+
+		self.inner.write_all(line.as_bytes())?;
+		self.inner.write_all(b"\n")
+	}
+
+	/// Writes a line that opens a block, increasing the indentation of subsequent lines until `close` is called.
+	pub fn open(&mut self, line: &str) -> io::Result<()> {
+		self.line(line)?;
+		self.depth += 1;
+
+		Ok(())
+	}
+
+	pub fn close(&mut self) {
+		self.depth -= 1;
+	}
+}
+
+/// Reads tokenized lines of the text format, skipping blank lines and comments starting with '#'.
+pub struct TextReader<'r, R: 'r> {
+	inner: &'r mut R,
+	line: usize,
+	peeked: Option<Vec<String>>
+}
+
+impl<'r, R> TextReader<'r, R> where R: BufRead {
+	/// Returns the tokens of the next line without consuming it.
+	pub fn peek(&mut self) -> io::Result<Option<&Vec<String>>> {
+		if self.peeked.is_none() {
+			self.peeked = self.read_line()?;
+		}
+
+		Ok(self.peeked.as_ref())
+	}
+
+	/// Returns the keyword of the next line without consuming it.
+	pub fn peek_keyword(&mut self) -> io::Result<Option<String>> {
+		Ok(self.peek()?.map(|tokens| tokens[0].clone()))
+	}
+
+	pub fn next_line(&mut self) -> io::Result<Option<Vec<String>>> {
+		match self.peeked.take() {
+			Some(tokens) => Ok(Some(tokens)),
+			None => self.read_line()
+		}
+	}
+
+	pub fn expect_line(&mut self) -> io::Result<Vec<String>> {
+		match self.next_line()? {
+			Some(tokens) => Ok(tokens),
+			None => Err(self.error("Unexpected end of file".to_string()))
+		}
+	}
+
+	/// Consumes a line with the given keyword and returns its arguments, however many there are.
+	pub fn expect_any(&mut self, keyword: &str) -> io::Result<Vec<String>> {
+		let mut tokens = self.expect_line()?;
+
+		if tokens[0] != keyword {
+			return Err(self.error(format!("Expected '{}', got '{}'", keyword, tokens[0])));
+		}
+
+		tokens.remove(0);
+		Ok(tokens)
+	}
+
+	/// Consumes a line with the given keyword and returns its arguments, which must number exactly `count`.
+	pub fn expect(&mut self, keyword: &str, count: usize) -> io::Result<Vec<String>> {
+		let mut tokens = self.expect_line()?;
+
+		if tokens[0] != keyword {
+			return Err(self.error(format!("Expected '{}', got '{}'", keyword, tokens[0])));
+		}
+
+		if tokens.len() - 1 != count {
+			return Err(self.error(format!("'{}' takes {} arguments, got {}", keyword, count, tokens.len() - 1)));
+		}
+
+		tokens.remove(0);
+		Ok(tokens)
+	}
+
+	pub fn error(&self, message: String) -> io::Error {
+		io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", self.line, message))
+	}
+
+	pub fn parse_u32(&self, token: &str) -> io::Result<u32> {
+		token.parse().map_err(|_| self.error(format!("Invalid integer '{}'", token)))
+	}
+
+	pub fn parse_f32(&self, token: &str) -> io::Result<f32> {
+		if let Some(hex) = token.strip_prefix("0x") {
+			return u32::from_str_radix(hex, 16).map(f32::from_bits).map_err(|_| self.error(format!("Invalid float bits '{}'", token)));
+		}
+
+		token.parse().map_err(|_| self.error(format!("Invalid float '{}'", token)))
+	}
+
+	pub fn parse_floats(&self, tokens: &[String]) -> io::Result<Vec<f32>> {
+		tokens.iter().map(|token| self.parse_f32(token)).collect()
+	}
+
+	fn read_line(&mut self) -> io::Result<Option<Vec<String>>> {
+		let mut buffer = String::new();
+
+		loop {
+			buffer.clear();
+
+			if self.inner.read_line(&mut buffer)? == 0 {
+				return Ok(None);
+			}
+
+			self.line += 1;
+
+			let tokens = self.tokenize(buffer.trim())?;
+
+			if !tokens.is_empty() {
+				return Ok(Some(tokens));
+			}
+		}
+	}
+
+	fn tokenize(&self, line: &str) -> io::Result<Vec<String>> {
+		let mut tokens = Vec::new();
+		let mut chars = line.chars().peekable();
+
+		while let Some(&c) = chars.peek() {
+			if c.is_whitespace() {
+				chars.next();
+			} else if c == '#' {
+				break;
+			} else if c == '"' {
+				chars.next();
+
+				let mut token = String::new();
+
+				loop {
+					match chars.next() {
+						Some('"') => break,
+						Some('\\') => match chars.next() {
+							Some('"') => token.push('"'),
+							Some('\\') => token.push('\\'),
+							Some('x') => {
+								let hex: String = chars.by_ref().take(2).collect();
+								let byte = u8::from_str_radix(&hex, 16).map_err(|_| self.error(format!("Invalid escape '\\x{}'", hex)))?;
+
+								token.push(byte as char);
+							},
+							Some('u') => {
+								let hex: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+								let char = u32::from_str_radix(&hex, 16).ok().and_then(::std::char::from_u32);
+
+								token.push(char.ok_or_else(|| self.error(format!("Invalid escape '\\u{{{}}}'", hex)))?);
+							},
+							other => return Err(self.error(format!("Invalid escape sequence '\\{}'", other.unwrap_or(' '))))
+						},
+						Some(c) => token.push(c),
+						None => return Err(self.error("Unterminated string".to_string()))
+					}
+				}
+
+				tokens.push(token);
+			} else {
+				let mut token = String::new();
+
+				while let Some(&c) = chars.peek() {
+					if c.is_whitespace() {
+						break;
+					}
+
+					token.push(c);
+					chars.next();
+				}
+
+				tokens.push(token);
+			}
+		}
+
+		Ok(tokens)
+	}
+}
+
+/// Quotes a string, escaping everything outside of printable ASCII.
+pub fn quote(s: &str) -> String {
+	let mut quoted = String::with_capacity(s.len() + 2);
+	quoted.push('"');
+
+	for c in s.chars() {
+		match c {
+			'"' => quoted.push_str("\\\""),
+			'\\' => quoted.push_str("\\\\"),
+			' '..='~' => quoted.push(c),
+			_ if (c as u32) < 0x100 => { let _ = write!(quoted, "\\x{:02x}", c as u32); },
+			_ => { let _ = write!(quoted, "\\u{{{:x}}}", c as u32); }
+		}
+	}
+
+	quoted.push('"');
+	quoted
+}
+
+/// Formats a float as its shortest decimal representation, falling back to the raw bits
+/// if the decimal would not parse back to the exact same value.
+pub fn float(value: f32) -> String {
+	let decimal = format!("{:?}", value);
+
+	match decimal.parse::<f32>() {
+		Ok(parsed) if parsed.to_bits() == value.to_bits() => decimal,
+		_ => format!("0x{:08x}", value.to_bits())
+	}
+}
+
+fn floats(values: &[f32]) -> String {
+	values.iter().map(|&value| float(value)).collect::<Vec<_>>().join(" ")
+}
+
+fn point3(point: Point3<f32>) -> String {
+	floats(&[point.x, point.y, point.z])
+}
+
+/// Lists the matrix components in the same order as they appear in the binary format.
+fn matrix4(matrix: &Matrix4<f32>) -> String {
+	floats(&[
+		matrix.x.x, matrix.y.x, matrix.z.x, matrix.w.x,
+		matrix.x.y, matrix.y.y, matrix.z.y, matrix.w.y,
+		matrix.x.z, matrix.y.z, matrix.z.z, matrix.w.z,
+		matrix.x.w, matrix.y.w, matrix.z.w, matrix.w.w
+	])
+}
+
+impl TextModel for V2 {
+	const KIND: &'static str = "v2";
+
+	fn write_text<W>(&self, w: &mut TextWriter<W>) -> io::Result<()> where W: Write {
+		w.line(&format!("center {}", point3(self.center)))?;
+
+		for triangles in &self.lod_levels {
+			w.open("lod")?;
+
+			for triangle in triangles {
+				w.line(&format!("tri {} {} {}", triangle.0, triangle.1, triangle.2))?;
+			}
+
+			w.close();
+		}
+
+		for material in &self.materials {
+			w.open(&format!("material {}", quote(&material.name)))?;
+			w.line(&format!("texture {}", material.texture))?;
+
+			for selection in &material.triangles {
+				w.line(&format!("selection {} {}", selection.offset, selection.len))?;
+			}
+
+			w.line(&format!("vertices {} {}", material.vertex_offset, material.vertex_count))?;
+			w.line(&format!("texture_name {}", quote(&material.texture_name)))?;
+			w.close();
+		}
+
+		for tag_point in &self.tag_points {
+			w.line(&format!("tag {}", quote(tag_point)))?;
+		}
+
+		for frame in &self.frames {
+			w.open("frame")?;
+			w.line(&format!("radius {}", float(frame.collider.radius)))?;
+
+			for vertex in &frame.vertices {
+				w.line(&format!("vertex {}", floats(&[
+					vertex.position.x, vertex.position.y, vertex.position.z,
+					vertex.normal.x, vertex.normal.y, vertex.normal.z,
+					vertex.texture.x, vertex.texture.y
+				])))?;
+			}
+
+			for tag_point in &frame.tag_points {
+				w.line(&format!("tag_point {}", point3(*tag_point)))?;
+			}
+
+			w.line(&format!("transform {}", matrix4(&frame.transform)))?;
+			w.line(&format!("aabb {} {}", point3(frame.collider.aabb.lower), point3(frame.collider.aabb.upper)))?;
+			w.close();
+		}
+
+		Ok(())
+	}
+
+	fn read_text<R>(r: &mut TextReader<R>) -> io::Result<Self> where R: BufRead {
+		let mut model = V2 {
+			center: read_point3(r, "center")?,
+			lod_levels: Vec::new(),
+			materials: Vec::new(),
+			tag_points: Vec::new(),
+			frames: Vec::new()
+		};
+
+		while let Some(keyword) = r.peek_keyword()? {
+			match keyword.as_str() {
+				"lod" => {
+					r.expect("lod", 0)?;
+
+					let mut triangles = Vec::new();
+					while r.peek_keyword()?.as_deref() == Some("tri") {
+						let tokens = r.expect("tri", 3)?;
+
+						triangles.push((r.parse_u32(&tokens[0])?, r.parse_u32(&tokens[1])?, r.parse_u32(&tokens[2])?));
+					}
+
+					model.lod_levels.push(triangles);
+				},
+				"material" => {
+					let name = r.expect("material", 1)?.remove(0);
+					let texture = r.expect("texture", 1)?;
+					let texture = r.parse_u32(&texture[0])?;
+
+					let mut triangles = Vec::new();
+					while r.peek_keyword()?.as_deref() == Some("selection") {
+						let tokens = r.expect("selection", 2)?;
+
+						triangles.push(v2::TriangleSelection {
+							offset: r.parse_u32(&tokens[0])?,
+							len: r.parse_u32(&tokens[1])?
+						});
+					}
+
+					if triangles.len() != model.lod_levels.len() {
+						return Err(r.error(format!("Material '{}' has {} selections, but the model has {} LOD levels", name, triangles.len(), model.lod_levels.len())));
+					}
+
+					let vertices = r.expect("vertices", 2)?;
+
+					model.materials.push(v2::Material {
+						name,
+						texture,
+						triangles,
+						vertex_offset: r.parse_u32(&vertices[0])?,
+						vertex_count: r.parse_u32(&vertices[1])?,
+						texture_name: r.expect("texture_name", 1)?.remove(0)
+					});
+				},
+				"tag" => model.tag_points.push(r.expect("tag", 1)?.remove(0)),
+				"frame" => {
+					r.expect("frame", 0)?;
+
+					let radius = r.expect("radius", 1)?;
+					let radius = r.parse_f32(&radius[0])?;
+
+					let mut vertices = Vec::new();
+					while r.peek_keyword()?.as_deref() == Some("vertex") {
+						let tokens = r.expect("vertex", 8)?;
+						let v = r.parse_floats(&tokens)?;
+
+						vertices.push(v2::Vertex {
+							position: Point3::new(v[0], v[1], v[2]),
+							normal: Vector3::new(v[3], v[4], v[5]),
+							texture: Point2::new(v[6], v[7])
+						});
+					}
+
+					if let Some(first) = model.frames.first() {
+						if first.vertices.len() != vertices.len() {
+							return Err(r.error(format!("Frame has {} vertices, but the first frame has {}", vertices.len(), first.vertices.len())));
+						}
+					}
+
+					let mut tag_points = Vec::new();
+					while r.peek_keyword()?.as_deref() == Some("tag_point") {
+						tag_points.push(read_point3(r, "tag_point")?);
+					}
+
+					if tag_points.len() != model.tag_points.len() {
+						return Err(r.error(format!("Frame has {} tag point positions, but the model has {} tag points", tag_points.len(), model.tag_points.len())));
+					}
+
+					let transform = read_matrix4(r)?;
+					let aabb = read_aabb(r)?;
+
+					model.frames.push(v2::Frame {
+						vertices,
+						tag_points,
+						transform,
+						collider: Collider { aabb, radius }
+					});
+				},
+				_ => break
+			}
+		}
+
+		Ok(model)
+	}
+}
+
+/// Parses the components written by `matrix4`.
+fn read_matrix4<R>(r: &mut TextReader<R>) -> io::Result<Matrix4<f32>> where R: BufRead {
+	let transform = r.expect("transform", 16)?;
+	let m = r.parse_floats(&transform)?;
+
+	Ok(Matrix4::new(
+		m[0], m[4], m[8], m[12],
+		m[1], m[5], m[9], m[13],
+		m[2], m[6], m[10], m[14],
+		m[3], m[7], m[11], m[15]
+	))
+}
+
+fn read_aabb<R>(r: &mut TextReader<R>) -> io::Result<Aabb> where R: BufRead {
+	let aabb = r.expect("aabb", 6)?;
+	let aabb = r.parse_floats(&aabb)?;
+
+	Ok(Aabb {
+		lower: Point3::new(aabb[0], aabb[1], aabb[2]),
+		upper: Point3::new(aabb[3], aabb[4], aabb[5])
+	})
+}
+
+fn read_point3<R>(r: &mut TextReader<R>, keyword: &str) -> io::Result<Point3<f32>> where R: BufRead {
+	let tokens = r.expect(keyword, 3)?;
+	let p = r.parse_floats(&tokens)?;
+
+	Ok(Point3::new(p[0], p[1], p[2]))
+}
+
+fn read_u32s<R>(r: &mut TextReader<R>, keyword: &str) -> io::Result<Vec<u32>> where R: BufRead {
+	let tokens = r.expect_any(keyword)?;
+
+	tokens.iter().map(|token| r.parse_u32(token)).collect()
+}
+
+fn u32s(values: &[u32]) -> String {
+	values.iter().map(u32::to_string).collect::<Vec<_>>().join(" ")
+}
+
+/// The `quantities` of a V1 model are not written, since they are counted again when the model is written.
+impl TextModel for V1 {
+	const KIND: &'static str = "v1";
+
+	fn write_text<W>(&self, w: &mut TextWriter<W>) -> io::Result<()> where W: Write {
+		w.line(&format!("center {}", point3(self.center)))?;
+		w.line(&format!("unknown {}", self.unknown))?;
+
+		for point in &self.points {
+			w.line(&format!("point {}", point))?;
+		}
+
+		for triangle in &self.triangles {
+			w.open("triangle")?;
+
+			for vertex in &[&triangle.0, &triangle.1, &triangle.2] {
+				w.line(&format!("corner {} {}", vertex.unknown0, floats(&[
+					vertex.uv.0, vertex.uv.1,
+					vertex.rgb.0, vertex.rgb.1, vertex.rgb.2,
+					vertex.unknown1[0], vertex.unknown1[1], vertex.unknown1[2], vertex.unknown1[3]
+				])))?;
+			}
+
+			w.close();
+		}
+
+		for group in &self.triangle_groups {
+			w.open(&format!("group {}", quote(&group.name)))?;
+			w.line(format!("indices {}", u32s(&group.indices)).trim_end())?;
+			w.close();
+		}
+
+		for material in &self.materials {
+			w.open("material")?;
+			w.line(format!("indices {}", u32s(&material.indices)).trim_end())?;
+
+			if let Some((ref name, value)) = material.texture {
+				w.line(&format!("texture {} {}", quote(name), value))?;
+			}
+
+			w.close();
+		}
+
+		for &(index, value) in &self.vertices {
+			w.line(&format!("vertex {} {}", index, float(value)))?;
+		}
+
+		for tag_point in &self.tag_points {
+			w.line(&format!("tag {}", quote(tag_point)))?;
+		}
+
+		for frame in &self.frames {
+			w.open("frame")?;
+			w.line(&format!("radius {}", float(frame.radius)))?;
+
+			for point in &frame.points {
+				w.line(&format!("point {}", point3(*point)))?;
+			}
+
+			for normal in &frame.normals {
+				w.line(&format!("normal {}", normal))?;
+			}
+
+			for tag_point in &frame.tag_points {
+				w.line(&format!("tag_point {}", point3(*tag_point)))?;
+			}
+
+			w.line(&format!("transform {}", matrix4(&frame.transform)))?;
+			w.line(&format!("aabb {} {}", point3(frame.bound.lower), point3(frame.bound.upper)))?;
+			w.close();
+		}
+
+		Ok(())
+	}
+
+	fn read_text<R>(r: &mut TextReader<R>) -> io::Result<Self> where R: BufRead {
+		let center = read_point3(r, "center")?;
+		let unknown = r.expect("unknown", 1)?;
+		let unknown = unknown[0].parse().map_err(|_| r.error(format!("Invalid byte '{}'", unknown[0])))?;
+
+		let mut model = V1 {
+			quantities: v1::Quantities {
+				frames: 0,
+				materials: 0,
+				vertex_points: 0,
+				triangles: 0,
+				triangle_groups: 0,
+				vertices: 0,
+				tag_points: 0,
+				additional_models: 0
+			},
+			center,
+			unknown,
+			points: Vec::new(),
+			triangles: Vec::new(),
+			triangle_groups: Vec::new(),
+			materials: Vec::new(),
+			vertices: Vec::new(),
+			tag_points: Vec::new(),
+			frames: Vec::new()
+		};
+
+		while let Some(keyword) = r.peek_keyword()? {
+			match keyword.as_str() {
+				"point" => {
+					let point = r.expect("point", 1)?;
+					model.points.push(r.parse_u32(&point[0])?);
+				},
+				"triangle" => {
+					r.expect("triangle", 0)?;
+
+					let mut corners = Vec::with_capacity(3);
+					for _ in 0..3 {
+						let tokens = r.expect("corner", 10)?;
+						let v = r.parse_floats(&tokens[1..])?;
+
+						corners.push(v1::Vertex {
+							unknown0: r.parse_u32(&tokens[0])?,
+							uv: (v[0], v[1]),
+							rgb: (v[2], v[3], v[4]),
+							unknown1: [v[5], v[6], v[7], v[8]]
+						});
+					}
+
+					let c = corners.pop().unwrap();
+					let b = corners.pop().unwrap();
+					let a = corners.pop().unwrap();
+
+					model.triangles.push((a, b, c));
+				},
+				"group" => {
+					let name = r.expect("group", 1)?.remove(0);
+
+					model.triangle_groups.push(v1::TriangleGroup {
+						name,
+						indices: read_u32s(r, "indices")?
+					});
+				},
+				"material" => {
+					r.expect("material", 0)?;
+
+					let indices = read_u32s(r, "indices")?;
+					let texture = if r.peek_keyword()?.as_deref() == Some("texture") {
+						let mut tokens = r.expect("texture", 2)?;
+						let value = r.parse_u32(&tokens[1])?;
+
+						Some((tokens.remove(0), value))
+					} else {
+						None
+					};
+
+					model.materials.push(v1::Material { indices, texture });
+				},
+				"vertex" => {
+					let tokens = r.expect("vertex", 2)?;
+
+					model.vertices.push((r.parse_u32(&tokens[0])?, r.parse_f32(&tokens[1])?));
+				},
+				"tag" => model.tag_points.push(r.expect("tag", 1)?.remove(0)),
+				"frame" => {
+					r.expect("frame", 0)?;
+
+					let radius = r.expect("radius", 1)?;
+					let radius = r.parse_f32(&radius[0])?;
+
+					let mut points = Vec::new();
+					while r.peek_keyword()?.as_deref() == Some("point") {
+						points.push(read_point3(r, "point")?);
+					}
+
+					let mut normals = Vec::new();
+					while r.peek_keyword()?.as_deref() == Some("normal") {
+						let normal = r.expect("normal", 1)?;
+
+						normals.push(normal[0].parse().map_err(|_| r.error(format!("Invalid normal index '{}'", normal[0])))?);
+					}
+
+					let mut tag_points = Vec::new();
+					while r.peek_keyword()?.as_deref() == Some("tag_point") {
+						tag_points.push(read_point3(r, "tag_point")?);
+					}
+
+					model.frames.push(v1::Frame {
+						radius,
+						points,
+						normals,
+						tag_points,
+						transform: read_matrix4(r)?,
+						bound: read_aabb(r)?
+					});
+				},
+				_ => break
+			}
+		}
+
+		model.quantities = model.quantities(0).map_err(|e| r.error(e.to_string()))?;
+
+		Ok(model)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+	use scene::Scene;
+	use samples::{v1_scene, v2_scene};
+	use v1::V1;
+	use v2::V2;
+
+	/// Converts binary to text and back, checking that the bytes are unchanged.
+	fn round_trip<M: super::TextModel>(binary: &[u8]) -> String {
+		let scene = Scene::<M>::read_with_trailing(&mut Cursor::new(binary)).unwrap();
+
+		let mut text = Vec::new();
+		scene.write_text(&mut text).unwrap();
+
+		let mut written = Vec::new();
+		Scene::<M>::read_text(&mut &text[..]).unwrap().write(&mut written).unwrap();
+
+		assert_eq!(binary, &written[..]);
+		String::from_utf8(text).unwrap()
+	}
+
+	#[test]
+	fn v2_binary_text_binary_is_identical() {
+		let mut binary = Vec::new();
+		v2_scene().write(&mut binary).unwrap();
+
+		let text = round_trip::<V2>(&binary);
+
+		assert!(text.starts_with("cemtxt 1 v2\n"));
+		assert!(text.contains("0x7fc01234"));
+	}
+
+	#[test]
+	fn v1_binary_text_binary_is_identical() {
+		let mut binary = Vec::new();
+		v1_scene().write(&mut binary).unwrap();
+
+		let text = round_trip::<V1>(&binary);
+
+		assert!(text.starts_with("cemtxt 1 v1\n"));
+		assert!(text.contains("0xffc00001"));
+	}
+
+	#[test]
+	fn wrong_kind_is_rejected() {
+		let mut text = Vec::new();
+		v2_scene().write_text(&mut text).unwrap();
+
+		assert!(Scene::<V1>::read_text(&mut &text[..]).is_err());
+	}
+}
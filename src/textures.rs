@@ -0,0 +1,228 @@
+//! Texture names in models are written by hand and by old tools, so they may differ from the files on disk in
+//! case, in path separators, and in whether they include a directory or an extension. Roots are indexed once
+//! when added, and names are looked up first by their full relative path and then by file name alone, trying
+//! each known extension. Roots added first take priority.
+
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use scene::Scene;
+use v2::V2;
+use image::{Image, tga, dds};
+
+/// Extensions of the texture files used by Empire Earth and Empires: Dawn of the Modern World, in order of preference.
+pub const EXTENSIONS: [&str; 2] = ["tga", "dds"];
+
+#[derive(Debug, Clone)]
+pub struct Resolver {
+	/// Extensions to try, lowercase and without the dot.
+	pub extensions: Vec<String>,
+	/// Files by their lowercase path relative to their root, with `/` as the separator.
+	paths: HashMap<String, PathBuf>,
+	/// Files by their lowercase file name.
+	names: HashMap<String, PathBuf>
+}
+
+/// A material whose texture could not be found.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Missing<'s> {
+	pub node: &'s str,
+	pub material: &'s str,
+	pub texture: &'s str
+}
+
+impl Resolver {
+	pub fn new() -> Self {
+		Resolver {
+			extensions: EXTENSIONS.iter().map(|extension| extension.to_string()).collect(),
+			paths: HashMap::new(),
+			names: HashMap::new()
+		}
+	}
+
+	/// Indexes every file below a data root.
+	pub fn add_root<P>(&mut self, root: P) -> io::Result<()> where P: AsRef<Path> {
+		let root = root.as_ref();
+		let mut directories = vec![root.to_path_buf()];
+
+		while let Some(directory) = directories.pop() {
+			let mut entries = fs::read_dir(&directory)?.collect::<io::Result<Vec<_>>>()?;
+
+			// Sorting keeps the choice between files that differ only in case the same on every run.
+			entries.sort_by_key(|entry| entry.file_name());
+
+			for entry in entries {
+				let path = entry.path();
+
+				if entry.file_type()?.is_dir() {
+					directories.push(path);
+					continue;
+				}
+
+				let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy().replace('\\', "/").to_lowercase();
+				let name = entry.file_name().to_string_lossy().to_lowercase();
+
+				self.paths.entry(relative).or_insert_with(|| path.clone());
+				self.names.entry(name).or_insert(path);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Finds the file for a texture name.
+	pub fn resolve(&self, name: &str) -> Option<&Path> {
+		let name = name.trim().replace('\\', "/").to_lowercase();
+		let name = name.trim_start_matches("./").trim_start_matches('/');
+
+		if name.is_empty() {
+			return None;
+		}
+
+		// Names may leave out the extension, or give one that the file does not have.
+		let stem = match name.rfind('.') {
+			Some(dot) if !name[dot..].contains('/') => &name[..dot],
+			_ => name
+		};
+
+		let mut candidates = vec![name.to_string()];
+		candidates.extend(self.extensions.iter().map(|extension| format!("{}.{}", stem, extension)));
+
+		for candidate in &candidates {
+			if let Some(path) = self.paths.get(candidate) {
+				return Some(path);
+			}
+		}
+
+		candidates.iter()
+			.map(|candidate| candidate.rsplit('/').next().unwrap_or(candidate))
+			.filter_map(|file_name| self.names.get(file_name))
+			.map(PathBuf::as_path)
+			.next()
+	}
+
+	/// Reads the file for a texture name, so that exporters can embed it as it is.
+	pub fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+		match self.resolve(name) {
+			Some(path) => fs::read(path),
+			None => Err(io::Error::new(io::ErrorKind::NotFound, format!("No texture file found for '{}'", name)))
+		}
+	}
+
+	/// Reads and decodes the file for a texture name. DDS files are recognized by their signature and TGA files
+	/// by their extension, since TGA has no signature.
+	pub fn image(&self, name: &str) -> io::Result<Image> {
+		let path = match self.resolve(name) {
+			Some(path) => path,
+			None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("No texture file found for '{}'", name)))
+		};
+
+		let data = fs::read(path)?;
+		let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+
+		if data.starts_with(b"DDS ") {
+			dds::read(&mut &data[..])
+		} else if extension.as_deref() == Some("tga") {
+			tga::read(&mut &data[..])
+		} else {
+			Err(io::Error::new(io::ErrorKind::InvalidData, format!("Cannot decode '{}': unsupported texture format", path.display())))
+		}
+	}
+
+	/// Lists every material in a scene with a texture name that does not resolve. Materials without a texture name are left out.
+	pub fn missing<'s>(&self, scene: &'s Scene<V2>) -> Vec<Missing<'s>> {
+		let mut missing = Vec::new();
+		self.collect_missing(scene, &mut missing);
+
+		missing
+	}
+
+	fn collect_missing<'s>(&self, scene: &'s Scene<V2>, missing: &mut Vec<Missing<'s>>) {
+		for material in &scene.model.materials {
+			if !material.texture_name.trim().is_empty() && self.resolve(&material.texture_name).is_none() {
+				missing.push(Missing {
+					node: &scene.name,
+					material: &material.name,
+					texture: &material.texture_name
+				});
+			}
+		}
+
+		for child in &scene.children {
+			self.collect_missing(child, missing);
+		}
+	}
+}
+
+impl Default for Resolver {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{env, fs, process};
+	use std::path::PathBuf;
+	use super::Resolver;
+
+	/// A fresh directory holding empty files at the given relative paths.
+	fn root(test: &str, files: &[&str]) -> PathBuf {
+		let root = env::temp_dir().join(format!("cem-textures-{}-{}", process::id(), test));
+		let _ = fs::remove_dir_all(&root);
+
+		for file in files {
+			let path = root.join(file);
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(path, b"").unwrap();
+		}
+
+		root
+	}
+
+	#[test]
+	fn names_ignore_case_and_separators() {
+		let root = root("case", &["Textures/Stone.TGA"]);
+		let mut resolver = Resolver::new();
+		resolver.add_root(&root).unwrap();
+
+		let expected = root.join("Textures/Stone.TGA");
+
+		assert_eq!(resolver.resolve("textures\\stone.tga"), Some(expected.as_path()));
+		assert_eq!(resolver.resolve(" ./TEXTURES/stone.tga "), Some(expected.as_path()));
+		assert_eq!(resolver.resolve("other/stone.tga"), Some(expected.as_path()));
+		assert_eq!(resolver.resolve(""), None);
+
+		fs::remove_dir_all(root).unwrap();
+	}
+
+	#[test]
+	fn extensions_are_substituted_in_order() {
+		let root = root("extensions", &["wood.dds", "wood.tga", "grass.dds", "old.bmp"]);
+		let mut resolver = Resolver::new();
+		resolver.add_root(&root).unwrap();
+
+		assert_eq!(resolver.resolve("wood"), Some(root.join("wood.tga").as_path()));
+		assert_eq!(resolver.resolve("grass.tga"), Some(root.join("grass.dds").as_path()));
+		assert_eq!(resolver.resolve("old.bmp"), Some(root.join("old.bmp").as_path()));
+		assert_eq!(resolver.resolve("old"), None);
+
+		fs::remove_dir_all(root).unwrap();
+	}
+
+	#[test]
+	fn earlier_roots_take_priority() {
+		let first = root("first", &["grass.tga"]);
+		let second = root("second", &["grass.tga", "sand.tga"]);
+		let mut resolver = Resolver::new();
+		resolver.add_root(&first).unwrap();
+		resolver.add_root(&second).unwrap();
+
+		assert_eq!(resolver.resolve("grass"), Some(first.join("grass.tga").as_path()));
+		assert_eq!(resolver.resolve("sand"), Some(second.join("sand.tga").as_path()));
+
+		fs::remove_dir_all(first).unwrap();
+		fs::remove_dir_all(second).unwrap();
+	}
+}
@@ -0,0 +1,370 @@
+//! Drives the model readers through a `Tracer`, which records which field consumed every byte of the input.
+//! Bytes that no field consumed are reported separately, which makes it easy to see where the unknown
+//! parts of the format sit within real files.
+
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display};
+use byteorder::{ReadBytesExt, LittleEndian};
+use {ModelHeader, Model, Encode, V1, V2, V5};
+
+/// A range of bytes consumed by a single field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+	pub start: u64,
+	pub end: u64,
+	/// Path of the field, such as `V2.frames[3].vertices[7].position`.
+	pub path: String,
+	/// The decoded value of the field, formatted with `Debug`. Empty if the field could not be decoded.
+	pub value: String
+}
+
+/// A reader that is told which field each read belongs to. The model readers are written against this trait,
+/// so that tracing follows exactly the layout they read. Names are only formatted when they are recorded.
+pub trait Fields: Read + Sized {
+	/// Reads a structure made up of other fields.
+	fn group<T, N, F>(&mut self, name: N, read: F) -> io::Result<T> where N: Display, F: FnOnce(&mut Self) -> io::Result<T>;
+
+	/// Reads a single field.
+	fn field<T, N, F>(&mut self, name: N, read: F) -> io::Result<T> where N: Display, T: Debug, F: FnOnce(&mut Self) -> io::Result<T>;
+
+	fn u8<N>(&mut self, name: N) -> io::Result<u8> where N: Display {
+		self.field(name, |r| r.read_u8())
+	}
+
+	fn u16<N>(&mut self, name: N) -> io::Result<u16> where N: Display {
+		self.field(name, |r| r.read_u16::<LittleEndian>())
+	}
+
+	fn u32<N>(&mut self, name: N) -> io::Result<u32> where N: Display {
+		self.field(name, |r| r.read_u32::<LittleEndian>())
+	}
+
+	fn i32<N>(&mut self, name: N) -> io::Result<i32> where N: Display {
+		self.field(name, |r| r.read_i32::<LittleEndian>())
+	}
+
+	fn f32<N>(&mut self, name: N) -> io::Result<f32> where N: Display {
+		self.field(name, |r| r.read_f32::<LittleEndian>())
+	}
+
+	/// Reads any type implementing `Encode` as a single field.
+	fn encoded<T, N>(&mut self, name: N) -> io::Result<T> where T: Encode + Debug, N: Display {
+		self.field(name, |r| T::read(r))
+	}
+}
+
+/// Reads fields without recording anything, for normal reading.
+pub struct Untraced<'r, R: 'r>(pub &'r mut R);
+
+impl<'r, R> Read for Untraced<'r, R> where R: Read {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		self.0.read(buf)
+	}
+}
+
+impl<'r, R> Fields for Untraced<'r, R> where R: Read {
+	#[inline]
+	fn group<T, N, F>(&mut self, _: N, read: F) -> io::Result<T> where N: Display, F: FnOnce(&mut Self) -> io::Result<T> {
+		read(self)
+	}
+
+	#[inline]
+	fn field<T, N, F>(&mut self, _: N, read: F) -> io::Result<T> where N: Display, T: Debug, F: FnOnce(&mut Self) -> io::Result<T> {
+		read(self)
+	}
+}
+
+/// Wraps a reader, keeping track of the current position and the field being read.
+pub struct Tracer<R> {
+	inner: R,
+	position: u64,
+	path: Vec<String>,
+	spans: Vec<Span>,
+	failure: Option<Span>
+}
+
+impl<R> Tracer<R> where R: Read {
+	pub fn new(inner: R) -> Self {
+		Tracer {
+			inner,
+			position: 0,
+			path: Vec::new(),
+			spans: Vec::new(),
+			failure: None
+		}
+	}
+
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+
+	pub fn spans(&self) -> &[Span] {
+		&self.spans
+	}
+
+	/// The innermost field or structure that failed to read, if any. The span starts where
+	/// that field started and ends wherever the reader stopped.
+	pub fn failure(&self) -> Option<&Span> {
+		self.failure.as_ref()
+	}
+
+	fn fail(&mut self, start: u64, error: &io::Error) {
+		if self.failure.is_none() {
+			self.failure = Some(Span { start, end: self.position, path: self.path_string(), value: error.to_string() });
+		}
+	}
+
+	fn path_string(&self) -> String {
+		let mut path = String::new();
+
+		for segment in &self.path {
+			if !path.is_empty() && !segment.starts_with('[') {
+				path.push('.');
+			}
+
+			path.push_str(segment);
+		}
+
+		path
+	}
+}
+
+impl<R> Fields for Tracer<R> where R: Read {
+	/// Only the innermost fields are recorded as spans.
+	fn group<T, N, F>(&mut self, name: N, read: F) -> io::Result<T> where N: Display, F: FnOnce(&mut Self) -> io::Result<T> {
+		self.path.push(name.to_string());
+		let result = read(self);
+
+		if let Err(ref e) = result {
+			self.fail(self.position, e);
+		}
+
+		self.path.pop();
+
+		result
+	}
+
+	fn field<T, N, F>(&mut self, name: N, read: F) -> io::Result<T> where N: Display, T: Debug, F: FnOnce(&mut Self) -> io::Result<T> {
+		let start = self.position;
+
+		self.path.push(name.to_string());
+		let result = read(self);
+
+		match result {
+			Ok(ref value) => if self.position > start {
+				let path = self.path_string();
+
+				self.spans.push(Span { start, end: self.position, path, value: format!("{:?}", value) });
+			},
+			Err(ref e) => self.fail(start, e)
+		}
+
+		self.path.pop();
+
+		result
+	}
+}
+
+impl<R> Read for Tracer<R> where R: Read {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let read = self.inner.read(buf)?;
+		self.position += read as u64;
+
+		Ok(read)
+	}
+}
+
+/// Traces a scene node and all of its children, using the same readers as `Scene::read`.
+pub fn scene<R>(t: &mut Tracer<R>) -> io::Result<()> where R: Read {
+	let start = t.position;
+	let header = t.group("header", ModelHeader::read_fields)?;
+
+	let additional_models = if header == V2::HEADER {
+		t.group("V2", V2::read_fields)?.1.additional_models
+	} else if header == V1::HEADER {
+		t.group("V1", V1::read_fields)?.1.additional_models
+	} else if header == V5::HEADER {
+		t.group("V5", V5::read_fields)?.1.additional_models
+	} else {
+		let error = io::Error::new(io::ErrorKind::InvalidData, format!("Unknown model header: {:?}", header));
+
+		// The header was read without error, so blame it here, where the span covers all of it.
+		t.path.push("header".to_string());
+		t.fail(start, &error);
+		t.path.pop();
+
+		return Err(error);
+	};
+
+	for i in 0..additional_models {
+		t.group(format_args!("children[{}]", i), scene)?;
+	}
+
+	Ok(())
+}
+
+/// The result of tracing an entire file.
+#[derive(Debug)]
+pub struct Coverage {
+	/// Every field that was read, in file order.
+	pub spans: Vec<Span>,
+	/// Ranges of bytes that were not consumed by any field.
+	pub unconsumed: Vec<Range<u64>>,
+	/// Total length of the input in bytes.
+	pub len: u64,
+	/// The error that stopped tracing, if the file could not be fully traced.
+	pub error: Option<io::Error>,
+	/// The field that was being read when tracing stopped.
+	pub failure: Option<Span>
+}
+
+impl Coverage {
+	/// Traces a complete scene, then consumes the rest of the input to find any bytes left over.
+	pub fn trace<R>(r: R) -> io::Result<Self> where R: Read {
+		let mut t = Tracer::new(r);
+		let error = scene(&mut t).err();
+
+		io::copy(&mut t, &mut io::sink())?;
+
+		let mut unconsumed = Vec::new();
+		let mut position = 0;
+
+		for span in &t.spans {
+			if span.start > position {
+				unconsumed.push(position..span.start);
+			}
+
+			position = span.end;
+		}
+
+		if t.position > position {
+			unconsumed.push(position..t.position);
+		}
+
+		Ok(Coverage {
+			len: t.position,
+			spans: t.spans,
+			unconsumed,
+			error,
+			failure: t.failure
+		})
+	}
+
+	/// Returns the number of bytes consumed by each field, with array indices removed so that
+	/// fields like `V2.frames[].vertices[].position` are grouped together.
+	pub fn totals(&self) -> BTreeMap<String, u64> {
+		let mut totals = BTreeMap::new();
+
+		for span in &self.spans {
+			let mut key = String::with_capacity(span.path.len());
+			let mut in_index = false;
+
+			for c in span.path.chars() {
+				match c {
+					'[' => { in_index = true; key.push_str("[]"); },
+					']' => in_index = false,
+					_ if in_index => (),
+					_ => key.push(c)
+				}
+			}
+
+			*totals.entry(key).or_insert(0) += span.end - span.start;
+		}
+
+		totals
+	}
+
+	/// Writes the coverage map as one line per range: the start and end offsets in hexadecimal, followed by the
+	/// field path or `<unconsumed>`.
+	pub fn write_map<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
+		let mut unconsumed = self.unconsumed.iter().peekable();
+
+		for span in &self.spans {
+			while let Some(range) = unconsumed.peek() {
+				if range.start > span.start {
+					break;
+				}
+
+				writeln!(w, "{:08x} {:08x} <unconsumed>", range.start, range.end)?;
+				unconsumed.next();
+			}
+
+			writeln!(w, "{:08x} {:08x} {}", span.start, span.end, span.path)?;
+		}
+
+		for range in unconsumed {
+			writeln!(w, "{:08x} {:08x} <unconsumed>", range.start, range.end)?;
+		}
+
+		if let Some(ref failure) = self.failure {
+			writeln!(w, "# tracing stopped at {:08x} in {}: {}", failure.start, failure.path, failure.value)?;
+		} else if let Some(ref error) = self.error {
+			writeln!(w, "# tracing stopped: {}", error)?;
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Coverage;
+	use samples::{v1_scene, v2_scene};
+
+	#[test]
+	fn v2_fields_cover_everything_but_trailing_data() {
+		let scene = v2_scene();
+		let mut data = Vec::new();
+		scene.write(&mut data).unwrap();
+
+		let coverage = Coverage::trace(&data[..]).unwrap();
+		let end = (data.len() - scene.trailing.len()) as u64;
+
+		assert!(coverage.error.is_none());
+		assert_eq!(coverage.unconsumed, vec![end..data.len() as u64]);
+		assert!(coverage.spans.iter().any(|span| span.path == "children[0].V2.frames[0].aabb.upper"));
+	}
+
+	#[test]
+	fn v1_fields_cover_everything_but_trailing_data() {
+		let scene = v1_scene();
+		let mut data = Vec::new();
+		scene.write(&mut data).unwrap();
+
+		let coverage = Coverage::trace(&data[..]).unwrap();
+		let end = (data.len() - scene.trailing.len()) as u64;
+
+		assert!(coverage.error.is_none());
+		assert_eq!(coverage.unconsumed, vec![end..data.len() as u64]);
+		assert!(coverage.spans.iter().any(|span| span.path == "V1.materials[0].texture.0"));
+	}
+
+	#[test]
+	fn unknown_header_is_reported_at_the_header() {
+		let mut data = Vec::new();
+		v2_scene().write(&mut data).unwrap();
+		data[0] ^= 0xff;
+
+		let coverage = Coverage::trace(&data[..]).unwrap();
+		let failure = coverage.failure.unwrap();
+
+		assert_eq!((failure.start, failure.end), (0, 8));
+		assert_eq!(failure.path, "header");
+		assert!(failure.value.contains("Unknown model header"));
+	}
+
+	#[test]
+	fn truncated_file_reports_the_failing_field() {
+		let mut data = Vec::new();
+		v2_scene().write(&mut data).unwrap();
+		data.truncate(40);
+
+		let coverage = Coverage::trace(&data[..]).unwrap();
+		let failure = coverage.failure.unwrap();
+
+		assert!(coverage.error.is_some());
+		assert!(failure.path.starts_with("V2."), "{}", failure.path);
+	}
+}
@@ -0,0 +1,218 @@
+//! A frame transform is expected to be an affine matrix built from a translation, a rotation and a scale.
+//! Anything else, such as shear between the axes or a non-trivial bottom row, cannot be represented by
+//! those parts and is flagged instead.
+
+use cgmath::{Vector3, Matrix3, Matrix4, Quaternion, InnerSpace, SquareMatrix};
+
+/// Components of the upper 3x3 matrix whose axes are further than this from orthogonal are considered sheared.
+pub const SHEAR_TOLERANCE: f32 = 1e-4;
+
+/// The translation, rotation and non-uniform scale that make up an affine transform.
+/// The transform is applied as scale first, then rotation, then translation.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Parts {
+	pub translation: Vector3<f32>,
+	pub rotation:    Quaternion<f32>,
+	/// Scale along each axis. A mirrored transform has a negative X scale.
+	pub scale:       Vector3<f32>
+}
+
+impl Parts {
+	pub fn identity() -> Self {
+		Parts {
+			translation: Vector3::new(0.0, 0.0, 0.0),
+			rotation: Quaternion::new(1.0, 0.0, 0.0, 0.0),
+			scale: Vector3::new(1.0, 1.0, 1.0)
+		}
+	}
+
+	/// Builds the matrix from the parts.
+	pub fn recompose(&self) -> Matrix4<f32> {
+		Matrix4::from_translation(self.translation)
+			* Matrix4::from(self.rotation)
+			* Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+	}
+}
+
+/// A matrix split into its parts. Unless the parts are edited, `matrix` returns the original matrix
+/// exactly, so that frames can be decomposed and written back without changing a single byte.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Decomposition {
+	original:  Matrix4<f32>,
+	extracted: Parts,
+	/// The parts of the transform. Changing these causes `matrix` to recompose the transform.
+	pub parts: Parts,
+	/// The largest cosine of the angle between two of the axes. Zero for a transform without shear.
+	pub shear: f32,
+	/// True if the bottom row of the matrix is not (0, 0, 0, 1), making the transform projective.
+	pub projective: bool
+}
+
+impl Decomposition {
+	pub fn new(matrix: Matrix4<f32>) -> Self {
+		let axes = [matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate()];
+
+		let mut scale = Vector3::new(axes[0].magnitude(), axes[1].magnitude(), axes[2].magnitude());
+
+		if Matrix3::from_cols(axes[0], axes[1], axes[2]).determinant() < 0.0 {
+			scale.x = -scale.x;
+		}
+
+		let normalized = [
+			normalize_or(axes[0] / scale.x, Vector3::unit_x()),
+			normalize_or(axes[1] / scale.y, Vector3::unit_y()),
+			normalize_or(axes[2] / scale.z, Vector3::unit_z())
+		];
+
+		let shear = normalized[0].dot(normalized[1]).abs()
+			.max(normalized[0].dot(normalized[2]).abs())
+			.max(normalized[1].dot(normalized[2]).abs());
+
+		// Gram-Schmidt, so that the rotation is still valid if the axes are sheared.
+		let x = normalized[0];
+		let y = normalize_or(normalized[1] - x * x.dot(normalized[1]), Vector3::unit_y());
+		let z = x.cross(y);
+
+		let extracted = Parts {
+			translation: matrix.w.truncate(),
+			rotation: Quaternion::from(Matrix3::from_cols(x, y, z)).normalize(),
+			scale
+		};
+
+		Decomposition {
+			original: matrix,
+			extracted,
+			parts: extracted,
+			shear,
+			projective: matrix.x.w != 0.0 || matrix.y.w != 0.0 || matrix.z.w != 0.0 || matrix.w.w != 1.0
+		}
+	}
+
+	pub fn has_shear(&self) -> bool {
+		self.shear > SHEAR_TOLERANCE
+	}
+
+	/// Returns true if the parts fully describe the original matrix.
+	pub fn is_affine(&self) -> bool {
+		!self.has_shear() && !self.projective
+	}
+
+	/// Returns true if any part has changed. Components are compared bit for bit, so that a NaN left untouched does
+	/// not count as an edit.
+	pub fn is_edited(&self) -> bool {
+		bits(&self.parts) != bits(&self.extracted)
+	}
+
+	/// Returns the original matrix if the parts are unchanged, or the recomposed matrix otherwise.
+	pub fn matrix(&self) -> Matrix4<f32> {
+		if self.is_edited() {
+			self.parts.recompose()
+		} else {
+			self.original
+		}
+	}
+
+	/// The largest difference between a component of the original matrix and the recomposed matrix.
+	pub fn round_trip_error(&self) -> f32 {
+		let recomposed = self.extracted.recompose();
+		let mut error: f32 = 0.0;
+
+		for i in 0..4 {
+			for j in 0..4 {
+				error = error.max((recomposed[i][j] - self.original[i][j]).abs());
+			}
+		}
+
+		error
+	}
+}
+
+fn bits(parts: &Parts) -> [u32; 10] {
+	let (t, r, s) = (parts.translation, parts.rotation, parts.scale);
+
+	[
+		t.x.to_bits(), t.y.to_bits(), t.z.to_bits(),
+		r.s.to_bits(), r.v.x.to_bits(), r.v.y.to_bits(), r.v.z.to_bits(),
+		s.x.to_bits(), s.y.to_bits(), s.z.to_bits()
+	]
+}
+
+fn normalize_or(v: Vector3<f32>, fallback: Vector3<f32>) -> Vector3<f32> {
+	let magnitude = v.magnitude();
+
+	if magnitude > 0.0 && magnitude.is_finite() {
+		v / magnitude
+	} else {
+		fallback
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Vector3, Matrix4, Rad};
+	use super::{Decomposition, SHEAR_TOLERANCE};
+
+	fn affine() -> Vec<Matrix4<f32>> {
+		vec![
+			Matrix4::from_scale(1.0),
+			Matrix4::from_translation(Vector3::new(1.5, -2.0, 40.0)) * Matrix4::from_angle_y(Rad(0.7)),
+			Matrix4::from_angle_z(Rad(-2.1)) * Matrix4::from_nonuniform_scale(0.5, 2.0, 3.0),
+			Matrix4::from_angle_x(Rad(1.2)) * Matrix4::from_nonuniform_scale(-1.0, 1.0, 1.0)
+		]
+	}
+
+	#[test]
+	fn parts_reproduce_the_matrix() {
+		for matrix in affine() {
+			let decomposition = Decomposition::new(matrix);
+
+			assert!(decomposition.is_affine(), "{:?}", matrix);
+			assert_eq!(decomposition.matrix(), matrix);
+			assert!(decomposition.round_trip_error() < SHEAR_TOLERANCE, "{:?}", decomposition);
+		}
+	}
+
+	#[test]
+	fn edited_parts_are_recomposed() {
+		for matrix in affine() {
+			let mut decomposition = Decomposition::new(matrix);
+			decomposition.parts.translation += Vector3::new(0.0, 1.0, 0.0);
+
+			let expected = Matrix4::from_translation(Vector3::new(0.0, 1.0, 0.0)) * matrix;
+			let recomposed = decomposition.matrix();
+
+			for i in 0..4 {
+				for j in 0..4 {
+					assert!((recomposed[i][j] - expected[i][j]).abs() < SHEAR_TOLERANCE, "{:?} != {:?}", recomposed, expected);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn untouched_nan_is_not_an_edit() {
+		let mut matrix = Matrix4::from_scale(1.0);
+		matrix.w.x = f32::NAN;
+		matrix.x.w = f32::from_bits(0x7fc0_1234);
+
+		let decomposition = Decomposition::new(matrix);
+		let returned = decomposition.matrix();
+		let (original, returned): (&[f32; 16], &[f32; 16]) = (matrix.as_ref(), returned.as_ref());
+
+		assert!(!decomposition.is_edited());
+		assert_eq!(returned.iter().map(|c| c.to_bits()).collect::<Vec<_>>(), original.iter().map(|c| c.to_bits()).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn shear_is_flagged() {
+		let mut matrix = Matrix4::from_scale(1.0);
+		matrix.y.x = 0.5;
+
+		let decomposition = Decomposition::new(matrix);
+
+		assert!(decomposition.has_shear());
+		assert!(!decomposition.is_affine());
+		assert_eq!(decomposition.matrix(), matrix);
+	}
+}
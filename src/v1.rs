@@ -1,9 +1,11 @@
 use cgmath::{Point3, Matrix4};
 use collider::Aabb;
-use std::io::{self, Read};
-use byteorder::{ReadBytesExt, LittleEndian};
-use {ModelHeader, MAGIC, Encode};
-use scene::NodeData;
+use std::io::{self, Read, Write};
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
+use {ModelHeader, MAGIC, Encode, preallocate};
+use scene::{NodeData, Model};
+use transform::Decomposition;
+use trace::{Fields, Untraced};
 use std::borrow::Cow;
 
 // 1.1
@@ -18,6 +20,7 @@ use std::borrow::Cow;
 pub const EXPECTED_MODEL_HEADER: ModelHeader = ModelHeader { magic: MAGIC, major: 1, minor: 3 };
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct V1 {
 	pub quantities: Quantities,
 	pub center: Point3<f32>,
@@ -32,95 +35,181 @@ pub struct V1 {
 }
 
 impl V1 {
-	pub fn read<R>(r: &mut R) -> io::Result<(Self, NodeData)> where R: Read {
-		let quantities = Quantities::read(r)?;
+	/// Reads a model without its header. This is the same as `Model::read`, kept so that callers do not need
+	/// the trait in scope.
+	pub fn read<R>(r: &mut R) -> io::Result<(Self, NodeData<'static>)> where R: Read {
+		Self::read_fields(&mut Untraced(r))
+	}
+
+	/// Reads a model without its header, naming each field as it is read. `Model::read` and `trace` both use this,
+	/// so that traces always follow the layout that is actually read.
+	pub fn read_fields<F>(r: &mut F) -> io::Result<(Self, NodeData<'static>)> where F: Fields {
+		let quantities = r.group("quantities", Quantities::read_fields)?;
 
 		let node = NodeData {
 			additional_models: quantities.additional_models,
-			name: Cow::Owned(String::read(r)?)
+			name: Cow::Owned(r.encoded("name")?)
 		};
 
 		Ok((V1 {
-			center: Point3::read(r)?,
-			unknown: r.read_u8()?,
+			center: r.encoded("center")?,
+			unknown: r.u8("unknown")?,
 			points: {
-				let mut points = Vec::with_capacity(quantities.vertex_points as usize);
+				let mut points = preallocate(quantities.vertex_points as usize);
 
-				for _ in 0..quantities.vertex_points {
-					points.push(r.read_u32::<LittleEndian>()?);
+				for i in 0..quantities.vertex_points {
+					points.push(r.u32(format_args!("points[{}]", i))?);
 				}
 
 				points
 			},
 			triangles: {
-				let mut triangles = Vec::with_capacity(quantities.triangles as usize);
-
-				for _ in 0..quantities.triangles {
-					triangles.push((
-						Vertex::read(r)?,
-						Vertex::read(r)?,
-						Vertex::read(r)?
-					));
+				let mut triangles = preallocate(quantities.triangles as usize);
+
+				for i in 0..quantities.triangles {
+					triangles.push(r.group(format_args!("triangles[{}]", i), |r| Ok((
+						r.group("0", Vertex::read_fields)?,
+						r.group("1", Vertex::read_fields)?,
+						r.group("2", Vertex::read_fields)?
+					)))?);
 				}
 
 				triangles
 			},
 			triangle_groups: {
-				let mut triangle_groups = Vec::with_capacity(quantities.triangle_groups as usize);
+				let mut triangle_groups = preallocate(quantities.triangle_groups as usize);
 
-				for _ in 0..quantities.triangle_groups {
-					triangle_groups.push(TriangleGroup::read(r)?);
+				for i in 0..quantities.triangle_groups {
+					triangle_groups.push(r.group(format_args!("triangle_groups[{}]", i), TriangleGroup::read_fields)?);
 				}
 
 				triangle_groups
 			},
 			materials: {
-				let mut materials = Vec::with_capacity(quantities.materials as usize);
+				let mut materials = preallocate(quantities.materials as usize);
 
-				for _ in 0..quantities.materials {
-					materials.push(Material::read(r)?);
+				for i in 0..quantities.materials {
+					materials.push(r.group(format_args!("materials[{}]", i), Material::read_fields)?);
 				}
 
 				materials
 			},
 			vertices: {
-				let mut vertices = Vec::with_capacity(quantities.vertices as usize);
+				let mut vertices = preallocate(quantities.vertices as usize);
 
-				for _ in 0..quantities.vertices {
-					vertices.push((
-						r.read_u32::<LittleEndian>()?,
-						r.read_f32::<LittleEndian>()?
-					));
+				for i in 0..quantities.vertices {
+					vertices.push(r.group(format_args!("vertices[{}]", i), |r| Ok((r.u32("0")?, r.f32("1")?)))?);
 				}
 
 				vertices
 			},
 			tag_points: {
-				let mut tag_points = Vec::with_capacity(quantities.tag_points as usize);
+				let mut tag_points = preallocate(quantities.tag_points as usize);
 
-				for _ in 0..quantities.tag_points {
-					tag_points.push(String::read(r)?);
+				for i in 0..quantities.tag_points {
+					tag_points.push(r.encoded(format_args!("tag_points[{}]", i))?);
 				}
 
 				tag_points
 			},
 			frames: {
-				let mut frames = Vec::with_capacity(quantities.frames as usize);
+				let mut frames = preallocate(quantities.frames as usize);
 
-				for _ in 0..quantities.frames {
-					frames.push(Frame::read(r, &quantities)?);
+				for i in 0..quantities.frames {
+					frames.push(r.group(format_args!("frames[{}]", i), |r| Frame::read_fields(r, &quantities))?);
 				}
 
 				frames
 			},
 			quantities
-		}, node ))
+		}, node))
+	}
+
+	/// Counts the contents of the model, rather than trusting `quantities`, so that edited models are written
+	/// consistently.
+	pub(crate) fn quantities(&self, additional_models: u32) -> Result<Quantities, &'static str> {
+		for frame in &self.frames {
+			if frame.points.len() != self.points.len() {
+				return Err("Every frame must have one point for each vertex point");
+			}
+
+			if frame.normals.len() != self.vertices.len() {
+				return Err("Every frame must have one normal for each vertex");
+			}
+
+			if frame.tag_points.len() != self.tag_points.len() {
+				return Err("Every frame must have one position for each tag point");
+			}
+		}
+
+		Ok(Quantities {
+			frames:            self.frames.len() as u32,
+			materials:         self.materials.len() as u32,
+			vertex_points:     self.points.len() as u32,
+			triangles:         self.triangles.len() as u32,
+			triangle_groups:   self.triangle_groups.len() as u32,
+			vertices:          self.vertices.len() as u32,
+			tag_points:        self.tag_points.len() as u32,
+			additional_models
+		})
+	}
+}
+
+impl Model for V1 {
+	const HEADER: ModelHeader = EXPECTED_MODEL_HEADER;
+
+	fn read<R>(r: &mut R) -> io::Result<(Self, NodeData<'_>)> where R: Read {
+		V1::read(r)
+	}
+
+	fn write<W>(&self, w: &mut W, node: NodeData) -> io::Result<()> where W: Write {
+		let quantities = self.quantities(node.additional_models).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+		quantities.write(w)?;
+
+		node.name.write(w)?;
+		self.center.write(w)?;
+		w.write_u8(self.unknown)?;
+
+		for &point in &self.points {
+			w.write_u32::<LittleEndian>(point)?;
+		}
+
+		for triangle in &self.triangles {
+			triangle.0.write(w)?;
+			triangle.1.write(w)?;
+			triangle.2.write(w)?;
+		}
+
+		for triangle_group in &self.triangle_groups {
+			triangle_group.write(w)?;
+		}
+
+		for material in &self.materials {
+			material.write(w)?;
+		}
+
+		for &(index, value) in &self.vertices {
+			w.write_u32::<LittleEndian>(index)?;
+			w.write_f32::<LittleEndian>(value)?;
+		}
+
+		for tag_point in &self.tag_points {
+			tag_point.write(w)?;
+		}
+
+		for frame in &self.frames {
+			frame.write(w)?;
+		}
+
+		Ok(())
 	}
 }
 
 /// Contains metadata about the quantities of certain things in this file.
 /// Not useful on its own, but necessary to parse the rest of the file.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Quantities {
 	pub frames:  u32,
 	pub materials:  u32,
@@ -136,20 +225,36 @@ pub struct Quantities {
 
 impl Quantities {
 	pub fn read<R>(r: &mut R) -> io::Result<Self> where R: Read {
+		Self::read_fields(&mut Untraced(r))
+	}
+
+	pub fn read_fields<F>(r: &mut F) -> io::Result<Self> where F: Fields {
 		Ok(Quantities {
-			frames:  r.read_u32::<LittleEndian>()?,
-			materials:  r.read_u32::<LittleEndian>()?,
-			vertex_points:  r.read_u32::<LittleEndian>()?,
-			triangles: r.read_u32::<LittleEndian>()?,
-			triangle_groups:  r.read_u32::<LittleEndian>()?,
-			vertices:  r.read_u32::<LittleEndian>()?,
-			tag_points:  r.read_u32::<LittleEndian>()?,
-			additional_models:  r.read_u32::<LittleEndian>()?
+			frames:  r.u32("frames")?,
+			materials:  r.u32("materials")?,
+			vertex_points:  r.u32("vertex_points")?,
+			triangles: r.u32("triangles")?,
+			triangle_groups:  r.u32("triangle_groups")?,
+			vertices:  r.u32("vertices")?,
+			tag_points:  r.u32("tag_points")?,
+			additional_models:  r.u32("additional_models")?
 		})
 	}
+
+	pub fn write<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
+		w.write_u32::<LittleEndian>(self.frames)?;
+		w.write_u32::<LittleEndian>(self.materials)?;
+		w.write_u32::<LittleEndian>(self.vertex_points)?;
+		w.write_u32::<LittleEndian>(self.triangles)?;
+		w.write_u32::<LittleEndian>(self.triangle_groups)?;
+		w.write_u32::<LittleEndian>(self.vertices)?;
+		w.write_u32::<LittleEndian>(self.tag_points)?;
+		w.write_u32::<LittleEndian>(self.additional_models)
+	}
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Vertex {
 	pub unknown0: u32,
 	pub uv: (f32, f32),
@@ -160,52 +265,79 @@ pub struct Vertex {
 
 impl Vertex {
 	pub fn read<R>(r: &mut R) -> io::Result<Self> where R: Read {
+		Self::read_fields(&mut Untraced(r))
+	}
+
+	pub fn read_fields<F>(r: &mut F) -> io::Result<Self> where F: Fields {
 		Ok(Vertex {
-			unknown0: r.read_u32::<LittleEndian>()?,
-			uv: (
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?
-			),
-			rgb: (
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?
-			),
-			unknown1: [
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?
-			]
+			unknown0: r.u32("unknown0")?,
+			uv: r.group("uv", |r| Ok((
+				r.f32("0")?,
+				r.f32("1")?
+			)))?,
+			rgb: r.group("rgb", |r| Ok((
+				r.f32("0")?,
+				r.f32("1")?,
+				r.f32("2")?
+			)))?,
+			unknown1: r.group("unknown1", |r| Ok([
+				r.f32("[0]")?,
+				r.f32("[1]")?,
+				r.f32("[2]")?,
+				r.f32("[3]")?
+			]))?
 		})
 	}
+
+	pub fn write<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
+		w.write_u32::<LittleEndian>(self.unknown0)?;
+		w.write_f32::<LittleEndian>(self.uv.0)?;
+		w.write_f32::<LittleEndian>(self.uv.1)?;
+		w.write_f32::<LittleEndian>(self.rgb.0)?;
+		w.write_f32::<LittleEndian>(self.rgb.1)?;
+		w.write_f32::<LittleEndian>(self.rgb.2)?;
+
+		for &value in &self.unknown1 {
+			w.write_f32::<LittleEndian>(value)?;
+		}
+
+		Ok(())
+	}
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TriangleGroup {
-	name: String,
-	indices: Vec<u32>
+	pub name: String,
+	pub indices: Vec<u32>
 }
 
 impl TriangleGroup {
 	pub fn read<R>(r: &mut R) -> io::Result<Self> where R: Read {
+		Self::read_fields(&mut Untraced(r))
+	}
+
+	pub fn read_fields<F>(r: &mut F) -> io::Result<Self> where F: Fields {
 		Ok(TriangleGroup {
-			name: String::read(r)?,
-			indices: {
-				let len = r.read_u32::<LittleEndian>()?;
-				let mut indices = Vec::with_capacity(len as usize);
+			name: r.encoded("name")?,
+			indices: read_indices(r)?
+		})
+	}
 
-				for _ in 0..len {
-					indices.push(r.read_u32::<LittleEndian>()?);
-				}
+	pub fn write<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
+		self.name.write(w)?;
+		w.write_u32::<LittleEndian>(self.indices.len() as u32)?;
 
-				indices
-			}
-		})
+		for &index in &self.indices {
+			w.write_u32::<LittleEndian>(index)?;
+		}
+
+		Ok(())
 	}
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Material {
 	pub indices: Vec<u32>,
 	/// Second value has an unknown meaning.
@@ -214,27 +346,48 @@ pub struct Material {
 
 impl Material {
 	pub fn read<R>(r: &mut R) -> io::Result<Self> where R: Read {
-		Ok(Material {
-			indices: {
-				let len = r.read_u32::<LittleEndian>()?;
-				let mut indices = Vec::with_capacity(len as usize);
+		Self::read_fields(&mut Untraced(r))
+	}
 
-				for _ in 0..len {
-					indices.push(r.read_u32::<LittleEndian>()?);
-				}
+	pub fn read_fields<F>(r: &mut F) -> io::Result<Self> where F: Fields {
+		let indices = read_indices(r)?;
 
-				indices
-			},
-			texture: match r.read_u8()? {
-				0 => None,
-				1 => Some((String::read(r)?, r.read_u32::<LittleEndian>()?)),
-				x => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("A boolean must be 0 or 1, got {}", x)))
+		let has_texture = r.field("has_texture", |r| match r.read_u8()? {
+			0 => Ok(false),
+			1 => Ok(true),
+			x => Err(io::Error::new(io::ErrorKind::InvalidData, format!("A boolean must be 0 or 1, got {}", x)))
+		})?;
+
+		Ok(Material {
+			indices,
+			texture: if has_texture {
+				Some(r.group("texture", |r| Ok((r.encoded("0")?, r.u32("1")?)))?)
+			} else {
+				None
 			}
 		})
 	}
+
+	pub fn write<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
+		w.write_u32::<LittleEndian>(self.indices.len() as u32)?;
+
+		for &index in &self.indices {
+			w.write_u32::<LittleEndian>(index)?;
+		}
+
+		match self.texture {
+			Some((ref name, value)) => {
+				w.write_u8(1)?;
+				name.write(w)?;
+				w.write_u32::<LittleEndian>(value)
+			},
+			None => w.write_u8(0)
+		}
+	}
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Frame {
 	pub radius:           f32,
 	pub points:           Vec<Point3<f32>>,
@@ -247,29 +400,38 @@ pub struct Frame {
 }
 
 impl Frame {
+	/// Splits the transform into translation, rotation and scale. Use `Decomposition::matrix` to write it back.
+	pub fn decompose_transform(&self) -> Decomposition {
+		Decomposition::new(self.transform)
+	}
+
 	pub fn read<R>(r: &mut R, quantities: &Quantities) -> io::Result<Self> where R: Read {
+		Self::read_fields(&mut Untraced(r), quantities)
+	}
+
+	pub fn read_fields<F>(r: &mut F, quantities: &Quantities) -> io::Result<Self> where F: Fields {
 		Ok(Frame {
-			radius: r.read_f32::<LittleEndian>()?,
+			radius: r.f32("radius")?,
 			points: {
-				let mut points = Vec::with_capacity(quantities.vertex_points as usize);
+				let mut points = preallocate(quantities.vertex_points as usize);
 
-				for _ in 0..quantities.vertex_points {
-					points.push(Point3::read(r)?);
+				for i in 0..quantities.vertex_points {
+					points.push(r.encoded(format_args!("points[{}]", i))?);
 				}
 
 				points
 			},
 			normals: {
-				let mut normals = Vec::with_capacity(quantities.vertices as usize);
+				let mut normals = preallocate(quantities.vertices as usize);
 
-				for _ in 0..quantities.vertices {
-					normals.push(r.read_u16::<LittleEndian>()?);
+				for i in 0..quantities.vertices {
+					normals.push(r.u16(format_args!("normals[{}]", i))?);
 				}
 
 				normals
 			},
 			/*triangle_normals: {
-				let mut triangle_normals = Vec::with_capacity(quantities.triangles as usize);
+				let mut triangle_normals = preallocate(quantities.triangles as usize);
 
 				for _ in 0..quantities.triangles {
 					triangle_normals.push(Point3::read(r)?);
@@ -278,16 +440,71 @@ impl Frame {
 				triangle_normals
 			},*/
 			tag_points: {
-				let mut tag_points = Vec::with_capacity(quantities.tag_points as usize);
+				let mut tag_points = preallocate(quantities.tag_points as usize);
 
-				for _ in 0..quantities.tag_points {
-					tag_points.push(Point3::read(r)?);
+				for i in 0..quantities.tag_points {
+					tag_points.push(r.encoded(format_args!("tag_points[{}]", i))?);
 				}
 
 				tag_points
 			},
-			transform: Matrix4::read(r)?,
-			bound: Aabb::read(r)?
+			transform: r.encoded("transform")?,
+			bound: r.group("bound", |r| Ok(Aabb {
+				lower: r.encoded("lower")?,
+				upper: r.encoded("upper")?
+			}))?
 		})
 	}
-}
\ No newline at end of file
+
+	pub fn write<W>(&self, w: &mut W) -> io::Result<()> where W: Write {
+		w.write_f32::<LittleEndian>(self.radius)?;
+
+		for point in &self.points {
+			point.write(w)?;
+		}
+
+		for &normal in &self.normals {
+			w.write_u16::<LittleEndian>(normal)?;
+		}
+
+		for tag_point in &self.tag_points {
+			tag_point.write(w)?;
+		}
+
+		self.transform.write(w)?;
+		self.bound.write(w)
+	}
+}
+
+/// Reads a list of indices preceded by its length.
+fn read_indices<F>(r: &mut F) -> io::Result<Vec<u32>> where F: Fields {
+	let len = r.u32("len")?;
+	let mut indices = preallocate(len as usize);
+
+	for i in 0..len {
+		indices.push(r.u32(format_args!("indices[{}]", i))?);
+	}
+
+	Ok(indices)
+}
+
+#[cfg(test)]
+mod tests {
+	use samples::v1_scene;
+	use {V1, ModelHeader, Encode};
+
+	#[test]
+	fn read_is_available_without_the_model_trait() {
+		let scene = v1_scene();
+		let mut data = Vec::new();
+		scene.write(&mut data).unwrap();
+
+		let mut r = &data[..];
+		ModelHeader::read(&mut r).unwrap();
+		let (model, node) = V1::read(&mut r).unwrap();
+
+		assert_eq!(node.name, scene.name);
+		assert_eq!(model.tag_points, scene.model.tag_points);
+		assert_eq!(r, &scene.trailing[..]);
+	}
+}
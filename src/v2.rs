@@ -1,37 +1,67 @@
 use cgmath::{Point2, Point3, Vector3, Matrix4, SquareMatrix};
 use collider::Aabb;
 use std::io::{self, Read, Write};
-use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
-use {ModelHeader, MAGIC, Encode};
+use byteorder::{WriteBytesExt, LittleEndian};
+use {ModelHeader, MAGIC, Encode, MAX_PREALLOCATION, preallocate};
 use collider::{Collider, ColliderBuilder};
+use transform::Decomposition;
+use tags::TagPoint;
+use trace::{Fields, Untraced};
 use scene::{NodeData, Model};
 use std::borrow::Cow;
 
 pub type VertexIndex = u32;
 
+/// Three indices into the vertices of each frame.
+pub type Triangle = (VertexIndex, VertexIndex, VertexIndex);
+
 /// Contains metadata about the quantities of certain things in this file.
 /// Not useful on its own, but necessary to parse the rest of the file.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Quantities {
+	pub triangles: u32,
+	pub vertices: u32,
+	pub tag_points: u32,
+	pub materials: u32,
+	pub frames: u32,
+	pub additional_models: u32,
+	pub lod_levels: u32
+}
+
+/// A model as far as it has been read, so that a reader that fails part way can keep everything before the failure.
 #[derive(Debug)]
-struct Quantities {
-	triangles: u32,
-	vertices: u32,
-	tag_points: u32,
-	materials: u32,
-	frames: u32,
-	additional_models: u32,
-	lod_levels: u32
+pub(crate) struct Progress {
+	pub quantities: Option<Quantities>,
+	pub name: Option<String>,
+	pub model: V2
+}
+
+impl Progress {
+	pub fn new() -> Self {
+		Progress {
+			quantities: None,
+			name: None,
+			model: V2 {
+				center: Point3::new(0.0, 0.0, 0.0),
+				lod_levels: Vec::new(),
+				materials: Vec::new(),
+				tag_points: Vec::new(),
+				frames: Vec::new()
+			}
+		}
+	}
 }
 
 impl Quantities {
-	fn read<R>(r: &mut R) -> io::Result<Self> where R: Read {
+	fn read_fields<F>(r: &mut F) -> io::Result<Self> where F: Fields {
 		Ok(Quantities {
-			triangles:         r.read_u32::<LittleEndian>()?,
-			vertices:          r.read_u32::<LittleEndian>()?,
-			tag_points:        r.read_u32::<LittleEndian>()?,
-			materials:         r.read_u32::<LittleEndian>()?,
-			frames:            r.read_u32::<LittleEndian>()?,
-			additional_models: r.read_u32::<LittleEndian>()?,
-			lod_levels:        r.read_u32::<LittleEndian>()?
+			triangles:         r.u32("triangles")?,
+			vertices:          r.u32("vertices")?,
+			tag_points:        r.u32("tag_points")?,
+			materials:         r.u32("materials")?,
+			frames:            r.u32("frames")?,
+			additional_models: r.u32("additional_models")?,
+			lod_levels:        r.u32("lod_levels")?
 		})
 	}
 
@@ -48,25 +78,162 @@ impl Quantities {
 
 /// A model. This contains all of the relevant sub structures.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct V2 {
 	pub center:            Point3<f32>,
-	pub lod_levels:        Vec<Vec<(VertexIndex, VertexIndex, VertexIndex)>>,
+	pub lod_levels:        Vec<Vec<Triangle>>,
 	pub materials:         Vec<Material>,
 	pub tag_points:        Vec<String>,
 	pub frames:            Vec<Frame>
 }
 
 impl V2 {
-	fn quantities(&self, additional_models: u32) -> Result<Quantities, &'static str> {
-		if self.materials.len() == 0 {
+	/// Returns the triangles of a LOD level selected by a material, clamped to the triangles that actually exist.
+	pub fn selection(&self, material: &Material, lod: usize) -> &[Triangle] {
+		let triangles = match self.lod_levels.get(lod) {
+			Some(triangles) => triangles,
+			None => return &[]
+		};
+
+		match material.triangles.get(lod) {
+			Some(selection) => {
+				let start = (selection.offset as usize).min(triangles.len());
+				let end = (start + selection.len as usize).min(triangles.len());
+
+				&triangles[start..end]
+			},
+			None => &[]
+		}
+	}
+
+	/// Finds a tag point by name, to follow it across frames.
+	pub fn tag_point(&self, name: &str) -> Option<TagPoint<'_>> {
+		TagPoint::find(self, name)
+	}
+
+	/// Moves the triangles of a material for which `selected` returns true into a new material with the given name,
+	/// such as `Special::PlayerColor.name()`, which shares the texture and vertex range of the original. Triangles
+	/// are reordered within the range of the original material in each LOD level, so the ranges of other
+	/// materials are unaffected as long as they do not overlap it. Returns the index of the new material, or
+	/// `None` if no triangles were selected.
+	pub fn retarget<F>(&mut self, material: usize, name: &str, mut selected: F) -> Option<usize> where F: FnMut(usize, &Triangle) -> bool {
+		let mut moved_any = false;
+		let mut ranges = Vec::with_capacity(self.lod_levels.len());
+
+		for (lod, triangles) in self.lod_levels.iter_mut().enumerate() {
+			let selection = match self.materials.get_mut(material).and_then(|material| material.triangles.get_mut(lod)) {
+				Some(selection) => selection,
+				None => {
+					ranges.push(TriangleSelection { offset: 0, len: 0 });
+					continue;
+				}
+			};
+
+			let start = (selection.offset as usize).min(triangles.len());
+			let end = (start + selection.len as usize).min(triangles.len());
+
+			let (moved, kept): (Vec<Triangle>, Vec<Triangle>) = triangles[start..end].iter().partition(|triangle| selected(lod, triangle));
+
+			if moved.is_empty() {
+				ranges.push(TriangleSelection { offset: 0, len: 0 });
+				continue;
+			}
+
+			triangles[start..start + kept.len()].copy_from_slice(&kept);
+			triangles[start + kept.len()..end].copy_from_slice(&moved);
+
+			selection.offset = start as u32;
+			selection.len = kept.len() as u32;
+
+			moved_any = true;
+			ranges.push(TriangleSelection { offset: (start + kept.len()) as u32, len: moved.len() as u32 });
+		}
+
+		if !moved_any {
+			return None;
+		}
+
+		let original = &self.materials[material];
+
+		self.materials.push(Material {
+			name: name.to_string(),
+			texture: original.texture,
+			triangles: ranges,
+			vertex_offset: original.vertex_offset,
+			vertex_count: original.vertex_count,
+			texture_name: original.texture_name.clone()
+		});
+
+		Some(self.materials.len() - 1)
+	}
+
+	/// Reads a model without its header, naming each field as it is read. `Model::read` and `trace` both use this,
+	/// so that traces always follow the layout that is actually read.
+	pub fn read_fields<F>(r: &mut F) -> io::Result<(Self, NodeData<'static>)> where F: Fields {
+		let mut progress = Progress::new();
+		Self::read_progress(r, &mut progress)?;
+
+		let node = NodeData {
+			additional_models: progress.quantities.map(|quantities| quantities.additional_models).unwrap_or(0),
+			name: Cow::Owned(progress.name.unwrap_or_default())
+		};
+
+		Ok((progress.model, node))
+	}
+
+	/// Reads a model like `read_fields`, adding every part to `progress` as soon as it has been read. Recovery uses
+	/// this to keep whatever came before a failure.
+	pub(crate) fn read_progress<F>(r: &mut F, progress: &mut Progress) -> io::Result<()> where F: Fields {
+		let quantities = r.group("quantities", Quantities::read_fields)?;
+		progress.quantities = Some(quantities);
+
+		progress.name = Some(r.encoded("name")?);
+
+		let model = &mut progress.model;
+		model.center = r.encoded("center")?;
+
+		model.lod_levels.reserve((quantities.lod_levels as usize).min(MAX_PREALLOCATION));
+		for i in 0..quantities.lod_levels {
+			model.lod_levels.push(r.group(format_args!("lod_levels[{}]", i), |r| {
+				let count = r.u32("count")?;
+
+				let mut triangles = preallocate(count as usize);
+				for j in 0..count {
+					triangles.push(r.group(format_args!("[{}]", j), |r| Ok((r.u32("0")?, r.u32("1")?, r.u32("2")?)))?);
+				}
+
+				Ok(triangles)
+			})?);
+		}
+
+		model.materials.reserve((quantities.materials as usize).min(MAX_PREALLOCATION));
+		for i in 0..quantities.materials {
+			model.materials.push(r.group(format_args!("materials[{}]", i), |r| Material::read_fields(r, quantities.lod_levels as usize))?);
+		}
+
+		model.tag_points.reserve((quantities.tag_points as usize).min(MAX_PREALLOCATION));
+		for i in 0..quantities.tag_points {
+			model.tag_points.push(r.encoded(format_args!("tag_points[{}]", i))?);
+		}
+
+		model.frames.reserve((quantities.frames as usize).min(MAX_PREALLOCATION));
+		for i in 0..quantities.frames {
+			model.frames.push(r.group(format_args!("frames[{}]", i), |r| Frame::read_fields(r, quantities.vertices as usize, quantities.tag_points as usize))?);
+		}
+
+		Ok(())
+	}
+
+	pub(crate) fn quantities(&self, additional_models: u32) -> Result<Quantities, &'static str> {
+		if self.materials.is_empty() {
 			return Err("A model must have at least 1 material");
 		}
 
-		if self.lod_levels.len() == 0 {
+		if self.lod_levels.is_empty() {
 			return Err("A model must have at least 1 LOD level");
 		}
 
-		if self.frames.len() == 0 {
+		if self.frames.is_empty() {
 			return Err("A model must have at least 1 frame")
 		}
 
@@ -85,54 +252,8 @@ impl V2 {
 impl Model for V2 {
 	const HEADER: ModelHeader = ModelHeader { magic: MAGIC, major: 2, minor: 0 };
 
-	fn read<R>(r: &mut R) -> io::Result<(Self, NodeData)> where R: Read {
-		let quantities = Quantities::read(r)?;
-
-		let node = NodeData {
-			additional_models: quantities.additional_models,
-			name: Cow::Owned(String::read(r)?)
-		};
-
-		let center = Point3::read(r)?;
-
-		let mut lod_levels = Vec::with_capacity(quantities.lod_levels as usize);
-		for _ in 0..lod_levels.capacity() {
-			let count = r.read_u32::<LittleEndian>()?;
-
-			let mut triangles = Vec::with_capacity(count as usize);
-			for _ in 0..count {
-				triangles.push((
-					r.read_u32::<LittleEndian>()?,
-					r.read_u32::<LittleEndian>()?,
-					r.read_u32::<LittleEndian>()?
-				));
-			}
-
-			lod_levels.push(triangles);
-		}
-
-		let mut materials = Vec::with_capacity(quantities.materials as usize);
-		for _ in 0..materials.capacity() {
-			materials.push(Material::read(r, lod_levels.len())?);
-		}
-
-		let mut tag_points = Vec::with_capacity(quantities.tag_points as usize);
-		for _ in 0..tag_points.capacity() {
-			tag_points.push(String::read(r)?);
-		}
-
-		let mut frames = Vec::with_capacity(quantities.frames as usize);
-		for _ in 0..frames.capacity() {
-			frames.push(Frame::read(r, quantities.vertices as usize, tag_points.len())?);
-		}
-
-		Ok((V2 {
-			center,
-			lod_levels,
-			materials,
-			tag_points,
-			frames
-		}, node ))
+	fn read<R>(r: &mut R) -> io::Result<(Self, NodeData<'_>)> where R: Read {
+		Self::read_fields(&mut Untraced(r))
 	}
 
 	fn write<W>(&self, w: &mut W, node: NodeData) -> io::Result<()> where W: Write {
@@ -171,8 +292,9 @@ impl Model for V2 {
 
 /// A material to be applied to vertices. Contains special names, the texture, and target vertices / triangles.
 /// The name of the material may give it special meaning depending on the context. For example, the "player color" material
-/// is used to render the player color.
+/// is used to render the player color. See `Material::special` for the names that are known.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Material {
 	/// A name. Empire Earth does not appear to care about the value.
 	pub name: String,
@@ -191,20 +313,25 @@ pub struct Material {
 
 impl Material {
 	pub fn read<R>(r: &mut R, lod_levels: usize) -> io::Result<Self> where R: Read {
+		Self::read_fields(&mut Untraced(r), lod_levels)
+	}
+
+	/// Reads a material, naming each field. Shared by V2 and V5.
+	pub fn read_fields<F>(r: &mut F, lod_levels: usize) -> io::Result<Self> where F: Fields {
 		Ok(Material {
-			name: String::read(r)?,
-			texture: r.read_u32::<LittleEndian>()?,
+			name: r.encoded("name")?,
+			texture: r.u32("texture")?,
 			triangles: {
-				let mut ranges = Vec::with_capacity(lod_levels);
-				for _ in 0..lod_levels {
-					ranges.push(TriangleSelection::read(r)?);
+				let mut ranges = preallocate(lod_levels);
+				for i in 0..lod_levels {
+					ranges.push(r.group(format_args!("triangles[{}]", i), TriangleSelection::read_fields)?);
 				}
 
 				ranges
 			},
-			vertex_offset: r.read_u32::<LittleEndian>()?,
-			vertex_count: r.read_u32::<LittleEndian>()?,
-			texture_name: String::read(r)?
+			vertex_offset: r.u32("vertex_offset")?,
+			vertex_count: r.u32("vertex_count")?,
+			texture_name: r.encoded("texture_name")?
 		})
 	}
 
@@ -222,10 +349,42 @@ impl Material {
 
 		Ok(())
 	}
+
+	/// The special meaning given to this material by its name, if any.
+	pub fn special(&self) -> Option<Special> {
+		Special::classify(&self.name)
+	}
+}
+
+/// A meaning that Empire Earth gives to materials with certain names. Only player color is known so far.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Special {
+	/// Tinted with the color of the player that owns the unit.
+	PlayerColor
+}
+
+impl Special {
+	/// Classifies a material name, ignoring case, spaces, underscores and the spelling of "colour".
+	pub fn classify(name: &str) -> Option<Self> {
+		let name: String = name.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect();
+
+		match name.as_str() {
+			"playercolor" | "playercolour" | "teamcolor" | "teamcolour" => Some(Special::PlayerColor),
+			_ => None
+		}
+	}
+
+	/// The name that models give materials with this meaning.
+	pub fn name(self) -> &'static str {
+		match self {
+			Special::PlayerColor => "player color"
+		}
+	}
 }
 
 /// Selects a range of triangles.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TriangleSelection {
 	pub offset: u32,
 	pub len: u32
@@ -233,9 +392,13 @@ pub struct TriangleSelection {
 
 impl TriangleSelection {
 	pub fn read<R>(r: &mut R) -> io::Result<Self> where R: Read {
+		Self::read_fields(&mut Untraced(r))
+	}
+
+	pub fn read_fields<F>(r: &mut F) -> io::Result<Self> where F: Fields {
 		Ok(TriangleSelection {
-			offset: r.read_u32::<LittleEndian>()?,
-			len: r.read_u32::<LittleEndian>()?
+			offset: r.u32("offset")?,
+			len: r.u32("len")?
 		})
 	}
 
@@ -249,6 +412,7 @@ impl TriangleSelection {
 /// Includes the AABB and radius for physics, the vertices, tag point positions, and a relative transform to be applied before rendering.
 /// This is made up entirely of 32-bit floating point data.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Frame {
 	pub vertices:   Vec<Vertex>,
 	pub tag_points: Vec<Point3<f32>>,
@@ -274,30 +438,42 @@ impl Frame {
 		}
 	}
 
+	/// Splits the transform into translation, rotation and scale. Use `Decomposition::matrix` to write it back.
+	pub fn decompose_transform(&self) -> Decomposition {
+		Decomposition::new(self.transform)
+	}
+
 	pub fn read<R>(r: &mut R, vertex_count: usize, tag_point_count: usize) -> io::Result<Self> where R: Read {
-		let radius = r.read_f32::<LittleEndian>()?;
+		Self::read_fields(&mut Untraced(r), vertex_count, tag_point_count)
+	}
+
+	pub fn read_fields<F>(r: &mut F, vertex_count: usize, tag_point_count: usize) -> io::Result<Self> where F: Fields {
+		let radius = r.f32("radius")?;
 
 		Ok(Frame {
 			vertices: {
-				let mut vertices = Vec::with_capacity(vertex_count);
-				for _ in 0..vertex_count {
-					vertices.push(Vertex::read(r)?);
+				let mut vertices = preallocate(vertex_count);
+				for i in 0..vertex_count {
+					vertices.push(r.group(format_args!("vertices[{}]", i), Vertex::read_fields)?);
 				}
 
 				vertices
 			},
 			tag_points: {
-				let mut tag_points = Vec::with_capacity(tag_point_count);
-				for _ in 0..tag_point_count {
-					tag_points.push(Point3::read(r)?);
+				let mut tag_points = preallocate(tag_point_count);
+				for i in 0..tag_point_count {
+					tag_points.push(r.encoded(format_args!("tag_points[{}]", i))?);
 				}
 
 				tag_points
 			},
-			transform: Matrix4::read(r)?,
+			transform: r.encoded("transform")?,
 			collider: Collider {
 				radius,
-				aabb: Aabb::read(r)?
+				aabb: r.group("aabb", |r| Ok(Aabb {
+					lower: r.encoded("lower")?,
+					upper: r.encoded("upper")?
+				}))?
 			}
 		})
 	}
@@ -320,6 +496,7 @@ impl Frame {
 
 /// A single vertex. Contains the position, a relevant vertex normal, and the position on the material's texture.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Vertex {
 	pub position: Point3<f32>,
 	pub normal:   Vector3<f32>,
@@ -328,10 +505,14 @@ pub struct Vertex {
 
 impl Vertex {
 	pub fn read<R>(r: &mut R) -> io::Result<Self> where R: Read {
+		Self::read_fields(&mut Untraced(r))
+	}
+
+	pub fn read_fields<F>(r: &mut F) -> io::Result<Self> where F: Fields {
 		Ok(Vertex {
-			position: Point3::read(r)?,
-			normal: Vector3::read(r)?,
-			texture: Point2::read(r)?
+			position: r.encoded("position")?,
+			normal: r.encoded("normal")?,
+			texture: r.encoded("texture")?
 		})
 	}
 
@@ -340,4 +521,72 @@ impl Vertex {
 		self.normal.write(w)?;
 		self.texture.write(w)
 	}
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+	use byteorder::{WriteBytesExt, LittleEndian};
+	use {Encode, Model, Scene};
+	use samples::v2_scene;
+	use super::{V2, Special};
+
+	#[test]
+	fn huge_counts_in_a_tiny_file_fail_without_allocating() {
+		let mut data = Vec::new();
+		V2::HEADER.write(&mut data).unwrap();
+
+		// Triangles, vertices, tag points, materials, frames, additional models and LOD levels.
+		for &count in &[0, u32::MAX, u32::MAX, 0, 1, 0, 0] {
+			data.write_u32::<LittleEndian>(count).unwrap();
+		}
+
+		"".to_string().write(&mut data).unwrap();
+		data.extend_from_slice(&[0; 16]);
+
+		assert!(Scene::<V2>::read(&mut Cursor::new(data)).is_err());
+	}
+
+	#[test]
+	fn retarget_moves_selected_triangles_to_the_end_of_the_range() {
+		let mut model = v2_scene().model;
+		let material = model.retarget(0, Special::PlayerColor.name(), |_, triangle| triangle.0 == 0).unwrap();
+
+		let ranges = |material: usize| -> Vec<(u32, u32)> {
+			model.materials[material].triangles.iter().map(|selection| (selection.offset, selection.len)).collect()
+		};
+
+		assert_eq!(material, 1);
+		assert_eq!(model.lod_levels, [vec![(1, 2, 3), (0, 1, 2)], vec![(0, 1, 2)]]);
+		assert_eq!(ranges(0), [(0, 1), (0, 0)]);
+		assert_eq!(ranges(1), [(1, 1), (0, 1)]);
+
+		let (original, moved) = (&model.materials[0], &model.materials[1]);
+
+		assert_eq!(moved.special(), Some(Special::PlayerColor));
+		assert_eq!((moved.texture, moved.vertex_offset, moved.vertex_count), (original.texture, original.vertex_offset, original.vertex_count));
+		assert_eq!(moved.texture_name, original.texture_name);
+	}
+
+	#[test]
+	fn retarget_without_a_selection_changes_nothing() {
+		let mut model = v2_scene().model;
+
+		assert_eq!(model.retarget(0, "unused", |_, _| false), None);
+		assert_eq!(model.retarget(5, "unused", |_, _| true), None);
+		assert_eq!(model.materials.len(), 1);
+		assert_eq!(model.lod_levels, [vec![(0, 1, 2), (1, 2, 3)], vec![(0, 1, 2)]]);
+	}
+
+	#[test]
+	fn special_names_are_classified_loosely() {
+		for name in &["player color", "PlayerColour", "player_color", "Team Color", "TEAM-COLOUR"] {
+			assert_eq!(Special::classify(name), Some(Special::PlayerColor), "{}", name);
+		}
+
+		for name in &["", "player", "color", "player color 2", "playercolors"] {
+			assert_eq!(Special::classify(name), None, "{}", name);
+		}
+
+		assert_eq!(Special::classify(Special::PlayerColor.name()), Some(Special::PlayerColor));
+	}
+}
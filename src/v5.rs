@@ -1,11 +1,13 @@
 use std::io::{self, Read, Write};
-use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
-use {ModelHeader, Model, MAGIC, v2, Encode};
+use byteorder::{WriteBytesExt, LittleEndian};
+use {ModelHeader, Model, MAGIC, v2, Encode, preallocate};
 use cgmath::Point3;
 use scene::NodeData;
+use trace::{Fields, Untraced};
 use std::borrow::Cow;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Quantities {
 	unknown0: u32, // Probably total vertices / dynamic vertices
 	unknown1: u32, // Probably common vertices
@@ -18,16 +20,16 @@ pub struct Quantities {
 }
 
 impl Quantities {
-	fn read<R>(r: &mut R) -> io::Result<Self> where R: Read {
+	fn read_fields<F>(r: &mut F) -> io::Result<Self> where F: Fields {
 		Ok(Quantities {
-			unknown0:          r.read_u32::<LittleEndian>()?,
-			unknown1:          r.read_u32::<LittleEndian>()?,
-			tag_points:        r.read_u32::<LittleEndian>()?,
-			materials:         r.read_u32::<LittleEndian>()?,
-			frames:            r.read_u32::<LittleEndian>()?,
-			additional_models: r.read_u32::<LittleEndian>()?,
-			lod_levels:        r.read_u32::<LittleEndian>()?,
-			points:          r.read_u32::<LittleEndian>()?
+			unknown0:          r.u32("unknown0")?,
+			unknown1:          r.u32("unknown1")?,
+			tag_points:        r.u32("tag_points")?,
+			materials:         r.u32("materials")?,
+			frames:            r.u32("frames")?,
+			additional_models: r.u32("additional_models")?,
+			lod_levels:        r.u32("lod_levels")?,
+			points:            r.u32("points")?
 		})
 	}
 
@@ -44,6 +46,7 @@ impl Quantities {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct V5 {
 	pub quantities: Quantities,
 	pub center: Point3<f32>,
@@ -57,100 +60,89 @@ pub struct V5 {
 }
 
 impl V5 {
-	fn quantities(&self, additional_models: u32) -> Result<Quantities, &'static str> {
-		let mut quantities = self.quantities;
-
-		quantities.additional_models = additional_models;
-
-		Ok(quantities)
-	}
-}
-
-impl Model for V5 {
-	const HEADER: ModelHeader = ModelHeader { magic: MAGIC, major: 5, minor: 0 };
-
-	fn read<R>(r: &mut R) -> io::Result<(Self, NodeData)> where R: Read {
-		let quantities = Quantities::read(r)?;
+	/// Reads a model without its header, naming each field as it is read. `Model::read` and `trace` both use this,
+	/// so that traces always follow the layout that is actually read. The layout of frames is not known yet, so
+	/// models with frames cannot be read.
+	pub fn read_fields<F>(r: &mut F) -> io::Result<(Self, NodeData<'static>)> where F: Fields {
+		let quantities = r.group("quantities", Quantities::read_fields)?;
 		let lod_levels = quantities.lod_levels as usize;
 
 		let node = NodeData {
 			additional_models: quantities.additional_models,
-			name: Cow::Owned(String::read(r)?)
+			name: Cow::Owned(r.encoded("name")?)
 		};
 
 		Ok((V5 {
-			center: Point3::read(r)?,
+			center: r.encoded("center")?,
 			common_vertices: {
-				let len = r.read_u32::<LittleEndian>()?;
-				let mut common_vertices = Vec::with_capacity(len as usize);
+				let len = r.u32("common_vertices.len")?;
+				let mut common_vertices = preallocate(len as usize);
 
-				for _ in 0..len {
-					common_vertices.push(CommonVertex::read(r)?);
+				for i in 0..len {
+					common_vertices.push(r.group(format_args!("common_vertices[{}]", i), CommonVertex::read_fields)?);
 				}
 
 				common_vertices
 			},
 			lod_levels: {
-				let mut lod_levels = Vec::with_capacity(quantities.lod_levels as usize);
-				for _ in 0..lod_levels.capacity() {
-					let count = r.read_u32::<LittleEndian>()?;
-
-					let mut triangles = Vec::with_capacity(count as usize);
-					for _ in 0..count {
-						triangles.push((
-							r.read_u16::<LittleEndian>()?,
-							r.read_u16::<LittleEndian>()?,
-							r.read_u16::<LittleEndian>()?
-						));
-					}
-
-					lod_levels.push(triangles);
+				let mut lod_levels = preallocate(quantities.lod_levels as usize);
+				for i in 0..quantities.lod_levels {
+					lod_levels.push(r.group(format_args!("lod_levels[{}]", i), |r| {
+						let count = r.u32("count")?;
+
+						let mut triangles = preallocate(count as usize);
+						for j in 0..count {
+							triangles.push(r.group(format_args!("[{}]", j), |r| Ok((r.u16("0")?, r.u16("1")?, r.u16("2")?)))?);
+						}
+
+						Ok(triangles)
+					})?);
 				}
 
 				lod_levels
 			},
 			materials: {
-				let mut materials = Vec::with_capacity(quantities.materials as usize);
+				let mut materials = preallocate(quantities.materials as usize);
 
-				for _ in 0..quantities.materials {
-					materials.push(v2::Material::read(r, lod_levels)?);
+				for i in 0..quantities.materials {
+					materials.push(r.group(format_args!("materials[{}]", i), |r| v2::Material::read_fields(r, lod_levels))?);
 				}
 
 				materials
 			},
 			tag_points: {
-				let mut tag_points = Vec::with_capacity(quantities.tag_points as usize);
+				let mut tag_points = preallocate(quantities.tag_points as usize);
 
-				for _ in 0..quantities.tag_points {
-					tag_points.push(String::read(r)?);
+				for i in 0..quantities.tag_points {
+					tag_points.push(r.encoded(format_args!("tag_points[{}]", i))?);
 				}
 
 				tag_points
 			},
 			frames: {
-				let mut frames = Vec::with_capacity(quantities.frames as usize);
+				let mut frames = preallocate(quantities.frames as usize);
 
-				for _ in 0..quantities.frames {
-					frames.push(Frame::read(r)?);
+				for i in 0..quantities.frames {
+					frames.push(r.group(format_args!("frames[{}]", i), Frame::read_fields)?);
 				}
 
 				frames
 			},
 			points: {
-				let mut points = Vec::with_capacity(quantities.points as usize);
+				let mut points = preallocate(quantities.points as usize);
 
-				for _ in 0..quantities.points {
-					points.push(Point3::read(r)?);
+				for i in 0..quantities.points {
+					points.push(r.encoded(format_args!("points[{}]", i))?);
 				}
 
 				points
 			},
 			shadow: {
-				let len = r.read_u32::<LittleEndian>()?;
-				let mut edges = Vec::with_capacity(len as usize);
+				let len = r.u32("shadow.len")?;
+				let mut edges = preallocate(len as usize);
 
-				for _ in 0..len {
-					edges.push(ShadowEdge::read(r)?);
+				for i in 0..len {
+					edges.push(r.group(format_args!("shadow[{}]", i), ShadowEdge::read_fields)?);
 				}
 
 				edges
@@ -159,6 +151,22 @@ impl Model for V5 {
 		}, node))
 	}
 
+	fn quantities(&self, additional_models: u32) -> Result<Quantities, &'static str> {
+		let mut quantities = self.quantities;
+
+		quantities.additional_models = additional_models;
+
+		Ok(quantities)
+	}
+}
+
+impl Model for V5 {
+	const HEADER: ModelHeader = ModelHeader { magic: MAGIC, major: 5, minor: 0 };
+
+	fn read<R>(r: &mut R) -> io::Result<(Self, NodeData<'_>)> where R: Read {
+		Self::read_fields(&mut Untraced(r))
+	}
+
 	fn write<W>(&self, w: &mut W, node: NodeData) -> io::Result<()> where W: Write {
 		let quantities = self.quantities(node.additional_models).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
@@ -174,57 +182,49 @@ impl Model for V5 {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CommonVertex {
 	pub unknown0: [f32; 16],
 	pub unknown1: i32
 }
 
 impl CommonVertex {
-	fn read<R>(r: &mut R) -> io::Result<Self> where R: Read {
+	fn read_fields<F>(r: &mut F) -> io::Result<Self> where F: Fields {
+		let mut unknown0 = [0.0; 16];
+
+		for (i, value) in unknown0.iter_mut().enumerate() {
+			*value = r.f32(format_args!("unknown0[{}]", i))?;
+		}
+
 		Ok(CommonVertex {
-			unknown0: [
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?,
-				r.read_f32::<LittleEndian>()?
-			],
-			unknown1: r.read_i32::<LittleEndian>()?
+			unknown0,
+			unknown1: r.i32("unknown1")?
 		})
 	}
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Frame {
-	radius: f32
+	pub radius: f32
 	// TODO: Vertices, TagPoints, Mat4, Aabb, BumpMap
 }
 
 impl Frame {
-	fn read<R>(_r: &mut R) -> io::Result<Self> where R: Read {
-		unimplemented!()
+	fn read_fields<F>(_r: &mut F) -> io::Result<Self> where F: Fields {
+		Err(io::Error::new(io::ErrorKind::InvalidData, "The layout of V5 frames is not known yet"))
 	}
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ShadowEdge {
-	unknown0: u32,
-	unknown1: [u16; 4]
+	pub unknown0: u32,
+	pub unknown1: [u16; 4]
 }
 
 impl ShadowEdge {
-	fn read<R>(_r: &mut R) -> io::Result<Self> where R: Read {
-		unimplemented!()
+	fn read_fields<F>(_r: &mut F) -> io::Result<Self> where F: Fields {
+		Err(io::Error::new(io::ErrorKind::InvalidData, "The layout of V5 shadow edges is not known yet"))
 	}
 }
\ No newline at end of file